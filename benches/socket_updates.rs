@@ -0,0 +1,64 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use node_engine::ui::*;
+use node_engine::*;
+
+const SOCKETS_PER_NODE: u32 = 8;
+
+fn build_sockets(nodes: usize) -> Vec<Vec<NodeSocket>> {
+  (0..nodes)
+    .map(|_| {
+      let node = uuid::Uuid::new_v4();
+      (0..SOCKETS_PER_NODE)
+        .map(|idx| NodeSocket::new(NodeSocketId::input(node, idx), false, DataType::F32, None))
+        .collect()
+    })
+    .collect()
+}
+
+fn new_meta() -> NodeGraphMeta {
+  let meta = NodeGraphMeta::default();
+  meta.update(emath::vec2(5000., 5000.), emath::Vec2::ZERO, 0.5);
+  meta
+}
+
+fn bench_socket_updates(c: &mut Criterion) {
+  let mut group = c.benchmark_group("Socket updates");
+  for nodes in [100, 1000, 5000] {
+    let sockets = build_sockets(nodes);
+    let pos = egui::pos2(100., 100.);
+    group.bench_with_input(
+      BenchmarkId::new("lock per socket", nodes),
+      &sockets,
+      |b, sockets| {
+        let meta = new_meta();
+        b.iter(|| {
+          for node in sockets {
+            for socket in node {
+              meta.update_node_socket(&mut socket.clone(), pos);
+            }
+          }
+        })
+      },
+    );
+    group.bench_with_input(
+      BenchmarkId::new("batch per node", nodes),
+      &sockets,
+      |b, sockets| {
+        let meta = new_meta();
+        b.iter(|| {
+          for node in sockets {
+            meta.batch_sockets(|| {
+              for socket in node {
+                meta.update_node_socket(&mut socket.clone(), pos);
+              }
+            });
+          }
+        })
+      },
+    );
+  }
+  group.finish();
+}
+
+criterion_group!(benches, bench_socket_updates);
+criterion_main!(benches);
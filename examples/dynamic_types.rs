@@ -0,0 +1,64 @@
+use node_engine::*;
+
+fn main() {
+  env_logger::init();
+  let native_options = eframe::NativeOptions {
+    viewport: egui::ViewportBuilder::default().with_inner_size([1000.0, 600.0]),
+    ..Default::default()
+  };
+  eframe::run_native(
+    "Dynamic types",
+    native_options,
+    Box::new(|cc| Ok(Box::new(MyEguiApp::new(cc)))),
+  )
+  .expect("ok");
+}
+
+/// Build two constant inputs (Vec2 and Vec3) feeding a chain of dynamic `Add` nodes.
+///
+/// Re-connect the first `Add` node's input between the two constants to see the
+/// socket and connection colors of the whole chain change on the next frame.
+fn build_graph(reg: &NodeRegistry, chain_len: usize) -> anyhow::Result<NodeGraph> {
+  let mut graph = NodeGraph::new();
+
+  let vec2 = graph.add(reg.new_by_name("Vector 2")?);
+  graph.add(reg.new_by_name("Vector 3")?);
+
+  let add = reg.new_by_name("Add")?;
+  let mut prev = vec2;
+  for _ in 0..chain_len {
+    let id = graph.add(add.duplicate());
+    graph.set_node_input(id, "A", Input::from(prev))?;
+    graph.set_node_input(id, "B", Input::from(0.1))?;
+    prev = id;
+  }
+
+  let output_id = graph.add(reg.new_by_name("Fragment")?);
+  graph.set_node_input(output_id, "Color", Input::from(prev))?;
+  graph.set_output(Some(output_id));
+  graph.auto_layout();
+
+  Ok(graph)
+}
+
+struct MyEguiApp {
+  editor: NodeGraphEditor,
+}
+
+impl MyEguiApp {
+  fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    let reg = NodeRegistry::build();
+    let mut editor = NodeGraphEditor::new();
+    editor.graph = build_graph(&reg, 4).expect("built graph");
+    Self { editor }
+  }
+}
+
+impl eframe::App for MyEguiApp {
+  fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+    if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+      ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+    }
+    self.editor.show(ctx);
+  }
+}
@@ -1,13 +1,50 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 
 use anyhow::{anyhow, Result};
 
 use crate::graph::*;
 use crate::node::*;
+use crate::profile::*;
+use crate::template::*;
 use crate::values::*;
 
+/// Name of the code block for helper functions.  See `NodeGraphCompile::add_function`.
+pub const FUNCTIONS_BLOCK: &str = "functions";
+
+/// Context added to compile errors with the node that failed to compile.
+///
+/// Only the innermost failing node is recorded, use `{:#}` to format the full error or
+/// `downcast_ref::<NodeCompileError>()` to get the node.
+#[derive(Clone, Debug)]
+pub struct NodeCompileError {
+  pub node: NodeId,
+  pub name: String,
+}
+
+impl core::fmt::Display for NodeCompileError {
+  fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    write!(f, "in node '{}' ({})", self.name, self.node)
+  }
+}
+
+impl NodeCompileError {
+  /// Add the node context to `res`, unless the error already has it.
+  fn wrap<T>(res: Result<T>, graph: &NodeGraph, node: NodeId) -> Result<T> {
+    res.map_err(|err| {
+      if err.downcast_ref::<NodeCompileError>().is_some() {
+        return err;
+      }
+      let name = graph
+        .get(node)
+        .map(|n| n.display_name().to_string())
+        .unwrap_or_default();
+      err.context(NodeCompileError { node, name })
+    })
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct CompiledValue {
   pub value: String,
@@ -39,6 +76,7 @@ impl CompiledValue {
       // From I32
       (DataType::I32, DataType::U32) => format!("u32({})", self.value),
       (DataType::I32, DataType::F32) => format!("f32({})", self.value),
+      (DataType::I32, DataType::F16) => format!("f16({})", self.value),
       (DataType::I32, DataType::Vec2) => format!("vec2<f32>({})", self.value),
       (DataType::I32, DataType::Vec3) => format!("vec3<f32>({})", self.value),
       (DataType::I32, DataType::Vec4) => format!("vec4<f32>({})", self.value),
@@ -48,6 +86,7 @@ impl CompiledValue {
       // From U32
       (DataType::U32, DataType::I32) => format!("i32({})", self.value),
       (DataType::U32, DataType::F32) => format!("f32({})", self.value),
+      (DataType::U32, DataType::F16) => format!("f16({})", self.value),
       (DataType::U32, DataType::Vec2) => format!("vec2<f32>({})", self.value),
       (DataType::U32, DataType::Vec3) => format!("vec3<f32>({})", self.value),
       (DataType::U32, DataType::Vec4) => format!("vec4<f32>({})", self.value),
@@ -57,16 +96,28 @@ impl CompiledValue {
       // From F32
       (DataType::F32, DataType::I32) => format!("i32({})", self.value),
       (DataType::F32, DataType::U32) => format!("u32({})", self.value),
+      (DataType::F32, DataType::F16) => format!("f16({})", self.value),
       (DataType::F32, DataType::Vec2) => format!("vec2<f32>({})", self.value),
       (DataType::F32, DataType::Vec3) => format!("vec3<f32>({})", self.value),
       (DataType::F32, DataType::Vec4) => format!("vec4<f32>({})", self.value),
       (DataType::F32, DataType::Dynamic | DataType::DynamicVector) => {
         return Ok(());
       }
+      // From F16
+      (DataType::F16, DataType::I32) => format!("i32({})", self.value),
+      (DataType::F16, DataType::U32) => format!("u32({})", self.value),
+      (DataType::F16, DataType::F32) => format!("f32({})", self.value),
+      (DataType::F16, DataType::Vec2) => format!("vec2<f32>(f32({}))", self.value),
+      (DataType::F16, DataType::Vec3) => format!("vec3<f32>(f32({}))", self.value),
+      (DataType::F16, DataType::Vec4) => format!("vec4<f32>(f32({}))", self.value),
+      (DataType::F16, DataType::Dynamic | DataType::DynamicVector) => {
+        return Ok(());
+      }
       // From Vec2
       (DataType::Vec2, DataType::I32) => format!("i32({}.x)", self.value),
       (DataType::Vec2, DataType::U32) => format!("u32({}.x)", self.value),
       (DataType::Vec2, DataType::F32) => format!("f32({}.x)", self.value),
+      (DataType::Vec2, DataType::F16) => format!("f16({}.x)", self.value),
       (DataType::Vec2, DataType::Vec3) => format!("vec3<f32>({}.xy, 0.)", self.value),
       (DataType::Vec2, DataType::Vec4) => format!("vec4<f32>({}.xy, 0., 1.)", self.value),
       (DataType::Vec2, DataType::Dynamic | DataType::DynamicVector) => {
@@ -76,6 +127,7 @@ impl CompiledValue {
       (DataType::Vec3, DataType::I32) => format!("i32({}.x)", self.value),
       (DataType::Vec3, DataType::U32) => format!("u32({}.x)", self.value),
       (DataType::Vec3, DataType::F32) => format!("f32({}.x)", self.value),
+      (DataType::Vec3, DataType::F16) => format!("f16({}.x)", self.value),
       (DataType::Vec3, DataType::Vec2) => format!("vec2<f32>({}.xy)", self.value),
       (DataType::Vec3, DataType::Vec4) => format!("vec4<f32>({}.xyz, 1.)", self.value),
       (DataType::Vec3, DataType::Dynamic | DataType::DynamicVector) => {
@@ -85,8 +137,9 @@ impl CompiledValue {
       (DataType::Vec4, DataType::I32) => format!("i32({}.x)", self.value),
       (DataType::Vec4, DataType::U32) => format!("u32({}.x)", self.value),
       (DataType::Vec4, DataType::F32) => format!("f32({}.x)", self.value),
+      (DataType::Vec4, DataType::F16) => format!("f16({}.x)", self.value),
       (DataType::Vec4, DataType::Vec2) => format!("vec2<f32>({}.xy)", self.value),
-      (DataType::Vec4, DataType::Vec3) => format!("vec4<f32>({}.xyz)", self.value),
+      (DataType::Vec4, DataType::Vec3) => format!("vec3<f32>({}.xyz)", self.value),
       (DataType::Vec4, DataType::Dynamic | DataType::DynamicVector) => {
         return Ok(());
       }
@@ -137,10 +190,22 @@ impl CompiledValue {
 pub enum NodeOutput {
   /// Only generated if it is used.
   LazyCode(String, String, DataType),
+  /// A value known when compiling, generated as a `const` when used.
+  LazyConst(String, Value),
   /// Already generated, so reference the variable.
   Compiled(CompiledValue),
 }
 
+impl NodeOutput {
+  pub fn data_type(&self) -> DataType {
+    match self {
+      Self::LazyCode(_, _, dt) => *dt,
+      Self::LazyConst(_, value) => value.data_type(),
+      Self::Compiled(value) => value.dt,
+    }
+  }
+}
+
 /// Code block Id
 #[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
 pub struct CodeBlockId(u32);
@@ -150,14 +215,20 @@ pub struct CodeBlock {
   code: Vec<String>,
   variables: IndexMap<String, DataType>,
   outputs: IndexMap<OutputId, NodeOutput>,
+  /// Values of the `const` variables.
+  constants: HashMap<String, Value>,
   counter: usize,
 }
 
 impl CodeBlock {
-  pub fn add_local(&mut self, prefix: &str, code: String, dt: DataType) -> String {
+  /// A new unique variable name starting with `prefix`.
+  fn next_name(&mut self, prefix: &str) -> String {
     self.counter += 1;
-    let idx = self.counter;
-    let name = format!("{prefix}_{idx}");
+    format!("{}_{}", sanitize_ident(prefix), self.counter)
+  }
+
+  pub fn add_local(&mut self, prefix: &str, code: String, dt: DataType) -> String {
+    let name = self.next_name(prefix);
     self.append(format!(
       r#"
   let {name} = {code};"#
@@ -166,12 +237,48 @@ impl CodeBlock {
     name
   }
 
+  /// Same as `add_local`, but declares a mutable `var`.  Needed for dynamic indexing of arrays.
+  pub fn add_var(&mut self, prefix: &str, code: String, dt: DataType) -> String {
+    let name = self.next_name(prefix);
+    self.append(format!(
+      r#"
+  var {name} = {code};"#
+    ));
+    self.variables.insert(name.clone(), dt);
+    name
+  }
+
+  /// Same as `add_local`, but declares a `const`.  Its value can be looked up with
+  /// `const_value`, so nodes can specialize their code on it.
+  pub fn add_const(&mut self, prefix: &str, value: Value) -> Result<String> {
+    let code = value.compile()?;
+    let name = self.next_name(prefix);
+    self.append(format!(
+      r#"
+  const {name} = {code};"#
+    ));
+    self.variables.insert(name.clone(), code.dt);
+    self.constants.insert(name.clone(), value);
+    Ok(name)
+  }
+
+  /// The value of the `const` variable `name`.
+  pub fn const_value(&self, name: &str) -> Option<&Value> {
+    self.constants.get(name)
+  }
+
   pub fn add_output(&mut self, id: OutputId, prefix: &str, code: String, dt: DataType) {
     self
       .outputs
       .insert(id, NodeOutput::LazyCode(prefix.to_string(), code, dt));
   }
 
+  pub fn add_const_output(&mut self, id: OutputId, prefix: &str, value: Value) {
+    self
+      .outputs
+      .insert(id, NodeOutput::LazyConst(prefix.to_string(), value));
+  }
+
   pub fn resolve_output(&mut self, id: OutputId) -> Result<CompiledValue> {
     let output = self
       .outputs
@@ -186,6 +293,13 @@ impl CodeBlock {
         self.outputs.insert(id, NodeOutput::Compiled(value.clone()));
         Ok(value)
       }
+      NodeOutput::LazyConst(prefix, value) => {
+        let dt = value.data_type();
+        let name = self.add_const(&prefix, value)?;
+        let value = CompiledValue { value: name, dt };
+        self.outputs.insert(id, NodeOutput::Compiled(value.clone()));
+        Ok(value)
+      }
       NodeOutput::Compiled(value) => Ok(value),
     };
     value
@@ -203,16 +317,92 @@ impl CodeBlock {
     self.code.push(code);
   }
 
+  /// Append `code` only if it hasn't already been appended.
+  pub fn append_once(&mut self, code: String) {
+    if !self.code.contains(&code) {
+      self.code.push(code);
+    }
+  }
+
   pub fn clear(&mut self) {
     self.code.clear();
     self.variables.clear();
     self.outputs.clear();
+    self.constants.clear();
     self.counter = 0;
   }
 
   pub fn dump(&self) -> String {
     self.code.join("")
   }
+
+  /// The data type of a node output compiled in this block.
+  pub fn output_type(&self, id: OutputId) -> Option<DataType> {
+    self.outputs.get(&id).map(|output| output.data_type())
+  }
+
+  /// The number of variables (`let`, `var` and `const`) generated in this block.
+  pub fn local_count(&self) -> usize {
+    self.variables.len()
+  }
+
+  /// Returns true if a variable of data type `dt` was generated in this block.
+  pub fn uses_type(&self, dt: DataType) -> bool {
+    self.variables.values().any(|v| *v == dt)
+  }
+}
+
+/// Map `prefix` to a valid WGSL identifier prefix.
+///
+/// Characters that aren't ASCII letters, digits or `_` are replaced with `_`, runs of `_`
+/// are merged (identifiers can't start with `__`) and a leading digit gets a `v_` prefix.
+/// An empty prefix becomes `v`.
+/// The result isn't checked against the WGSL keywords, it should be followed by a suffix.
+pub fn sanitize_ident(prefix: &str) -> String {
+  let mut ident = String::with_capacity(prefix.len());
+  for c in prefix.chars() {
+    let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+    if c == '_' && (ident.is_empty() || ident.ends_with('_')) {
+      continue;
+    }
+    ident.push(c);
+  }
+  while ident.ends_with('_') {
+    ident.pop();
+  }
+  if ident.is_empty() {
+    ident.push('v');
+  } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+    ident.insert_str(0, "v_");
+  }
+  ident
+}
+
+/// The compiled outputs of a node, reused by `NodeGraphCompile::recompile`.
+#[derive(Clone, Debug)]
+struct CachedNode {
+  /// Only nodes that don't emit code directly can be cached.
+  pure: bool,
+  /// Outputs resolved by the node and the variable they resolved to.
+  resolved: Vec<(OutputId, String)>,
+  /// Lazy outputs added by the node.
+  outputs: Vec<(OutputId, String, String, DataType)>,
+  /// Constant outputs added by the node.
+  consts: Vec<(OutputId, String, Value)>,
+  /// Extensions enabled by the node.
+  enables: Vec<String>,
+}
+
+impl Default for CachedNode {
+  fn default() -> Self {
+    Self {
+      pure: true,
+      resolved: Vec::new(),
+      outputs: Vec::new(),
+      consts: Vec::new(),
+      enables: Vec::new(),
+    }
+  }
 }
 
 #[derive(Default, Debug)]
@@ -221,8 +411,63 @@ pub struct NodeGraphCompile {
   blocks: IndexMap<CodeBlockId, CodeBlock>,
   names: HashMap<String, CodeBlockId>,
   block_order: Vec<CodeBlockId>,
+  /// Blocks listed here are dumped first, in this order.
+  dump_order: Vec<String>,
+  /// Names of the helper functions already added.
+  functions: HashSet<String>,
   stack: Vec<CodeBlockId>,
   compiled: HashMap<NodeId, bool>,
+  cache: HashMap<NodeId, CachedNode>,
+  recording: Vec<CachedNode>,
+  template: ShaderTemplate,
+  profile: Option<NodeProfile>,
+  output_format: OutputFormat,
+  depth: usize,
+  max_depth: Option<usize>,
+  /// The entry-points added by the output nodes.
+  entry_points: IndexMap<String, ShaderStage>,
+  bindings: Vec<ShaderBinding>,
+  /// Extensions for the `enable` directives.
+  enables: IndexSet<String>,
+}
+
+/// Size of the generated code, to estimate the cost of a shader.  See `NodeGraphCompile::metrics`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompileMetrics {
+  /// The number of variables in each block, in dump order.
+  pub locals: Vec<(String, usize)>,
+  /// Non-empty lines of the generated code.
+  pub lines: usize,
+  /// Calls to the `textureSample*` functions.
+  pub texture_samples: usize,
+}
+
+impl CompileMetrics {
+  /// The number of variables in all blocks.
+  pub fn total_locals(&self) -> usize {
+    self.locals.iter().map(|(_, count)| count).sum()
+  }
+}
+
+impl std::fmt::Display for CompileMetrics {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "lines: {}, locals: {}, texture samples: {}",
+      self.lines,
+      self.total_locals(),
+      self.texture_samples
+    )
+  }
+}
+
+/// The compiled shader and the metadata needed to build a pipeline for it.
+#[derive(Clone, Debug)]
+pub struct ShaderArtifact {
+  pub source: String,
+  pub entry_points: Vec<(String, ShaderStage)>,
+  pub bindings: Vec<ShaderBinding>,
+  pub output_format: OutputFormat,
 }
 
 impl NodeGraphCompile {
@@ -230,6 +475,50 @@ impl NodeGraphCompile {
     Self::default()
   }
 
+  /// Use `template` for the shader boilerplate emitted by the output nodes.
+  pub fn with_template(template: ShaderTemplate) -> Self {
+    Self {
+      template,
+      ..Self::default()
+    }
+  }
+
+  /// Enable/disable recording the time spent compiling each node type.
+  pub fn set_profiling(&mut self, enable: bool) {
+    if enable {
+      self.profile.get_or_insert_with(NodeProfile::new);
+    } else {
+      self.profile = None;
+    }
+  }
+
+  /// Limit how deep nested nodes are compiled.  Defaults to `DEFAULT_MAX_DEPTH`.
+  pub fn set_max_depth(&mut self, depth: usize) {
+    self.max_depth = Some(depth);
+  }
+
+  pub fn max_depth(&self) -> usize {
+    self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+  }
+
+  /// The recorded timings, if profiling is enabled.
+  pub fn profile(&self) -> Option<&NodeProfile> {
+    self.profile.as_ref()
+  }
+
+  pub fn template(&self) -> &ShaderTemplate {
+    &self.template
+  }
+
+  pub fn set_template(&mut self, template: ShaderTemplate) {
+    self.template = template;
+  }
+
+  /// The fragment output format of the last compiled graph.  See `NodeGraph::output_format`.
+  pub fn output_format(&self) -> OutputFormat {
+    self.output_format
+  }
+
   pub fn define_block(&mut self, name: &str) -> CodeBlockId {
     if let Some(&id) = self.names.get(name) {
       return id;
@@ -242,7 +531,20 @@ impl NodeGraphCompile {
     id
   }
 
+  /// Mark the node being compiled as not cacheable.  Nodes that have direct access
+  /// to the code blocks or compile nodes from another graph can't be replayed.
+  pub fn mark_impure(&mut self) {
+    if let Some(node) = self.recording.last_mut() {
+      node.pure = false;
+    }
+  }
+
   pub fn current_block(&mut self) -> Result<&mut CodeBlock> {
+    self.mark_impure();
+    self.block_mut()
+  }
+
+  fn block_mut(&mut self) -> Result<&mut CodeBlock> {
     self
       .stack
       .last()
@@ -251,6 +553,7 @@ impl NodeGraphCompile {
   }
 
   pub fn push(&mut self, id: CodeBlockId) {
+    self.mark_impure();
     self.stack.push(id);
   }
 
@@ -261,14 +564,79 @@ impl NodeGraphCompile {
   }
 
   pub fn pop(&mut self, expect: Option<CodeBlockId>) -> Result<Option<CodeBlockId>> {
+    self.mark_impure();
     let id = self.stack.pop();
     if id != expect {
-      Err(anyhow!("Stack corruption detected."))
+      Err(anyhow!(
+        "Stack corruption detected: expected {}, found {}.  Remaining stack: {}",
+        self.describe_block(expect),
+        self.describe_block(id),
+        self.describe_stack()
+      ))
     } else {
       Ok(id)
     }
   }
 
+  /// The name a block was defined with.
+  pub fn block_name(&self, id: CodeBlockId) -> Option<&str> {
+    self
+      .names
+      .iter()
+      .find_map(|(name, b)| (*b == id).then_some(name.as_str()))
+  }
+
+  fn describe_block(&self, id: Option<CodeBlockId>) -> String {
+    match id {
+      Some(id) => format!(
+        "\"{}\"({})",
+        self.block_name(id).unwrap_or("<unnamed>"),
+        id.0
+      ),
+      None => "no block".to_string(),
+    }
+  }
+
+  fn describe_stack(&self) -> String {
+    let blocks: Vec<String> = self
+      .stack
+      .iter()
+      .map(|id| self.describe_block(Some(*id)))
+      .collect();
+    format!("[{}]", blocks.join(", "))
+  }
+
+  /// Push the block `name` (defining it if needed), run `f` and pop the block.
+  ///
+  /// The block is popped even if `f` fails.  Blocks pushed by `f` without being
+  /// popped are also removed and reported as stack corruption.
+  pub fn with_block<R>(&mut self, name: &str, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+    let depth = self.stack.len();
+    let id = self.push_new_block(name);
+    let res = f(self);
+    let balanced = self.stack.len() == depth + 1 && self.stack.last() == Some(&id);
+    let stack = self.describe_stack();
+    self.stack.truncate(depth);
+    match res {
+      Ok(_) if !balanced => Err(anyhow!(
+        "Stack corruption detected: expected {} on top, stack: {stack}",
+        self.describe_block(Some(id))
+      )),
+      res => res,
+    }
+  }
+
+  /// The data type a node output was compiled to, after dynamic types are resolved.
+  ///
+  /// Returns `None` if the output wasn't compiled.
+  pub fn resolved_type(&self, id: OutputId) -> Option<DataType> {
+    self
+      .blocks
+      .values()
+      .rev()
+      .find_map(|block| block.output_type(id))
+  }
+
   pub fn get_block(&self, name: &str) -> Option<&CodeBlock> {
     self
       .names
@@ -277,6 +645,7 @@ impl NodeGraphCompile {
   }
 
   pub fn get_block_mut(&mut self, name: &str) -> Option<&mut CodeBlock> {
+    self.mark_impure();
     self
       .names
       .get(name)
@@ -289,6 +658,7 @@ impl NodeGraphCompile {
   }
 
   pub fn get_block_by_id_mut(&mut self, id: CodeBlockId) -> Option<&mut CodeBlock> {
+    self.mark_impure();
     self.blocks.get_mut(&id)
   }
 
@@ -297,6 +667,11 @@ impl NodeGraphCompile {
     Ok(block.add_local(prefix, code, dt))
   }
 
+  pub fn add_var(&mut self, prefix: &str, code: String, dt: DataType) -> Result<String> {
+    let block = self.current_block()?;
+    Ok(block.add_var(prefix, code, dt))
+  }
+
   pub fn add_output(
     &mut self,
     id: OutputId,
@@ -304,11 +679,38 @@ impl NodeGraphCompile {
     code: String,
     dt: DataType,
   ) -> Result<()> {
-    let block = self.current_block()?;
+    if let Some(node) = self.recording.last_mut() {
+      node
+        .outputs
+        .push((id, prefix.to_string(), code.clone(), dt));
+    }
+    let block = self.block_mut()?;
     block.add_output(id, prefix, code, dt);
     Ok(())
   }
 
+  /// Add an output with a value known when compiling.  It is generated as a `const`
+  /// and nodes using it can specialize on its value, see `const_value`.
+  pub fn add_const_output(&mut self, id: OutputId, prefix: &str, value: Value) -> Result<()> {
+    if let Some(node) = self.recording.last_mut() {
+      node.consts.push((id, prefix.to_string(), value.clone()));
+    }
+    let block = self.block_mut()?;
+    block.add_const_output(id, prefix, value);
+    Ok(())
+  }
+
+  /// The value of `value` if it is a constant generated by `add_const_output`.
+  pub fn const_value(&self, value: &CompiledValue) -> Option<Value> {
+    self
+      .stack
+      .iter()
+      .rev()
+      .filter_map(|id| self.blocks.get(id))
+      .find_map(|block| block.const_value(&value.value))
+      .cloned()
+  }
+
   pub fn append_code(&mut self, name: &str, code: String) -> Result<()> {
     match self.get_block_mut(name) {
       Some(block) => {
@@ -319,10 +721,68 @@ impl NodeGraphCompile {
     }
   }
 
+  pub fn append_code_once(&mut self, name: &str, code: String) -> Result<()> {
+    match self.get_block_mut(name) {
+      Some(block) => {
+        block.append_once(code);
+        Ok(())
+      }
+      None => Err(anyhow!("Undefined block: {name:?}")),
+    }
+  }
+
+  /// Set the order used by `dump`.  Blocks not listed are dumped afterwards in the
+  /// order they were defined.  Names of blocks that aren't defined are ignored.
+  pub fn set_block_order(&mut self, names: &[&str]) {
+    self.dump_order = names.iter().map(|name| name.to_string()).collect();
+  }
+
+  fn ordered_blocks(&self) -> Vec<CodeBlockId> {
+    let mut order: Vec<CodeBlockId> = self
+      .dump_order
+      .iter()
+      .filter_map(|name| self.names.get(name).copied())
+      .collect();
+    for id in &self.block_order {
+      if !order.contains(id) {
+        order.push(*id);
+      }
+    }
+    order
+  }
+
+  /// The names of the blocks in the order they are dumped.
+  pub fn block_order(&self) -> Vec<&str> {
+    let names: HashMap<CodeBlockId, &str> = self
+      .names
+      .iter()
+      .map(|(name, id)| (*id, name.as_str()))
+      .collect();
+    self
+      .ordered_blocks()
+      .into_iter()
+      .filter_map(|id| names.get(&id).copied())
+      .collect()
+  }
+
   pub fn dump(&self) -> String {
     let mut output = Vec::new();
-    for block in self.blocks.values() {
-      output.push(block.dump());
+    // Enable directives must come before any other code.
+    let mut enables = self.enables.clone();
+    if self
+      .blocks
+      .values()
+      .any(|block| block.uses_type(DataType::F16))
+    {
+      enables.insert("f16".to_string());
+    }
+    for extension in enables {
+      output.push(format!("enable {extension};\n"));
+    }
+    for id in self.ordered_blocks() {
+      if let Some(block) = self.blocks.get(&id) {
+        output.push(block.dump());
+      }
     }
     output.join("")
   }
@@ -331,20 +791,154 @@ impl NodeGraphCompile {
     for block in self.blocks.values_mut() {
       block.clear();
     }
+    self.functions.clear();
+    self.compiled.clear();
+    self.entry_points.clear();
+    self.bindings.clear();
+    self.enables.clear();
+  }
+
+  /// Add an `enable` directive for `extension` to the generated code.
+  pub fn enable(&mut self, extension: &str) {
+    if let Some(node) = self.recording.last_mut() {
+      node.enables.push(extension.to_string());
+    }
+    self.enables.insert(extension.to_string());
+  }
+
+  /// Enable the extensions needed by a literal `value` used in the generated code.
+  pub fn literal(&mut self, value: CompiledValue) -> CompiledValue {
+    if value.dt == DataType::F16 {
+      self.enable("f16");
+    }
+    value
+  }
+
+  /// Register a shader entry-point function.  Called by the output nodes.
+  pub fn add_entry_point(&mut self, name: &str, stage: ShaderStage) {
+    self.mark_impure();
+    self.entry_points.insert(name.to_string(), stage);
+  }
+
+  /// The registered entry-points, in the order they were added.
+  pub fn entry_points(&self) -> Vec<(String, ShaderStage)> {
+    self
+      .entry_points
+      .iter()
+      .map(|(name, stage)| (name.clone(), *stage))
+      .collect()
+  }
+
+  /// Register a resource binding used by the shader.
+  pub fn add_binding(&mut self, binding: ShaderBinding) {
+    self.mark_impure();
+    if !self.bindings.contains(&binding) {
+      self.bindings.push(binding);
+    }
+  }
+
+  pub fn bindings(&self) -> &[ShaderBinding] {
+    &self.bindings
+  }
+
+  /// Measure the generated code.
+  pub fn metrics(&self) -> CompileMetrics {
+    let code = self.dump();
+    let locals = self
+      .ordered_blocks()
+      .into_iter()
+      .filter_map(|id| {
+        let block = self.blocks.get(&id)?;
+        let name = self.block_name(id).unwrap_or("<unnamed>");
+        Some((name.to_string(), block.local_count()))
+      })
+      .collect();
+    CompileMetrics {
+      locals,
+      lines: code.lines().filter(|line| !line.trim().is_empty()).count(),
+      texture_samples: code.matches("textureSample").count(),
+    }
+  }
+
+  /// The compiled shader with its entry-points and bindings.
+  pub fn artifact(&self) -> ShaderArtifact {
+    ShaderArtifact {
+      source: self.dump(),
+      entry_points: self.entry_points(),
+      bindings: self.bindings.clone(),
+      output_format: self.output_format,
+    }
+  }
+
+  /// Add a helper function to the `functions` block.  Only the first function with
+  /// the same `name` is added.
+  ///
+  /// The `functions` block is defined if needed, after the `bindings` (or `imports`) block.
+  pub fn add_function(&mut self, name: &str, code: &str) -> Result<()> {
+    if !self.names.contains_key(FUNCTIONS_BLOCK) {
+      let id = self.define_block(FUNCTIONS_BLOCK);
+      self.block_order.retain(|b| *b != id);
+      let pos = ["bindings", "imports"]
+        .iter()
+        .filter_map(|name| self.names.get(*name))
+        .find_map(|b| self.block_order.iter().position(|o| o == b))
+        .map(|pos| pos + 1)
+        .unwrap_or(0);
+      self.block_order.insert(pos, id);
+    }
+    if self.functions.insert(name.to_string()) {
+      self.append_code(FUNCTIONS_BLOCK, code.to_string())?;
+    } else {
+      // The code was already emitted, but the node still depends on it.
+      self.mark_impure();
+    }
+    Ok(())
   }
 
   pub fn resolve_output(&mut self, graph: &NodeGraph, id: OutputId) -> Result<CompiledValue> {
     // Make sure the output node has been compiled.
     self.compile_node(graph, id.node)?;
-    let block = self.current_block()?;
-    block.resolve_output(id)
+    let block = self.block_mut()?;
+    let value = NodeCompileError::wrap(block.resolve_output(id), graph, id.node)?;
+    if let Some(node) = self.recording.last_mut() {
+      node.resolved.push((id, value.value.clone()));
+    }
+    Ok(value)
   }
 
   pub fn compile_graph(&mut self, graph: &NodeGraph) -> Result<()> {
-    let id = graph
-      .output()
-      .ok_or_else(|| anyhow!("Graph missing output node"))?;
-    self.compile_node(graph, id)
+    self.cache.clear();
+    self.compile_output(graph)
+  }
+
+  /// Recompile the graph, only nodes in `dirty` and the nodes downstream from them
+  /// are compiled again.  The other nodes reuse their cached outputs.
+  ///
+  /// The code blocks are cleared before compiling.
+  pub fn recompile(&mut self, graph: &NodeGraph, dirty: &HashSet<NodeId>) -> Result<()> {
+    for id in graph.downstream_nodes(dirty) {
+      self.cache.remove(&id);
+    }
+    self.clear();
+    self.compile_output(graph)
+  }
+
+  fn compile_output(&mut self, graph: &NodeGraph) -> Result<()> {
+    self.output_format = graph.output_format();
+    let outputs = graph.outputs();
+    if outputs.is_empty() {
+      return Err(anyhow!("Graph missing output node"));
+    }
+    for id in outputs.values() {
+      self.compile_node(graph, *id)?;
+    }
+    Ok(())
+  }
+
+  /// Returns true if node `id` was already compiled.  Each node is only compiled once,
+  /// even if multiple of its outputs are used.
+  pub fn is_compiled(&self, id: NodeId) -> bool {
+    self.compiled.get(&id).copied().unwrap_or_default()
   }
 
   pub fn compile_node(&mut self, graph: &NodeGraph, id: NodeId) -> Result<()> {
@@ -353,7 +947,654 @@ impl NodeGraphCompile {
       return Ok(());
     }
     let node = graph.get(id)?;
-    // compile node.
-    node.compile(graph, self, id)
+    if self.depth >= self.max_depth() {
+      return NodeCompileError::wrap(
+        Err(anyhow!(
+          "Maximum node depth ({}) exceeded",
+          self.max_depth()
+        )),
+        graph,
+        id,
+      );
+    }
+    // Record the outputs of the node for the next recompile.
+    self.recording.push(CachedNode::default());
+    let start = self.profile.as_mut().map(|profile| profile.start());
+    self.depth += 1;
+    let res = match self.cache.remove(&id) {
+      Some(cached) => self.replay_node(graph, id, cached),
+      // compile node.
+      None => node.compile(graph, self, id),
+    };
+    self.depth -= 1;
+    if let (Some(profile), Some(start)) = (self.profile.as_mut(), start) {
+      profile.finish(&node.def().name, start);
+    }
+    let cached = self.recording.pop().unwrap_or_default();
+    if res.is_ok() && cached.pure {
+      self.cache.insert(id, cached);
+    }
+    NodeCompileError::wrap(res, graph, id)
+  }
+
+  fn replay_node(&mut self, graph: &NodeGraph, id: NodeId, cached: CachedNode) -> Result<()> {
+    // Resolve the node's inputs again.  The cached code can only be reused if they
+    // resolve to the same variables, otherwise compile the node again.
+    for (output, old) in &cached.resolved {
+      let value = self.resolve_output(graph, *output)?;
+      if *old != value.value {
+        // The node's inputs are recorded again by `compile`.
+        if let Some(node) = self.recording.last_mut() {
+          node.resolved.clear();
+        }
+        return graph.get(id)?.compile(graph, self, id);
+      }
+    }
+    for (id, prefix, code, dt) in cached.outputs {
+      self.add_output(id, &prefix, code, dt)?;
+    }
+    for (id, prefix, value) in cached.consts {
+      self.add_const_output(id, &prefix, value)?;
+    }
+    for extension in cached.enables {
+      self.enable(&extension);
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::*;
+
+  fn compile(
+    graph: &NodeGraph,
+    compile: &mut NodeGraphCompile,
+    dirty: Option<&HashSet<NodeId>>,
+  ) -> Result<String> {
+    match dirty {
+      Some(dirty) => compile.recompile(graph, dirty)?,
+      None => compile.compile_graph(graph)?,
+    }
+    let out = graph.output().expect("output node");
+    compile.resolve_output(graph, OutputId::new(out, 0))?;
+    Ok(compile.dump())
+  }
+
+  #[test]
+  fn conversions_agree() {
+    for from in DataType::ALL {
+      if from.is_dynamic() {
+        // Dynamic types are resolved before compiling.
+        continue;
+      }
+      for to in DataType::ALL {
+        let supported = from.conversion(&to).is_supported();
+        let mut compiled = CompiledValue {
+          value: "v".to_string(),
+          dt: from,
+        };
+        assert_eq!(
+          compiled.convert(to).is_ok(),
+          supported,
+          "CompiledValue: {from:?} -> {to:?}"
+        );
+        assert_eq!(
+          from.default_value().convert(to).is_ok(),
+          supported,
+          "Value: {from:?} -> {to:?}"
+        );
+        assert_eq!(to.is_compatible(&from), supported);
+      }
+    }
+    let mut compiled = CompiledValue {
+      value: "v".to_string(),
+      dt: DataType::Vec4,
+    };
+    compiled.convert(DataType::Vec3).unwrap();
+    assert_eq!(compiled.value, "vec3<f32>(v.xyz)");
+  }
+
+  #[test]
+  fn recompile_dirty_nodes() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float1 = graph.add(reg.new_by_name("Float")?);
+    let float2 = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float1.into())?;
+    graph.set_node_input(add, "B", 1.0.into())?;
+    let mul = graph.add(reg.new_by_name("Multiply")?);
+    graph.set_node_input(mul, "A", float2.into())?;
+    graph.set_node_input(mul, "B", 2.0.into())?;
+    let out = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(out, "A", add.into())?;
+    graph.set_node_input(out, "B", mul.into())?;
+    graph.set_output(Some(out));
+    graph.take_dirty();
+    // Only the editing methods mark nodes as dirty.
+    graph.get_mut(add)?;
+    assert!(graph.dirty_nodes().is_empty());
+
+    let mut incremental = NodeGraphCompile::new();
+    incremental.push_new_block("main");
+    compile(&graph, &mut incremental, None)?;
+
+    // Removing a variable from the first branch shifts the names used by the second branch.
+    graph.set_node_input(add, "A", 3.0.into())?;
+    graph.set_node_param(float1, "Value", 4.0.into())?;
+    let dirty = graph.take_dirty();
+    assert_eq!(dirty.len(), 2);
+    let code = compile(&graph, &mut incremental, Some(&dirty))?;
+
+    let mut full = NodeGraphCompile::new();
+    full.push_new_block("main");
+    assert_eq!(code, compile(&graph, &mut full, None)?);
+    // The recompiled nodes don't record their inputs twice.
+    for id in [mul, out] {
+      assert_eq!(incremental.cache[&id].resolved, full.cache[&id].resolved);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn recompile_property() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    graph.set_output(Some(add));
+    let (prop_id, _) = graph.promote_to_property(InputId::new(add, 0))?;
+    graph.take_dirty();
+
+    let mut incremental = NodeGraphCompile::new();
+    incremental.push_new_block("main");
+    compile(&graph, &mut incremental, None)?;
+
+    // Changing the property recompiles the nodes using it.
+    graph.property_mut(prop_id)?.set_value(Value::F32(4.0));
+    let dirty = graph.take_dirty();
+    let code = compile(&graph, &mut incremental, Some(&dirty))?;
+    assert!(code.contains("4.0"), "{code}");
+
+    let mut full = NodeGraphCompile::new();
+    full.push_new_block("main");
+    assert_eq!(code, compile(&graph, &mut full, None)?);
+    Ok(())
+  }
+
+  #[test]
+  fn compile_vertex_and_fragment() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    let vertex = graph.add(reg.new_by_name("Vertex")?);
+    graph.set_output_named(VERTEX_OUTPUT, Some(vertex));
+    assert_eq!(graph.vertex_output(), Some(vertex));
+
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    let code = compile.dump();
+    assert!(code.contains("@fragment"));
+    assert!(code.contains("@vertex"));
+    assert_eq!(
+      code.matches("#import bevy_render::instance_index").count(),
+      1
+    );
+    assert_eq!(code.matches("struct ShaderGraphMaterialUniform").count(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn compile_metrics() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let tex = graph.add(reg.new_by_name("Texture Sample")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", tex.into())?;
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", add.into())?;
+    graph.set_output(Some(frag));
+
+    let mut compile = NodeGraphCompile::new();
+    for block in ["imports", "bindings", "fragment"] {
+      compile.define_block(block);
+    }
+    compile.compile_graph(&graph)?;
+    let metrics = compile.metrics();
+    let code = compile.dump();
+    assert_eq!(metrics.texture_samples, 1);
+    assert_eq!(
+      metrics.lines,
+      code.lines().filter(|l| !l.trim().is_empty()).count()
+    );
+    let fragment = metrics
+      .locals
+      .iter()
+      .find(|(name, _)| name == "fragment")
+      .map(|(_, count)| *count);
+    assert_eq!(fragment, Some(code.matches("  let ").count()));
+    assert_eq!(metrics.total_locals(), fragment.unwrap_or_default());
+    assert!(metrics.to_string().contains("texture samples: 1"));
+    Ok(())
+  }
+
+  #[test]
+  fn shader_artifact() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    let vertex = graph.add(reg.new_by_name("Vertex")?);
+    graph.set_vertex_output(Some(vertex));
+
+    let mut compile = NodeGraphCompile::with_template(ShaderTemplate::bare());
+    for block in ["imports", "bindings", "fragment", "vertex"] {
+      compile.define_block(block);
+    }
+    compile.compile_graph(&graph)?;
+    let artifact = compile.artifact();
+    assert_eq!(
+      artifact.entry_points,
+      vec![
+        ("fragment".to_string(), ShaderStage::Fragment),
+        ("vertex".to_string(), ShaderStage::Vertex),
+      ]
+    );
+    assert_eq!(
+      artifact.bindings,
+      vec![ShaderBinding::new(0, 0, "material")]
+    );
+    for (name, _) in &artifact.entry_points {
+      assert!(artifact.source.contains(&format!("fn {name}(")));
+    }
+
+    // Recompiling keeps the metadata.
+    compile.recompile(&graph, &HashSet::new())?;
+    assert_eq!(compile.artifact().entry_points.len(), 2);
+    assert_eq!(compile.artifact().bindings.len(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn with_block_pops() -> Result<()> {
+    let mut compile = NodeGraphCompile::new();
+    let res = compile.with_block("fragment", |c| -> Result<()> {
+      c.current_block()?.append("frag;".to_string());
+      Err(anyhow!("failed"))
+    });
+    assert!(res.is_err());
+    assert!(compile.current_block().is_err());
+
+    // Unbalanced pushes are cleaned up and reported.
+    let res = compile.with_block("fragment", |c| {
+      c.push_new_block("inner");
+      Ok(())
+    });
+    assert!(res.is_err());
+    assert!(compile.current_block().is_err());
+
+    let len = compile.with_block("fragment", |c| {
+      c.current_block()?.append("more;".to_string());
+      Ok(c.dump().len())
+    })?;
+    assert_eq!(len, "frag;more;".len());
+    Ok(())
+  }
+
+  #[test]
+  fn stack_corruption_message() {
+    let mut compile = NodeGraphCompile::new();
+    let frag = compile.push_new_block("fragment");
+    compile.push_new_block("inner");
+    let err = compile.pop(Some(frag)).unwrap_err().to_string();
+    assert_eq!(
+      err,
+      "Stack corruption detected: expected \"fragment\"(1), found \"inner\"(2).  Remaining stack: [\"fragment\"(1)]"
+    );
+    let err = compile.pop(None).unwrap_err().to_string();
+    assert!(err.contains("expected no block, found \"fragment\"(1)"));
+  }
+
+  #[test]
+  fn compile_bare_template() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+
+    let mut compile = NodeGraphCompile::with_template(ShaderTemplate::bare());
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    let code = compile.dump();
+    assert!(code.contains("-> @location(0) vec4<f32>"));
+    assert!(!code.contains("#import"));
+    assert!(!code.contains("pbr_input"));
+    Ok(())
+  }
+
+  #[test]
+  fn compile_f16() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    graph.set_node_input(frag, "Color", float.into())?;
+
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    assert!(!compile.dump().contains("enable f16;"));
+
+    let half = graph.add(Node::from_impl(Box::new(
+      crate::nodes::channel::ConvertNode::with_target(DataType::F16).expect("F16 target"),
+    )));
+    graph.set_node_input(half, "Input", float.into())?;
+    graph.set_node_input(frag, "Color", half.into())?;
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    let code = compile.dump();
+    assert!(code.starts_with("enable f16;\n"));
+    assert!(code.contains("f16("));
+    assert!(code.contains("vec4<f32>(f32("));
+    assert_eq!(Value::F16(0.5).compile()?.value, "0.5h");
+
+    // F16 literals used directly in the code also need the directive.
+    let mut compile = NodeGraphCompile::new();
+    let literal = compile.literal(Value::F16(0.5).compile()?);
+    assert_eq!(literal.dt, DataType::F16);
+    assert_eq!(compile.dump(), "enable f16;\n");
+    compile.clear();
+    assert_eq!(compile.dump(), "");
+    Ok(())
+  }
+
+  #[test]
+  fn resolved_type() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let swizzle = graph.add(reg.new_by_name("Swizzle")?);
+    graph.set_node_input(swizzle, "Input", vec3.into())?;
+    graph.set_node_param(swizzle, "Swizzle", ParameterValue::Text("xy".into()))?;
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    graph.set_node_input(frag, "Color", swizzle.into())?;
+    let unused = graph.add(reg.new_by_name("Float")?);
+
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    assert_eq!(
+      compile.resolved_type(OutputId::new(vec3, 0)),
+      Some(DataType::Vec3)
+    );
+    assert_eq!(
+      compile.resolved_type(OutputId::new(swizzle, 0)),
+      Some(DataType::Vec2)
+    );
+    assert_eq!(compile.resolved_type(OutputId::new(unused, 0)), None);
+    Ok(())
+  }
+
+  #[test]
+  fn output_format() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    assert_eq!(graph.output_format(), OutputFormat::Rgba8UnormSrgb);
+    let changed = graph.changed_counter();
+    graph.set_output_format(OutputFormat::Rgba16Float);
+    assert!(graph.changed_counter() > changed);
+
+    let loaded: NodeGraph = serde_json::from_str(&serde_json::to_string(&graph)?)?;
+    assert_eq!(loaded.output_format(), OutputFormat::Rgba16Float);
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&loaded)?;
+    assert_eq!(compile.output_format(), OutputFormat::Rgba16Float);
+    assert!(compile.output_format().is_hdr());
+    assert_eq!(compile.output_format().wgsl_name(), Some("rgba16float"));
+    assert_eq!(compile.artifact().output_format, OutputFormat::Rgba16Float);
+    Ok(())
+  }
+
+  #[test]
+  fn compile_split_once() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec4 = graph.add(reg.new_by_name("Vector 4")?);
+    let split = graph.add(reg.new_by_name("Split")?);
+    graph.set_node_input(split, "Input", vec4.into())?;
+    let combine = graph.add(reg.new_by_name("Combine")?);
+    for (idx, name) in ["R", "G", "B", "A"].into_iter().enumerate() {
+      graph.set_node_input(
+        combine,
+        name,
+        Input::Connect(OutputId::new(split, idx as u32), None),
+      )?;
+    }
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", combine.into())?;
+    graph.set_output(Some(frag));
+
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.set_profiling(true);
+    assert!(!compile.is_compiled(split));
+    compile.compile_graph(&graph)?;
+    assert!(compile.is_compiled(split));
+    let profile = compile.profile().expect("profile");
+    assert_eq!(profile.get("Split").map(|t| t.count), Some(1));
+    Ok(())
+  }
+
+  #[test]
+  fn static_bool_branch() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let toggle = graph.add(reg.new_by_name("Static Boolean")?);
+    let red = graph.add(reg.new_by_name("Vector 4")?);
+    let red_value = Value::Vec4(glam::vec4(1.0, 0.0, 0.0, 1.0));
+    graph.set_node_param(red, "Value", ParameterValue::Value(red_value.clone()))?;
+    let green = graph.add(reg.new_by_name("Vector 4")?);
+    let green_value = Value::Vec4(glam::vec4(0.0, 1.0, 0.0, 1.0));
+    graph.set_node_param(green, "Value", ParameterValue::Value(green_value.clone()))?;
+    let branch = graph.add(reg.new_by_name("Branch")?);
+    graph.set_node_input(branch, "Predicate", toggle.into())?;
+    graph.set_node_input(branch, "On True", red.into())?;
+    graph.set_node_input(branch, "On False", green.into())?;
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", branch.into())?;
+    graph.set_output(Some(frag));
+
+    assert_eq!(graph.evaluate_node(branch)?, green_value);
+    let code = graph.compile_to_wgsl()?;
+    assert!(code.contains("const static_bool_node_"));
+    assert!(code.contains("vec4<f32>(0.0, 1.0, 0.0, 1.0)"));
+    assert!(!code.contains("vec4<f32>(1.0, 0.0, 0.0, 1.0)"));
+    assert!(!code.contains("select("));
+
+    graph.set_node_param(toggle, "Value", ParameterValue::Value(Value::U32(1)))?;
+    assert_eq!(graph.evaluate_node(branch)?, red_value);
+    let code = graph.compile_to_wgsl()?;
+    assert!(code.contains("vec4<f32>(1.0, 0.0, 0.0, 1.0)"));
+    assert!(!code.contains("vec4<f32>(0.0, 1.0, 0.0, 1.0)"));
+
+    // Runtime predicates compile both values.
+    let dynamic = graph.add(reg.new_by_name("Boolean")?);
+    graph.set_node_input(branch, "Predicate", dynamic.into())?;
+    let code = graph.compile_to_wgsl()?;
+    assert!(code.contains("select("));
+    assert!(code.contains("vec4<f32>(1.0, 0.0, 0.0, 1.0)"));
+    assert!(code.contains("vec4<f32>(0.0, 1.0, 0.0, 1.0)"));
+    Ok(())
+  }
+
+  #[test]
+  fn compile_array() -> Result<()> {
+    use crate::nodes::basic::FloatArrayNode;
+    use crate::nodes::channel::ArrayIndexNode;
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let array = graph.add(Node::from_impl(Box::new(FloatArrayNode {
+      value: F32Array::new(vec![0.25, 0.5, 1.0]),
+      ..Default::default()
+    })));
+    let index = graph.add(Node::from_impl(Box::new(ArrayIndexNode::new())));
+    graph.set_node_input(index, "Array", array.into())?;
+    graph.set_node_input(index, "Index", Input::Value(Value::I32(1)))?;
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+    graph.set_node_input(frag, "Color", index.into())?;
+
+    let mut execution = NodeGraphExecution::new();
+    assert_eq!(execution.eval_node(&graph, index)?, Value::F32(0.5));
+    graph.set_node_input(index, "Index", Input::Value(Value::I32(7)))?;
+    let mut execution = NodeGraphExecution::new();
+    assert_eq!(execution.eval_node(&graph, index)?, Value::F32(1.0));
+
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.compile_graph(&graph)?;
+    let code = compile.dump();
+    assert!(code.contains("var array_index_node_array_"));
+    assert!(code.contains("array<f32, 3>(0.25, 0.5, 1.0)"));
+    // The constant index is clamped to the array.
+    assert!(code.contains("[clamp(7, 0, 2)]"));
+    assert!(F32Array::default().compile().is_err());
+
+    // Empty arrays return `0.0` in `eval`, but can't be compiled.
+    graph.set_node_param(array, "Value", Value::F32Array(F32Array::default()).into())?;
+    let mut execution = NodeGraphExecution::new();
+    assert_eq!(execution.eval_node(&graph, index)?, Value::F32(0.0));
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    assert!(compile.compile_graph(&graph).is_err());
+    assert!(!compile.dump().contains("clamp("));
+    Ok(())
+  }
+
+  #[test]
+  fn block_order() -> Result<()> {
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("fragment");
+    compile.define_block("bindings");
+    compile.define_block("imports");
+    compile.append_code("fragment", "fragment;".to_string())?;
+    compile.append_code("bindings", "bindings;".to_string())?;
+    compile.append_code("imports", "imports;".to_string())?;
+    assert_eq!(compile.dump(), "fragment;bindings;imports;");
+
+    compile.set_block_order(&["imports", "missing", "bindings"]);
+    assert_eq!(
+      compile.block_order(),
+      vec!["imports", "bindings", "fragment"]
+    );
+    assert_eq!(compile.dump(), "imports;bindings;fragment;");
+    Ok(())
+  }
+
+  #[test]
+  fn add_function() -> Result<()> {
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.define_block("fragment");
+    compile.add_function("helper", "fn helper() {}\n")?;
+    compile.add_function("helper", "fn helper() {}\n")?;
+    assert_eq!(
+      compile.block_order(),
+      vec!["imports", "bindings", FUNCTIONS_BLOCK, "fragment"]
+    );
+    assert_eq!(compile.dump().matches("fn helper").count(), 1);
+
+    compile.clear();
+    compile.add_function("helper", "fn helper() {}\n")?;
+    assert_eq!(compile.dump().matches("fn helper").count(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn node_error_context() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    // The property node references a missing graph property.
+    let prop = graph.add(reg.new_by_name("Property")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let mul = graph.add(reg.new_by_name("Multiply")?);
+    graph.set_input(InputId::new(add, 0), prop.into())?;
+    graph.set_input(InputId::new(mul, 0), add.into())?;
+    graph.set_output(Some(mul));
+
+    let mut compile = NodeGraphCompile::new();
+    let err = compile.compile_graph(&graph).unwrap_err();
+    let node = err
+      .downcast_ref::<NodeCompileError>()
+      .expect("node context");
+    assert_eq!(node.node, prop);
+    assert_eq!(node.name, "Property");
+    let msg = format!("{err:#}");
+    assert!(
+      msg.starts_with(&format!("in node 'Property' ({prop}): ")),
+      "{msg}"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn max_depth() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let mut last = graph.add(reg.new_by_name("Add")?);
+    // The fragment output node is the last level.
+    for _ in 1..DEFAULT_MAX_DEPTH - 1 {
+      let add = graph.add(reg.new_by_name("Add")?);
+      graph.set_node_input(add, "A", last.into())?;
+      last = add;
+    }
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", last.into())?;
+    graph.set_output(Some(frag));
+
+    // The default depth is enough for the graph.
+    graph.compile_to_wgsl()?;
+
+    let mut compile = NodeGraphCompile::new();
+    for block in ["imports", "bindings", "fragment"] {
+      compile.define_block(block);
+    }
+    compile.set_max_depth(DEFAULT_MAX_DEPTH - 1);
+    let err = compile.compile_graph(&graph).unwrap_err();
+    assert!(format!("{err:#}").contains("Maximum node depth"), "{err:#}");
+    Ok(())
+  }
+
+  #[test]
+  fn sanitize_idents() {
+    assert_eq!(sanitize_ident("add_node"), "add_node");
+    assert_eq!(sanitize_ident("My Node"), "My_Node");
+    assert_eq!(sanitize_ident("  Größe (x)"), "Gr_e_x");
+    assert_eq!(sanitize_ident("__private"), "private");
+    assert_eq!(sanitize_ident("2d noise"), "v_2d_noise");
+    assert_eq!(sanitize_ident(""), "v");
+
+    let mut block = CodeBlock::default();
+    let name = block.add_local("Custom Node", "1.0".to_string(), DataType::F32);
+    assert_eq!(name, "Custom_Node_1");
   }
 }
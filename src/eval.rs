@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::{anyhow, Result};
 
 use crate::graph::*;
 use crate::node::*;
+use crate::profile::*;
 use crate::values::*;
 
 #[derive(Clone, Default, Debug)]
@@ -15,7 +16,12 @@ pub enum NodeEvalState {
 
 #[derive(Clone, Default, Debug)]
 pub struct NodeGraphExecution {
-  nodes: HashMap<NodeId, NodeEvalState>,
+  outputs: HashMap<OutputId, NodeEvalState>,
+  /// External values (time, resolution, ...) that nodes can read during `eval`.
+  context: HashMap<String, Value>,
+  profile: Option<NodeProfile>,
+  depth: usize,
+  max_depth: Option<usize>,
 }
 
 impl NodeGraphExecution {
@@ -23,24 +29,91 @@ impl NodeGraphExecution {
     Self::default()
   }
 
+  /// Clear the cached outputs.  The context values are kept.
   pub fn clear(&mut self) {
-    self.nodes.clear();
+    self.outputs.clear();
+  }
+
+  /// Limit how deep nested nodes are evaluated.  Defaults to `DEFAULT_MAX_DEPTH`.
+  pub fn set_max_depth(&mut self, depth: usize) {
+    self.max_depth = Some(depth);
+  }
+
+  pub fn max_depth(&self) -> usize {
+    self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)
+  }
+
+  /// Enable/disable recording the time spent evaluating each node type.
+  pub fn set_profiling(&mut self, enable: bool) {
+    if enable {
+      self.profile.get_or_insert_with(NodeProfile::new);
+    } else {
+      self.profile = None;
+    }
+  }
+
+  /// The recorded timings, if profiling is enabled.
+  pub fn profile(&self) -> Option<&NodeProfile> {
+    self.profile.as_ref()
+  }
+
+  /// Set an external value that nodes can read with `get_context`.
+  pub fn set_context(&mut self, name: &str, value: Value) {
+    self.context.insert(name.to_string(), value);
+  }
+
+  pub fn get_context(&self, name: &str) -> Option<&Value> {
+    self.context.get(name)
+  }
+
+  pub fn remove_context(&mut self, name: &str) -> Option<Value> {
+    self.context.remove(name)
+  }
+
+  /// Set the cached value of an output.
+  pub fn set_output(&mut self, id: OutputId, value: Value) {
+    self.outputs.insert(id, NodeEvalState::Cached(value));
+  }
+
+  /// Returns true if the output has a cached value.
+  pub fn is_cached(&self, id: OutputId) -> bool {
+    matches!(self.outputs.get(&id), Some(NodeEvalState::Cached(_)))
+  }
+
+  /// Evict the cached outputs of node `id` and all nodes downstream of it.
+  ///
+  /// Use with `eval_graph_cached` to only re-evaluate the nodes affected by a change.
+  pub fn invalidate(&mut self, graph: &NodeGraph, id: NodeId) {
+    let nodes = graph.downstream_nodes(&HashSet::from([id]));
+    self
+      .outputs
+      .retain(|output, _| !nodes.contains(&output.node()));
   }
 
   pub fn eval_graph(&mut self, graph: &NodeGraph) -> Result<Value> {
     self.clear();
+    self.eval_graph_cached(graph)
+  }
+
+  /// Evaluate the graph without clearing the cached outputs.
+  pub fn eval_graph_cached(&mut self, graph: &NodeGraph) -> Result<Value> {
     let id = graph
       .output()
       .ok_or_else(|| anyhow!("Graph missing output node"))?;
     self.eval_node(graph, id)
   }
 
+  /// Evaluate the node's first output.
   pub fn eval_node(&mut self, graph: &NodeGraph, id: NodeId) -> Result<Value> {
-    let node = graph.get(id)?;
-    if node.cache_output() {
+    self.eval_output(graph, OutputId::new(id, 0))
+  }
+
+  pub fn eval_output(&mut self, graph: &NodeGraph, id: OutputId) -> Result<Value> {
+    let node = graph.get(id.node())?;
+    if node.cache_output() || node.is_pure() {
       use std::collections::hash_map::Entry;
       // Check for cached value or recursive connections.
-      match self.nodes.entry(id) {
+      match self.outputs.entry(id) {
         Entry::Occupied(entry) => match entry.get() {
           NodeEvalState::Processing => {
             Err(anyhow!("Recursive node connection"))?;
@@ -54,21 +127,46 @@ impl NodeGraphExecution {
         }
       }
       // Evaluate node.
-      let value = node.eval(graph, self, id)?;
+      let value = match self.timed_eval(graph, node, id) {
+        Ok(value) => value,
+        Err(err) => {
+          self.outputs.remove(&id);
+          return Err(err);
+        }
+      };
       // Cache results.
-      self.nodes.insert(id, NodeEvalState::Cached(value.clone()));
+      self
+        .outputs
+        .insert(id, NodeEvalState::Cached(value.clone()));
       Ok(value)
     } else {
       // Evaluate node.
-      node.eval(graph, self, id)
+      self.timed_eval(graph, node, id)
     }
   }
+
+  fn timed_eval(&mut self, graph: &NodeGraph, node: &Node, id: OutputId) -> Result<Value> {
+    if self.depth >= self.max_depth() {
+      return Err(anyhow!(
+        "Maximum node depth ({}) exceeded",
+        self.max_depth()
+      ));
+    }
+    let start = self.profile.as_mut().map(|profile| profile.start());
+    self.depth += 1;
+    let res = node.eval_output(graph, self, id);
+    self.depth -= 1;
+    if let (Some(profile), Some(start)) = (self.profile.as_mut(), start) {
+      profile.finish(&node.def().name, start);
+    }
+    res
+  }
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
-  use crate::NodeRegistry;
+  use crate::{Input, NodeRegistry};
 
   #[test]
   fn compile_graph() -> Result<()> {
@@ -95,4 +193,205 @@ mod tests {
     assert_eq!(val, Value::F32(8.0));
     Ok(())
   }
+
+  #[test]
+  fn context_kept_between_evals() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let node = graph.add(reg.new_by_name("Add")?);
+    graph.set_output(Some(node));
+
+    let mut execution = NodeGraphExecution::new();
+    execution.set_context("time", Value::F32(1.5));
+    execution.eval_graph(&graph)?;
+    assert_eq!(execution.get_context("time"), Some(&Value::F32(1.5)));
+    assert_eq!(execution.remove_context("time"), Some(Value::F32(1.5)));
+    assert_eq!(execution.get_context("time"), None);
+    Ok(())
+  }
+
+  #[test]
+  fn invalidate_downstream() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float.into())?;
+    let other = graph.add(reg.new_by_name("Float")?);
+
+    let mut execution = NodeGraphExecution::new();
+    for id in [float, add, other] {
+      execution.set_output(OutputId::new(id, 0), Value::F32(1.0));
+    }
+    execution.invalidate(&graph, float);
+    assert!(!execution.is_cached(OutputId::new(float, 0)));
+    assert!(!execution.is_cached(OutputId::new(add, 0)));
+    assert!(execution.is_cached(OutputId::new(other, 0)));
+
+    execution.invalidate(&graph, add);
+    assert!(execution.is_cached(OutputId::new(other, 0)));
+    Ok(())
+  }
+
+  #[test]
+  fn incremental_eval() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let a = graph.add(reg.new_by_name("Float")?);
+    let b = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_param(a, "Value", 1.0.into())?;
+    graph.set_node_param(b, "Value", 2.0.into())?;
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", a.into())?;
+    graph.set_node_input(add, "B", b.into())?;
+    graph.set_output(Some(add));
+
+    let mut execution = NodeGraphExecution::new();
+    execution.set_profiling(true);
+    assert_eq!(execution.eval_graph(&graph)?, Value::F32(3.0));
+    let count = |execution: &NodeGraphExecution, name| {
+      execution
+        .profile()
+        .and_then(|profile| profile.get(name))
+        .map(|t| t.count)
+    };
+    assert_eq!(count(&execution, "Float"), Some(2));
+    assert_eq!(count(&execution, "Add"), Some(1));
+
+    // Nothing changed, so nothing is evaluated again.
+    assert_eq!(execution.eval_graph_cached(&graph)?, Value::F32(3.0));
+    assert_eq!(count(&execution, "Float"), Some(2));
+    assert_eq!(count(&execution, "Add"), Some(1));
+
+    // Only the changed node and the nodes downstream of it are evaluated again.
+    graph.set_node_param(a, "Value", 5.0.into())?;
+    execution.invalidate(&graph, a);
+    assert_eq!(execution.eval_graph_cached(&graph)?, Value::F32(7.0));
+    assert_eq!(count(&execution, "Float"), Some(3));
+    assert_eq!(count(&execution, "Add"), Some(2));
+    assert!(execution.is_cached(OutputId::new(b, 0)));
+    Ok(())
+  }
+
+  #[test]
+  fn profile_eval() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = reg.new_by_name("Add")?;
+    let node1 = graph.add(add.duplicate());
+    let node2 = graph.add(add.duplicate());
+    graph.set_node_input(node2, "A", node1.into())?;
+    graph.set_output(Some(node2));
+
+    let mut execution = NodeGraphExecution::new();
+    execution.eval_graph(&graph)?;
+    assert!(execution.profile().is_none());
+
+    execution.set_profiling(true);
+    execution.eval_graph(&graph)?;
+    let profile = execution.profile().expect("profile");
+    assert_eq!(profile.get("Add").map(|t| t.count), Some(2));
+    assert_eq!(profile.summary().len(), 1);
+    Ok(())
+  }
+
+  #[test]
+  fn eval_combine_split() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let combine = graph.add(reg.new_by_name("Combine")?);
+    graph.set_node_input(combine, "R", 1.0.into())?;
+    graph.set_node_input(combine, "G", 2.0.into())?;
+    graph.set_node_input(combine, "B", 3.0.into())?;
+    graph.set_node_input(combine, "A", 4.0.into())?;
+    let split = graph.add(reg.new_by_name("Split")?);
+    // Connect the `RGB` output.
+    graph.set_node_input(
+      split,
+      "Input",
+      Input::Connect(OutputId::new(combine, 1), None),
+    )?;
+    let swizzle = graph.add(reg.new_by_name("Swizzle")?);
+    graph.set_node_input(swizzle, "Input", combine.into())?;
+    graph.set_node_param(swizzle, "Swizzle", ParameterValue::Text("wzy".into()))?;
+
+    let mut execution = NodeGraphExecution::new();
+    for (idx, expected) in [1.0, 2.0, 3.0, 0.0].into_iter().enumerate() {
+      let val = execution.eval_output(&graph, OutputId::new(split, idx as u32))?;
+      assert_eq!(val, Value::F32(expected));
+    }
+    let val = execution.eval_node(&graph, swizzle)?;
+    assert_eq!(val, Value::Vec3(glam::Vec3::new(4.0, 3.0, 2.0)));
+    Ok(())
+  }
+
+  #[test]
+  fn max_depth() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let mut last = graph.add(reg.new_by_name("Add")?);
+    for _ in 1..DEFAULT_MAX_DEPTH {
+      let add = graph.add(reg.new_by_name("Add")?);
+      graph.set_node_input(add, "A", last.into())?;
+      last = add;
+    }
+    graph.set_output(Some(last));
+
+    // The default depth is enough for the graph.
+    let mut execution = NodeGraphExecution::new();
+    assert_eq!(execution.eval_graph(&graph)?, Value::F32(0.0));
+
+    execution.set_max_depth(DEFAULT_MAX_DEPTH - 1);
+    execution.clear();
+    let err = execution.eval_graph(&graph).unwrap_err();
+    assert!(err.to_string().contains("Maximum node depth"), "{err}");
+    Ok(())
+  }
+
+  #[test]
+  fn eval_after_error() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    // The `Uv` node can only be compiled, so evaluating it fails.
+    let uv = graph.add(reg.new_by_name("Uv")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", uv.into())?;
+    graph.set_output(Some(add));
+
+    let mut execution = NodeGraphExecution::new();
+    for _ in 0..2 {
+      let err = execution.eval_graph_cached(&graph).unwrap_err();
+      assert!(err.to_string().contains("doesn't support `eval`"), "{err}");
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn eval_f16_inputs() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_param(float, "Value", 2.0.into())?;
+    let half = graph.add(Node::from_impl(Box::new(
+      crate::nodes::channel::ConvertNode::with_target(DataType::F16).expect("F16 target"),
+    )));
+    graph.set_node_input(half, "Input", float.into())?;
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", half.into())?;
+    graph.set_node_input(add, "B", 1.0.into())?;
+    graph.set_output(Some(add));
+
+    let mut execution = NodeGraphExecution::new();
+    assert_eq!(execution.eval_graph(&graph)?, Value::F16(3.0));
+
+    // Typed `f32` inputs accept F16 values, both connected and set directly.
+    let combine = graph.add(reg.new_by_name("Combine")?);
+    graph.set_node_input(combine, "R", half.into())?;
+    graph.set_node_input(combine, "G", Value::F16(0.5).into())?;
+    graph.set_output(Some(combine));
+    let mut execution = NodeGraphExecution::new();
+    let val = execution.eval_graph(&graph)?;
+    assert_eq!(val.components()?[..2], [2.0, 0.5]);
+    Ok(())
+  }
 }
@@ -1,10 +1,12 @@
 use std::fs::File;
 use std::path::Path;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+#[cfg(feature = "egui")]
+use std::sync::Arc;
 use uuid::Uuid;
 
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 
 use serde::{de::Deserializer, ser::SerializeSeq, Deserialize, Serialize, Serializer};
 
@@ -20,10 +22,19 @@ pub struct EditorState {
   origin: emath::Vec2,
   zoom: f32,
   scroll_offset: emath::Vec2,
+  /// Margin around the nodes that limits panning.  `None` disables the bounds.
+  #[serde(default = "default_pan_margin")]
+  pan_margin: Option<f32>,
+  /// Size of the visible area in UI-space.
+  #[serde(skip)]
+  view_size: emath::Vec2,
   #[serde(skip)]
   graph_pointer_pos: Option<emath::Vec2>,
   #[serde(skip)]
   add_node_at: Option<emath::Vec2>,
+  /// Panning with the middle mouse button or SPACE + drag.
+  #[serde(skip)]
+  panning: bool,
 }
 
 impl Default for EditorState {
@@ -35,8 +46,11 @@ impl Default for EditorState {
       origin,
       zoom: 0.5,
       scroll_offset: origin - emath::vec2(450., 250.),
+      pan_margin: default_pan_margin(),
+      view_size: emath::Vec2::ZERO,
       graph_pointer_pos: None,
       add_node_at: None,
+      panning: false,
     }
   }
 }
@@ -52,6 +66,117 @@ impl EditorState {
     scroll_offset.zoom(self.zoom);
     (size, origin, scroll_offset, self.zoom)
   }
+
+  /// Move the view by `delta` in UI-space.
+  fn pan(&mut self, delta: emath::Vec2) {
+    self.scroll_offset -= delta / self.zoom;
+  }
+
+  /// Clamp the scroll offset so the view can't leave the node bounds (plus margin).
+  ///
+  /// `nodes` is the bounding box of the nodes in graph-space and `view` is the
+  /// unzoomed size of the visible area.
+  fn clamp_scroll_offset(&mut self, nodes: emath::Rect, view: emath::Vec2) {
+    let Some(margin) = self.pan_margin else {
+      return;
+    };
+    if !nodes.is_positive() {
+      return;
+    }
+    let bounds = nodes.translate(self.origin).expand(margin);
+    // When the view is larger than the bounds, keep the bounds inside the view.
+    let min = bounds.min.to_vec2().min(bounds.max.to_vec2() - view);
+    let max = bounds.min.to_vec2().max(bounds.max.to_vec2() - view);
+    let max_offset = (self.size - view).max(emath::Vec2::ZERO);
+    self.scroll_offset = self
+      .scroll_offset
+      .clamp(min, max)
+      .clamp(emath::Vec2::ZERO, max_offset);
+  }
+}
+
+impl EditorState {
+  /// Zoom and scroll to show `rect` (in graph-space).
+  fn fit_view(&mut self, rect: emath::Rect) {
+    let view = if self.view_size.min_elem() > 0.0 {
+      self.view_size
+    } else {
+      emath::vec2(900., 500.)
+    };
+    let rect = rect.expand(50.0);
+    self.zoom = (view.x / rect.width())
+      .min(view.y / rect.height())
+      .clamp(0.1, 1.0);
+    self.scroll_offset = self.origin + rect.center().to_vec2() - view / self.zoom / 2.0;
+  }
+}
+
+/// The editor's viewport, persisted separately from the graph data.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+  pub zoom: f32,
+  /// Unzoomed scroll offset.
+  pub scroll_offset: emath::Vec2,
+}
+
+impl Default for CameraState {
+  fn default() -> Self {
+    EditorState::default().camera()
+  }
+}
+
+impl EditorState {
+  fn camera(&self) -> CameraState {
+    CameraState {
+      zoom: self.zoom,
+      scroll_offset: self.scroll_offset,
+    }
+  }
+
+  fn set_camera(&mut self, camera: CameraState) {
+    self.zoom = camera.zoom.clamp(0.1, 1.0);
+    self.scroll_offset = camera
+      .scroll_offset
+      .clamp(emath::Vec2::ZERO, self.size.max(emath::Vec2::ZERO));
+  }
+}
+
+/// Editor state that isn't part of the graph data: the camera, the selection and the
+/// details panel.  See `NodeGraph::save_with_ui`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphUiState {
+  pub camera: CameraState,
+  /// Selected nodes and groups.
+  #[serde(default)]
+  pub selected: Vec<Uuid>,
+  /// The node shown in the details panel.
+  #[serde(default)]
+  pub details_node: Option<NodeId>,
+  #[serde(default)]
+  pub details_pinned: bool,
+}
+
+/// A graph saved together with its UI state.  The UI state is an extra field, so
+/// the file can still be loaded as a plain graph.
+#[cfg(feature = "egui")]
+#[derive(Serialize)]
+struct SaveGraphWithUi<'a> {
+  #[serde(flatten)]
+  graph: &'a NodeGraph,
+  ui: GraphUiState,
+}
+
+#[cfg(feature = "egui")]
+#[derive(Deserialize)]
+struct LoadGraphWithUi {
+  #[serde(flatten)]
+  graph: NodeGraph,
+  #[serde(default)]
+  ui: Option<GraphUiState>,
+}
+
+fn default_pan_margin() -> Option<f32> {
+  Some(200.0)
 }
 
 pub trait GetId {
@@ -97,7 +222,73 @@ where
 }
 
 #[derive(Clone, Default, Debug)]
-struct ConnectionMap(pub(crate) IndexMap<InputId, OutputId>);
+struct ConnectionMap {
+  inputs: IndexMap<InputId, OutputId>,
+  /// Reverse index of `inputs`: output -> connected inputs.
+  outputs: HashMap<OutputId, IndexSet<InputId>>,
+}
+
+impl ConnectionMap {
+  pub fn len(&self) -> usize {
+    self.inputs.len()
+  }
+
+  pub fn insert(&mut self, input: InputId, output: OutputId) -> Option<OutputId> {
+    let old = self.inputs.insert(input, output);
+    if let Some(old) = old {
+      self.remove_consumer(old, &input);
+    }
+    self.outputs.entry(output).or_default().insert(input);
+    old
+  }
+
+  pub fn remove(&mut self, input: &InputId) -> Option<OutputId> {
+    let old = self.inputs.shift_remove(input);
+    if let Some(old) = old {
+      self.remove_consumer(old, input);
+    }
+    old
+  }
+
+  fn remove_consumer(&mut self, output: OutputId, input: &InputId) {
+    if let Some(inputs) = self.outputs.get_mut(&output) {
+      inputs.shift_remove(input);
+      if inputs.is_empty() {
+        self.outputs.remove(&output);
+      }
+    }
+  }
+
+  pub fn get(&self, input: &InputId) -> Option<&OutputId> {
+    self.inputs.get(input)
+  }
+
+  pub fn iter(&self) -> indexmap::map::Iter<'_, InputId, OutputId> {
+    self.inputs.iter()
+  }
+
+  /// Sort connections by input and the consumers of each output.
+  pub fn sort(&mut self) {
+    self.inputs.sort_keys();
+    for inputs in self.outputs.values_mut() {
+      inputs.sort();
+    }
+  }
+
+  /// All inputs connected to `output`.
+  pub fn consumers(&self, output: &OutputId) -> impl Iterator<Item = &InputId> {
+    self.outputs.get(output).into_iter().flatten()
+  }
+}
+
+impl<'a> IntoIterator for &'a ConnectionMap {
+  type Item = (&'a InputId, &'a OutputId);
+  type IntoIter = indexmap::map::Iter<'a, InputId, OutputId>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
 
 impl Serialize for ConnectionMap {
   fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -109,8 +300,8 @@ impl Serialize for ConnectionMap {
       input: &'a InputId,
       output: &'a OutputId,
     }
-    let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
-    for (input, output) in &self.0 {
+    let mut seq = serializer.serialize_seq(Some(self.inputs.len()))?;
+    for (input, output) in &self.inputs {
       seq.serialize_element(&Connection { input, output })?;
     }
     seq.end()
@@ -128,12 +319,11 @@ impl<'de> Deserialize<'de> for ConnectionMap {
       output: OutputId,
     }
     let connections = Vec::<Connection>::deserialize(deserializer)?;
-    Ok(Self(
-      connections
-        .into_iter()
-        .map(|c| (c.input, c.output))
-        .collect(),
-    ))
+    let mut map = Self::default();
+    for c in connections {
+      map.insert(c.input, c.output);
+    }
+    Ok(map)
   }
 }
 
@@ -151,26 +341,71 @@ impl GetId for NodeGraphProperty {
   }
 }
 
+impl NodeGraphProperty {
+  pub fn new(name: &str, value: Value) -> Self {
+    Self {
+      id: Uuid::new_v4(),
+      name: name.to_string(),
+      description: String::new(),
+      value,
+    }
+  }
+
+  pub fn id(&self) -> Uuid {
+    self.id
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  pub fn description(&self) -> &str {
+    &self.description
+  }
+
+  pub fn set_description(&mut self, description: &str) {
+    self.description = description.to_string();
+  }
+
+  pub fn value(&self) -> &Value {
+    &self.value
+  }
+
+  pub fn set_value(&mut self, value: Value) {
+    self.value = value;
+  }
+}
+
 #[derive(Clone, Debug)]
 pub struct NodeFinder {
   pub registry: NodeRegistry,
   pub node_filter: NodeFilter,
+  pub presets: PresetLibrary,
   open: bool,
   open_at: Option<emath::Pos2>,
+  /// Index of the matching node selected with the arrow keys.
+  highlight: Option<usize>,
 }
 
 impl Default for NodeFinder {
   fn default() -> Self {
+    Self::new(NodeRegistry::build())
+  }
+}
+
+impl NodeFinder {
+  /// A node finder listing the nodes from `registry`.
+  pub fn new(registry: NodeRegistry) -> Self {
     Self {
-      registry: NodeRegistry::build(),
+      registry,
       node_filter: Default::default(),
+      presets: Default::default(),
       open: false,
       open_at: None,
+      highlight: None,
     }
   }
-}
 
-impl NodeFinder {
   pub fn open_at(&mut self, pos: emath::Pos2) {
     self.open_at = Some(pos);
     self.open = true;
@@ -179,6 +414,53 @@ impl NodeFinder {
   pub fn close(&mut self) {
     self.open = false;
     self.open_at = None;
+    self.highlight = None;
+  }
+
+  /// Move the keyboard highlight by `step` through `count` matching nodes.  Wraps around.
+  fn move_highlight(&mut self, step: isize, count: usize) {
+    if count == 0 {
+      self.highlight = None;
+      return;
+    }
+    let count = count as isize;
+    let idx = match self.highlight {
+      Some(idx) => (idx as isize + step).rem_euclid(count),
+      None if step < 0 => count - 1,
+      None => 0,
+    };
+    self.highlight = Some(idx as usize);
+  }
+
+  /// Handle the arrow and enter keys.  Returns the node id picked with Enter.
+  fn keyboard_nav(&mut self, ui: &egui::Ui, matches: &[NodeId]) -> (Option<NodeId>, bool) {
+    let (up, down, enter) = ui.input_mut(|i| {
+      (
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::Enter),
+      )
+    });
+    if down {
+      self.move_highlight(1, matches.len());
+    }
+    if up {
+      self.move_highlight(-1, matches.len());
+    }
+    if self.highlight.is_some_and(|idx| idx >= matches.len()) {
+      self.highlight = None;
+    }
+    let picked = if enter {
+      match (self.highlight, matches) {
+        (Some(idx), _) => matches.get(idx).copied(),
+        // Only one match, pick it.
+        (None, [id]) => Some(*id),
+        _ => None,
+      }
+    } else {
+      None
+    };
+    (picked, up || down)
   }
 
   pub fn ui(&mut self, ui: &mut egui::Ui) -> Option<Node> {
@@ -210,10 +492,48 @@ impl NodeFinder {
       ui.vertical(|ui| {
         // Title bar.
         ui.label("Create node");
+        // Keyboard navigation.  Handled before the filter's text box gets the keys.
+        let matches = self.registry.matching_nodes(&self.node_filter);
+        let (picked, reveal) = self.keyboard_nav(ui, &matches);
+        if let Some(id) = picked {
+          match self.registry.new_by_id(id) {
+            Ok(new_node) => node = Some(new_node),
+            Err(err) => log::error!("Failed to create node: {err:?}"),
+          }
+        }
         // Node filter UI.
+        let old_filter = self.node_filter.name.clone();
         self.node_filter.ui(ui);
+        if self.node_filter.name != old_filter {
+          self.highlight = None;
+        }
+        // Package facet.
+        let packages = self.registry.packages();
+        if packages.len() > 1 {
+          let package = &mut self.node_filter.package;
+          egui::ComboBox::from_label("Package")
+            .selected_text(package.as_deref().unwrap_or("All"))
+            .show_ui(ui, |ui| {
+              ui.selectable_value(package, None, "All");
+              for name in packages {
+                let label = name.clone();
+                ui.selectable_value(package, Some(name), label);
+              }
+            });
+        }
         // Show available nodes from registry.
-        node = self.registry.ui(ui, &self.node_filter);
+        let highlight = self.highlight.and_then(|idx| matches.get(idx).copied());
+        if let Some(selected) =
+          self
+            .registry
+            .ui_with_highlight(ui, &self.node_filter, highlight, reveal)
+        {
+          node = Some(selected);
+        }
+        // Show matching presets.
+        if let Some(preset) = self.presets.ui(ui, &self.node_filter, &self.registry) {
+          node = Some(preset);
+        }
       });
     });
     node
@@ -228,6 +548,38 @@ struct MenuState {
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
 struct DetailPanelState {
   pub selected_node: Option<NodeId>,
+  /// Keep showing the selected node when the selection changes.
+  #[serde(default)]
+  pub pinned: bool,
+}
+
+/// The name of the default output slot.
+pub const FRAGMENT_OUTPUT: &str = "fragment";
+/// The name of the vertex output slot.
+pub const VERTEX_OUTPUT: &str = "vertex";
+
+/// The default limit on how deep compile and eval follow node connections.
+/// Deeper graphs fail with an error instead of overflowing the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+// Older graphs only had a single output node.
+fn deserialize_outputs<'de, D>(deserializer: D) -> Result<IndexMap<String, NodeId>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Outputs {
+    Named(IndexMap<String, NodeId>),
+    Single(Option<NodeId>),
+  }
+  Ok(match Outputs::deserialize(deserializer)? {
+    Outputs::Named(outputs) => outputs,
+    Outputs::Single(output) => output
+      .map(|id| (FRAGMENT_OUTPUT.to_string(), id))
+      .into_iter()
+      .collect(),
+  })
 }
 
 #[derive(Clone, Default, Debug, Serialize, Deserialize)]
@@ -238,10 +590,21 @@ pub struct NodeGraph {
   nodes: IdMap<Node>,
   groups: IdMap<NodeGroup>,
   connections: ConnectionMap,
-  output: Option<NodeId>,
+  #[serde(default, alias = "output", deserialize_with = "deserialize_outputs")]
+  outputs: IndexMap<String, NodeId>,
+  #[serde(default)]
+  connection_policy: ConnectionPolicy,
+  /// Insert `Convert` nodes when connecting sockets with different data types in the editor.
+  #[serde(default)]
+  insert_adapters: bool,
+  /// The texture format of the fragment output.
+  #[serde(default)]
+  output_format: OutputFormat,
   #[serde(skip)]
   changed: usize,
   #[serde(skip)]
+  dirty: HashSet<NodeId>,
+  #[serde(skip)]
   hover_connection: Option<InputId>,
   #[serde(skip)]
   menu_state: Option<MenuState>,
@@ -252,8 +615,97 @@ pub struct NodeGraph {
   ui_state: NodeGraphMeta,
   #[serde(skip)]
   node_finder: NodeFinder,
+  #[serde(skip)]
+  search: NodeFilter,
+  #[serde(skip)]
+  spatial: SpatialIndex,
+  #[serde(skip)]
+  last_error: Option<(std::time::Instant, String)>,
+  /// The node being dragged in the editor.  Dropping it onto a connection inserts it.
+  #[serde(skip)]
+  dragging_node: Option<NodeId>,
+  /// Insert the node created by the node finder into this connection.
+  #[serde(skip)]
+  finder_connection: Option<InputId>,
+}
+
+/// Counts of the items in a graph.  See `NodeGraph::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphStats {
+  pub nodes: usize,
+  pub connections: usize,
+  pub groups: usize,
+  pub properties: usize,
+}
+
+impl std::fmt::Display for GraphStats {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "nodes: {}, connections: {}, groups: {}, properties: {}",
+      self.nodes, self.connections, self.groups, self.properties
+    )
+  }
+}
+
+/// A parameter that has a different value in two graphs.  See `GraphDiff`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParamChange {
+  pub node: NodeId,
+  pub name: String,
+  pub old: ParameterValue,
+  pub new: ParameterValue,
+}
+
+/// Structural differences between two graphs.  See `NodeGraph::diff`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphDiff {
+  pub added_nodes: Vec<NodeId>,
+  pub removed_nodes: Vec<NodeId>,
+  /// Nodes in both graphs with different serialized state.
+  pub changed_nodes: Vec<NodeId>,
+  pub added_connections: Vec<(InputId, OutputId)>,
+  pub removed_connections: Vec<(InputId, OutputId)>,
+  pub param_changes: Vec<ParamChange>,
+}
+
+impl GraphDiff {
+  pub fn is_empty(&self) -> bool {
+    self.added_nodes.is_empty()
+      && self.removed_nodes.is_empty()
+      && self.changed_nodes.is_empty()
+      && self.added_connections.is_empty()
+      && self.removed_connections.is_empty()
+      && self.param_changes.is_empty()
+  }
+}
+
+/// Result of `NodeGraph::replace_node_type`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeTypeReplacement {
+  /// The replaced nodes.  They keep their ids.
+  pub nodes: Vec<NodeId>,
+  /// Connections that couldn't be restored, because the new node type has no matching
+  /// socket or the data types aren't compatible.
+  pub dropped: Vec<(InputId, OutputId)>,
+  /// Input values that couldn't be restored, with the input of the old node.
+  pub dropped_values: Vec<(InputId, Value)>,
 }
 
+/// Space between the layers and between the nodes in a layer for `NodeGraph::auto_layout`.
+pub const AUTO_LAYOUT_SPACING: emath::Vec2 = emath::vec2(100.0, 50.0);
+/// Size used for nodes that haven't been rendered yet.
+const AUTO_LAYOUT_MIN_SIZE: emath::Vec2 = emath::vec2(200.0, 80.0);
+/// Number of crossing reduction passes.
+const AUTO_LAYOUT_SWEEPS: usize = 4;
+
+/// Horizontal distance between a promoted input's node and its new `Property` node.
+const NODE_PROMOTE_OFFSET: f32 = 250.0;
+
+/// How long connection errors are shown in the editor.
+#[cfg(feature = "egui")]
+const ERROR_TOAST_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+
 impl NodeGraph {
   pub fn new() -> Self {
     Self {
@@ -272,12 +724,37 @@ impl NodeGraph {
     id
   }
 
+  pub fn get_group(&self, group_id: NodeGroupId) -> Result<&NodeGroup> {
+    self
+      .groups
+      .0
+      .get(&group_id)
+      .ok_or_else(|| anyhow!("Missing node group: {group_id:?}"))
+  }
+
+  pub fn get_group_mut(&mut self, group_id: NodeGroupId) -> Result<&mut NodeGroup> {
+    self
+      .groups
+      .0
+      .get_mut(&group_id)
+      .ok_or_else(|| anyhow!("Missing node group: {group_id:?}"))
+  }
+
+  /// Remove a group.  With `delete_nodes` the nested groups and all their nodes are
+  /// also removed, otherwise the group's nodes and child groups move to its parent.
   pub fn remove_group(&mut self, group_id: NodeGroupId, delete_nodes: bool) {
-    self.groups.0.shift_remove(&group_id);
+    let Some(group) = self.groups.0.shift_remove(&group_id) else {
+      return;
+    };
     if delete_nodes {
+      let mut groups = self.group_descendants(group_id);
+      for id in &groups {
+        self.groups.0.shift_remove(id);
+      }
+      groups.push(group_id);
       let mut nodes = Vec::new();
       for (node_id, node) in &mut self.nodes.0 {
-        if node.group_id == group_id {
+        if groups.contains(&node.group_id) {
           nodes.push(*node_id);
         }
       }
@@ -285,151 +762,1457 @@ impl NodeGraph {
         self.remove(node_id);
       }
     } else {
-      for (_, node) in &mut self.nodes.0 {
-        if node.group_id == group_id {
-          node.group_id = Uuid::nil();
-        }
-      }
+      self.reparent_group_contents(group_id, group.parent_id);
     }
+    self.resize_group(group.parent_id);
   }
 
-  pub fn resize_group(&mut self, group_id: NodeGroupId) {
-    if let Some(group) = self.groups.0.get_mut(&group_id) {
-      let mut area = emath::Rect::NOTHING;
-      for (_, node) in &mut self.nodes.0 {
-        if node.group_id == group_id {
-          area = area.union(node.rect());
-        }
+  /// Move the nodes and child groups of `group_id` into `parent_id`.
+  fn reparent_group_contents(&mut self, group_id: NodeGroupId, parent_id: NodeGroupId) {
+    for (_, node) in &mut self.nodes.0 {
+      if node.group_id == group_id {
+        node.group_id = parent_id;
+      }
+    }
+    for (_, group) in &mut self.groups.0 {
+      if group.parent_id == group_id {
+        group.parent_id = parent_id;
       }
-      group.set_area(area);
     }
   }
 
-  /// Returns the `changed` counter to detect when the graph needs to be recompiled.
-  pub fn changed_counter(&self) -> usize {
-    self.changed
-  }
-
-  // Inc. the `changed` counter to detect when the graph needs to be recompiled.
-  fn updated(&mut self) {
-    self.changed += 1;
-  }
-
-  pub fn add(&mut self, mut node: Node) -> NodeId {
-    self.updated();
-    if let Some(position) = &self.editor.add_node_at {
-      node.set_position(*position);
-    }
-    // Check for duplicate node ids.
-    if self.contains(node.id()) {
-      node.new_id();
-    }
-    let id = node.id();
-    self.nodes.0.insert(id, node);
-    id
+  /// Get the groups directly inside `group_id`.
+  pub fn child_groups(&self, group_id: NodeGroupId) -> Vec<NodeGroupId> {
+    self
+      .groups
+      .0
+      .values()
+      .filter(|group| group.parent_id == group_id)
+      .map(|group| group.id)
+      .collect()
   }
 
-  pub fn remove(&mut self, id: NodeId) -> Option<Node> {
-    self.updated();
-    // Remove all connections to the node.
-    self.connections.0.retain(|input, output| {
-      if output.node() == id {
-        // Need to disconnect inputs from the nodes outputs.
-        let node = self.nodes.0.get_mut(&input.node());
-        if let Some(node) = node {
-          if let Err(err) = node.set_input(*input, Input::Disconnect) {
-            log::warn!("Failed to disconnect from input node: {err:?}");
-          }
+  /// Get all the groups nested inside `group_id`.
+  pub fn group_descendants(&self, group_id: NodeGroupId) -> Vec<NodeGroupId> {
+    let mut found = Vec::new();
+    let mut queue = VecDeque::from([group_id]);
+    while let Some(id) = queue.pop_front() {
+      for child in self.child_groups(id) {
+        if child != group_id && !found.contains(&child) {
+          found.push(child);
+          queue.push_back(child);
         }
-        false
-      } else if input.node() == id {
-        // We can just remove the nodes own inputs.
-        false
-      } else {
-        // Keep
-        true
       }
-    });
-    #[cfg(feature = "egui")]
-    {
-      // Remove all UI state for the node
-      self.ui_state.remove_node(id);
     }
-    // Remove node.
-    self.nodes.0.shift_remove(&id)
+    found
   }
 
-  pub fn contains(&self, id: NodeId) -> bool {
-    self.nodes.0.contains_key(&id)
+  /// Get the parents of `group_id`, starting with the direct parent.
+  pub fn group_ancestors(&self, group_id: NodeGroupId) -> Vec<NodeGroupId> {
+    let mut found = Vec::new();
+    let mut id = group_id;
+    while let Some(group) = self.groups.0.get(&id) {
+      id = group.parent_id;
+      if id.is_nil() || id == group_id || found.contains(&id) {
+        break;
+      }
+      found.push(id);
+    }
+    found
   }
 
-  pub fn get_input_id<I: Into<InputKey>>(&self, id: NodeId, idx: I) -> Result<InputId> {
-    let node = self.get(id)?;
-    let idx = node.get_input_idx(&idx.into())?;
-    Ok(InputId::new(id, idx))
+  /// Move `group_id` into `parent_id`.  A nil `parent_id` makes it a top-level group.
+  pub fn set_group_parent(&mut self, group_id: NodeGroupId, parent_id: NodeGroupId) -> Result<()> {
+    let old_parent = self.get_group(group_id)?.parent_id;
+    if !parent_id.is_nil() {
+      self.get_group(parent_id)?;
+      if parent_id == group_id || self.group_descendants(group_id).contains(&parent_id) {
+        return Err(anyhow!("A group can't be nested inside itself"));
+      }
+    }
+    self.get_group_mut(group_id)?.parent_id = parent_id;
+    self.resize_group(old_parent);
+    self.resize_group(parent_id);
+    Ok(())
   }
 
-  pub fn get_node_input<I: Into<InputKey>>(&self, id: NodeId, idx: I) -> Result<Input> {
-    self.get(id).and_then(|n| n.get_input(idx.into()))
+  /// Returns true if the group or one of its parents is locked.
+  pub fn is_group_locked(&self, group_id: NodeGroupId) -> bool {
+    std::iter::once(group_id)
+      .chain(self.group_ancestors(group_id))
+      .any(|id| {
+        self
+          .groups
+          .0
+          .get(&id)
+          .is_some_and(|group| group.is_locked())
+      })
   }
 
-  pub fn set_node_input<I: Into<InputKey>>(
-    &mut self,
-    id: NodeId,
-    key: I,
-    value: Input,
-  ) -> Result<Option<OutputId>> {
-    let key = key.into();
-    // Get node.
-    let node = self
-      .nodes
-      .0
-      .get_mut(&id)
-      .ok_or_else(|| anyhow!("Missing node: {id:?}"))?;
-    // Convert Input key to id.
-    let input_id = node.get_input_idx(&key).map(|idx| InputId::new(id, idx))?;
-    // Update connections.
-    match &value {
-      Input::Disconnect => {
-        if self.connections.0.shift_remove(&input_id).is_none() {
-          // The input was already disconnected.  No change.
-          return Ok(None);
-        }
+  /// Move the nodes and nested groups of `group_id` by `delta`.  The group itself isn't moved.
+  fn move_group_contents(&mut self, group_id: NodeGroupId, delta: emath::Vec2) {
+    let mut groups = self.group_descendants(group_id);
+    for id in &groups {
+      if let Some(group) = self.groups.0.get_mut(id) {
+        group.translate(delta);
       }
-      Input::Connect(output_id, _) => {
-        self.connections.0.insert(input_id, *output_id);
+    }
+    groups.push(group_id);
+    for (_, node) in &mut self.nodes.0 {
+      if groups.contains(&node.group_id) {
+        node.set_position(node.area.min.to_vec2() + delta);
       }
-      _ => {}
     }
-    // Set the node input.
-    let old = node.set_input(key, value.clone())?;
-    // Mark graph as updated.
-    self.updated();
-    Ok(old)
-  }
-
-  pub fn set_input(&mut self, input_id: InputId, value: Input) -> Result<Option<OutputId>> {
-    self.set_node_input(input_id.node(), input_id, value)
+    self.spatial.invalidate();
   }
 
-  pub fn disconnect(&mut self, input: InputId) -> Result<()> {
-    self.set_input(input, Input::Disconnect)?;
-    Ok(())
+  /// Get the group indices ordered so that parent groups come before their children.
+  fn groups_by_depth(&self) -> Vec<usize> {
+    let mut order: Vec<(usize, usize)> = self
+      .groups
+      .0
+      .keys()
+      .enumerate()
+      .map(|(idx, id)| (self.group_ancestors(*id).len(), idx))
+      .collect();
+    order.sort_unstable();
+    order.into_iter().map(|(_, idx)| idx).collect()
   }
 
-  pub fn connect(&mut self, input: InputId, output: OutputId, dt: DataType) -> Result<()> {
-    self.set_input(input, Input::Connect(output, Some(dt)))?;
-    Ok(())
+  /// Collapse a group into a `SubGraphNode`.
+  ///
+  /// The connections crossing the group's boundary become the inputs and outputs
+  /// of the new node.  Inputs connected to the same output share one port.
+  /// The graph is left unchanged if the new node can't be connected.
+  pub fn collapse_group(&mut self, group_id: NodeGroupId) -> Result<NodeId> {
+    self.transaction(|graph| graph.try_collapse_group(group_id))
   }
 
-  pub fn get(&self, id: NodeId) -> Result<&Node> {
-    self
+  fn try_collapse_group(&mut self, group_id: NodeGroupId) -> Result<NodeId> {
+    let members: IndexSet<NodeId> = self
       .nodes
       .0
-      .get(&id)
-      .ok_or_else(|| anyhow!("Missing node: {id:?}"))
-  }
-
+      .values()
+      .filter(|node| node.group_id == group_id)
+      .map(|node| node.id)
+      .collect();
+    if members.is_empty() {
+      return Err(anyhow!("Group has no nodes: {group_id:?}"));
+    }
+    if self.outputs.values().any(|id| members.contains(id)) {
+      return Err(anyhow!("Can't collapse a group containing an output node"));
+    }
+    let types = self.infer_types();
+    let output_type = |graph: &Self, id: OutputId| {
+      types
+        .get(&id)
+        .copied()
+        .or_else(|| graph.output_data_type(id))
+        .unwrap_or(DataType::Dynamic)
+    };
+
+    // Move the nodes into the subgraph.
+    let mut inner = NodeGraph::new();
+    inner.connection_policy = self.connection_policy;
+    let mut area = emath::Rect::NOTHING;
+    for id in &members {
+      let mut node = self.get(*id)?.clone();
+      node.group_id = Uuid::nil();
+      area = area.union(node.area);
+      inner.nodes.0.insert(*id, node);
+    }
+    // Copy the properties used by the nodes.
+    let mut properties = IdMap::default();
+    for node in inner.nodes.0.values() {
+      if let Some(property) = node
+        .as_property()
+        .and_then(|node| self.property(node.property()).ok())
+      {
+        properties.0.insert(property.id, property.clone());
+      }
+    }
+    let properties = inner.merge_properties(&properties);
+    for node in inner.nodes.0.values_mut() {
+      node.remap_property(&properties);
+    }
+
+    // Map the boundary-crossing connections to ports.
+    let mut inputs = Vec::new();
+    let mut input_ports = IndexSet::new();
+    let mut outputs = Vec::new();
+    let mut output_ports = IndexSet::new();
+    let mut outgoing = Vec::new();
+    let connections: Vec<_> = self.connections.iter().map(|(i, o)| (*i, *o)).collect();
+    for (input, output) in connections {
+      let dt = output_type(self, output);
+      match (
+        members.contains(&input.node),
+        members.contains(&output.node),
+      ) {
+        (true, true) => {
+          inner.set_input(input, Input::Connect(output, Some(dt)))?;
+        }
+        (true, false) => {
+          let (idx, new) = input_ports.insert_full(output);
+          if new {
+            let node = inner.get(input.node)?;
+            let name = node
+              .def()
+              .inputs
+              .get_index(input.idx as usize)
+              .map(|(name, _)| name.as_str())
+              .unwrap_or("Input");
+            let name = subgraph_port_name(inputs.iter().map(|p: &SubGraphInput| &p.name), name);
+            let mut port = Node::new(SubGraphInputNode::new().def())?;
+            port.set_position(node.area.min.to_vec2() - emath::vec2(150., 0.));
+            let port = inner.add(port);
+            inputs.push(SubGraphInput::new(&name, dt, port));
+          }
+          let port = OutputId::new(inputs[idx].port, 0);
+          inner.set_input(input, Input::Connect(port, Some(dt)))?;
+        }
+        (false, true) => {
+          let (idx, new) = output_ports.insert_full(output);
+          if new {
+            let name = inner
+              .get(output.node)?
+              .def()
+              .outputs
+              .get_index(output.idx as usize)
+              .map(|(name, _)| name.as_str())
+              .unwrap_or("Output");
+            let name = subgraph_port_name(outputs.iter().map(|p: &SubGraphOutput| &p.name), name);
+            outputs.push(SubGraphOutput {
+              name,
+              value_type: dt,
+              output,
+            });
+          }
+          outgoing.push((input, idx as u32, dt));
+        }
+        (false, false) => (),
+      }
+    }
+
+    // Replace the group with the subgraph node.
+    for id in &members {
+      self.remove(*id);
+    }
+    if let Some(group) = self.groups.0.shift_remove(&group_id) {
+      self.reparent_group_contents(group_id, group.parent_id);
+    }
+    let input_types: Vec<_> = inputs.iter().map(|p| p.value_type).collect();
+    let mut node = Node::from_impl(Box::new(SubGraphNode::from_parts(inner, inputs, outputs)));
+    node.set_position(area.min.to_vec2());
+    let id = self.add(node);
+    for (idx, (output, dt)) in input_ports.into_iter().zip(input_types).enumerate() {
+      self.set_input(
+        InputId::new(id, idx as u32),
+        Input::Connect(output, Some(dt)),
+      )?;
+    }
+    for (input, idx, dt) in outgoing {
+      self.set_input(input, Input::Connect(OutputId::new(id, idx), Some(dt)))?;
+    }
+    Ok(id)
+  }
+
+  /// Expand a `SubGraphNode` back into its nodes.
+  ///
+  /// The contained nodes get new ids and the connections to the subgraph's ports
+  /// are rewired to them.  Returns the ids of the new nodes.
+  /// The graph is left unchanged if the nodes can't be rewired.
+  pub fn expand_subgraph(&mut self, id: NodeId) -> Result<Vec<NodeId>> {
+    self.transaction(|graph| graph.try_expand_subgraph(id))
+  }
+
+  fn try_expand_subgraph(&mut self, id: NodeId) -> Result<Vec<NodeId>> {
+    let node = self.get(id)?;
+    let sub = node
+      .as_subgraph()
+      .ok_or_else(|| anyhow!("Not a subgraph node: {id:?}"))?
+      .clone();
+    let position = node.area.min;
+    let ports: HashMap<NodeId, Input> = sub
+      .inputs()
+      .iter()
+      .map(|port| (port.port, port.as_input()))
+      .collect();
+    let outgoing: Vec<_> = (0..sub.outputs().len())
+      .map(|idx| self.output_consumers(OutputId::new(id, idx as u32)))
+      .collect();
+    self.remove(id);
+
+    // Insert the contained nodes with new ids, keeping their layout.
+    let inner = sub.graph();
+    let mut area = emath::Rect::NOTHING;
+    for (node_id, node) in &inner.nodes.0 {
+      if !ports.contains_key(node_id) {
+        area = area.union(node.area);
+      }
+    }
+    let offset = position - area.min;
+    let properties = self.merge_properties(&inner.properties);
+    let mut ids = IndexMap::new();
+    for (node_id, node) in &inner.nodes.0 {
+      if ports.contains_key(node_id) {
+        continue;
+      }
+      let mut node = node.duplicate();
+      node.group_id = Uuid::nil();
+      node.area = node.area.translate(offset);
+      node.remap_property(&properties);
+      let new_id = node.id;
+      self.node_updated(new_id);
+      self.nodes.0.insert(new_id, node);
+      ids.insert(*node_id, new_id);
+    }
+    let map_node = |node: NodeId| {
+      ids
+        .get(&node)
+        .copied()
+        .ok_or_else(|| anyhow!("Missing subgraph node: {node:?}"))
+    };
+    let map_output =
+      |output: OutputId| -> Result<_> { Ok(OutputId::new(map_node(output.node)?, output.idx)) };
+
+    // Rewire the internal connections and the inputs fed by the ports.
+    for (input, output) in &inner.connections {
+      let input = InputId::new(map_node(input.node)?, input.idx);
+      let value = match ports.get(&output.node) {
+        Some(port) => port.clone(),
+        None => Input::Connect(map_output(*output)?, None),
+      };
+      self.set_input(input, value)?;
+    }
+    // Rewire the consumers of the subgraph's outputs.
+    for (port, inputs) in sub.outputs().iter().zip(outgoing) {
+      let output = map_output(port.output)?;
+      for input in inputs {
+        self.set_input(input, Input::Connect(output, Some(port.value_type)))?;
+      }
+    }
+    Ok(ids.into_values().collect())
+  }
+
+  /// Insert all nodes and groups of `other` with new ids, keeping their layout with
+  /// the top-left corner at `offset`.  Returns the ids of the inserted nodes.
+  ///
+  /// Properties are only added if the name isn't used yet and outputs are only added
+  /// if this graph doesn't already have an output with the same name.
+  /// Nothing is inserted if `other` can't be merged, e.g. it has dangling node ids.
+  pub fn merge(&mut self, other: NodeGraph, offset: emath::Vec2) -> Result<Vec<NodeId>> {
+    self.transaction(|graph| graph.try_merge(other, offset))
+  }
+
+  fn try_merge(&mut self, other: NodeGraph, offset: emath::Vec2) -> Result<Vec<NodeId>> {
+    let mut area = emath::Rect::NOTHING;
+    for node in other.nodes.0.values() {
+      area = area.union(node.area);
+    }
+    let delta = if area.is_finite() {
+      offset - area.min.to_vec2()
+    } else {
+      offset
+    };
+
+    let groups: HashMap<NodeGroupId, NodeGroupId> = other
+      .groups
+      .0
+      .keys()
+      .map(|id| (*id, Uuid::new_v4()))
+      .collect();
+    let map_group = |id: NodeGroupId| groups.get(&id).copied().unwrap_or(Uuid::nil());
+    for group in other.groups.0.values() {
+      let mut group = group.clone();
+      group.id = map_group(group.id);
+      group.parent_id = map_group(group.parent_id);
+      group.translate(delta);
+      self.groups.0.insert(group.id, group);
+    }
+
+    let properties = self.merge_properties(&other.properties);
+    let mut ids = IndexMap::new();
+    for (node_id, node) in &other.nodes.0 {
+      let mut node = node.duplicate();
+      node.group_id = map_group(node.group_id);
+      node.area = node.area.translate(delta);
+      node.remap_property(&properties);
+      let new_id = node.id;
+      self.node_updated(new_id);
+      self.nodes.0.insert(new_id, node);
+      ids.insert(*node_id, new_id);
+    }
+    let map_node = |id: NodeId| {
+      ids
+        .get(&id)
+        .copied()
+        .ok_or_else(|| anyhow!("Missing node: {id:?}"))
+    };
+    for (input, output) in &other.connections {
+      // Keep the connection's stored data type.
+      let dt = match other.get(input.node)?.get_input(input.idx)? {
+        Input::Connect(_, dt) => dt,
+        _ => None,
+      };
+      let input = InputId::new(map_node(input.node)?, input.idx);
+      let output = OutputId::new(map_node(output.node)?, output.idx);
+      self.set_input(input, Input::Connect(output, dt))?;
+    }
+
+    for (name, id) in &other.outputs {
+      if !self.outputs.contains_key(name) {
+        self.outputs.insert(name.clone(), map_node(*id)?);
+      }
+    }
+    self.updated();
+    Ok(ids.into_values().collect())
+  }
+
+  /// Clone the graph with new ids for the graph, its nodes and groups.
+  ///
+  /// Connections, outputs and group memberships are remapped to the new ids.  Unlike
+  /// `clone` the copy doesn't share the editor's UI state, so both can be edited
+  /// independently.  Properties keep their ids, they are only used inside the graph.
+  pub fn clone_fresh(&self) -> Result<Self> {
+    self.clone_with_new_ids().map(|(graph, _)| graph)
+  }
+
+  /// Same as `clone_fresh`, but also returns the map from the old to the new node ids.
+  pub(crate) fn clone_with_new_ids(&self) -> Result<(Self, HashMap<NodeId, NodeId>)> {
+    let group_ids: HashMap<NodeGroupId, NodeGroupId> = self
+      .groups
+      .0
+      .keys()
+      .map(|id| (*id, Uuid::new_v4()))
+      .collect();
+    let map_group = |id: NodeGroupId| group_ids.get(&id).copied().unwrap_or(Uuid::nil());
+    let mut groups = IdMap::default();
+    for group in self.groups.0.values() {
+      let mut group = group.clone();
+      group.id = map_group(group.id);
+      group.parent_id = map_group(group.parent_id);
+      groups.0.insert(group.id, group);
+    }
+
+    let mut ids = HashMap::new();
+    let mut nodes = IdMap::default();
+    for (node_id, node) in &self.nodes.0 {
+      let mut node = node.duplicate();
+      node.group_id = map_group(node.group_id);
+      ids.insert(*node_id, node.id);
+      nodes.0.insert(node.id, node);
+    }
+    let map_node = |id: &NodeId| {
+      ids
+        .get(id)
+        .copied()
+        .ok_or_else(|| anyhow!("Missing node: {id:?}"))
+    };
+    let mut connections = ConnectionMap::default();
+    for (input, output) in &self.connections {
+      let input = InputId::new(map_node(&input.node)?, input.idx);
+      let output = OutputId::new(map_node(&output.node)?, output.idx);
+      // The nodes also store their connections.
+      let node = nodes
+        .0
+        .get_mut(&input.node)
+        .ok_or_else(|| anyhow!("Missing node: {:?}", input.node))?;
+      let dt = match node.get_input(input.idx)? {
+        Input::Connect(_, dt) => dt,
+        _ => None,
+      };
+      node.set_input(input.idx, Input::Connect(output, dt))?;
+      connections.insert(input, output);
+    }
+    let outputs = self
+      .outputs
+      .iter()
+      .map(|(name, id)| Ok((name.clone(), map_node(id)?)))
+      .collect::<Result<_>>()?;
+
+    let mut graph = Self {
+      id: Uuid::new_v4(),
+      editor: self.editor.clone(),
+      properties: self.properties.clone(),
+      nodes,
+      groups,
+      connections,
+      outputs,
+      connection_policy: self.connection_policy,
+      insert_adapters: self.insert_adapters,
+      output_format: self.output_format,
+      node_finder: NodeFinder::new(self.registry().clone()),
+      ..Self::default()
+    };
+    graph.node_finder.presets = self.node_finder.presets.clone();
+    graph.dirty = graph.nodes.0.keys().copied().collect();
+    graph.updated();
+    Ok((graph, ids))
+  }
+
+  /// Get the indices of the nodes that need to be rendered for the graph-space `view`.
+  ///
+  /// These are the nodes near the view and the nodes that still need to update their frame.
+  #[cfg(feature = "egui")]
+  fn visible_nodes(&mut self, view: emath::Rect) -> Vec<usize> {
+    self
+      .spatial
+      .update(self.nodes.0.iter().map(|(id, node)| (*id, node.area)));
+    let mut ids = self.spatial.query(view.expand(NODE_GROUP_MARGIN));
+    ids.extend(self.spatial.take_pending());
+    ids.extend(
+      self
+        .dirty
+        .iter()
+        .filter(|id| self.nodes.0.get(*id).is_some_and(|node| node.updated)),
+    );
+    let mut indices: Vec<usize> = ids
+      .iter()
+      .filter_map(|id| self.nodes.0.get_index_of(id))
+      .collect();
+    // Keep the render order stable.
+    indices.sort_unstable();
+    indices
+  }
+
+  /// Fit the group around its nodes and child groups.  The parent groups are also resized.
+  pub fn resize_group(&mut self, group_id: NodeGroupId) {
+    let groups = std::iter::once(group_id).chain(self.group_ancestors(group_id));
+    for group_id in groups.collect::<Vec<_>>() {
+      let mut area = emath::Rect::NOTHING;
+      for (_, node) in &self.nodes.0 {
+        if node.group_id == group_id {
+          area = area.union(node.area);
+        }
+      }
+      for (_, group) in &self.groups.0 {
+        if group.parent_id == group_id {
+          area = area.union(group.area());
+        }
+      }
+      if let Some(group) = self.groups.0.get_mut(&group_id) {
+        group.set_area(area);
+      }
+    }
+  }
+
+  /// Add a graph property.  Returns the property id.
+  pub fn add_property(&mut self, property: NodeGraphProperty) -> Uuid {
+    let id = property.id;
+    self.properties.0.insert(id, property);
+    self.updated();
+    id
+  }
+
+  pub fn property(&self, id: Uuid) -> Result<&NodeGraphProperty> {
+    self
+      .properties
+      .0
+      .get(&id)
+      .ok_or_else(|| anyhow!("Missing property: {id:?}"))
+  }
+
+  pub fn property_mut(&mut self, id: Uuid) -> Result<&mut NodeGraphProperty> {
+    // The nodes using the property need to be recompiled.
+    let nodes: Vec<NodeId> = self
+      .nodes
+      .0
+      .values()
+      .filter(|node| node.as_property().is_some_and(|node| node.property() == id))
+      .map(|node| node.id)
+      .collect();
+    for node in nodes {
+      self.node_updated(node);
+    }
+    self.updated();
+    self
+      .properties
+      .0
+      .get_mut(&id)
+      .ok_or_else(|| anyhow!("Missing property: {id:?}"))
+  }
+
+  pub fn properties(&self) -> impl Iterator<Item = &NodeGraphProperty> {
+    self.properties.0.values()
+  }
+
+  /// Add the `properties` of another graph.  Properties with a name already used by
+  /// this graph are replaced by the existing property.  Returns the map from the old
+  /// to the new property ids.
+  fn merge_properties(&mut self, properties: &IdMap<NodeGraphProperty>) -> HashMap<Uuid, Uuid> {
+    let mut ids = HashMap::new();
+    for property in properties.0.values() {
+      if let Some(existing) = self.properties().find(|p| p.name == property.name) {
+        ids.insert(property.id, existing.id);
+        continue;
+      }
+      let old_id = property.id;
+      let mut property = property.clone();
+      if self.properties.0.contains_key(&property.id) {
+        property.id = Uuid::new_v4();
+      }
+      ids.insert(old_id, property.id);
+      self.properties.0.insert(property.id, property);
+    }
+    ids
+  }
+
+  /// Replace the value of an unconnected input with a graph property.
+  ///
+  /// A new property is created from the input's current value and a `Property` node
+  /// is connected to the input.  Returns the ids of the property and the new node.
+  /// Neither is added if the node can't be connected.
+  pub fn promote_to_property(&mut self, input: InputId) -> Result<(Uuid, NodeId)> {
+    self.transaction(|graph| graph.try_promote_to_property(input))
+  }
+
+  fn try_promote_to_property(&mut self, input: InputId) -> Result<(Uuid, NodeId)> {
+    let node = self.get(input.node())?;
+    let value = match node.get_input(input)? {
+      Input::Value(value) => value,
+      _ => {
+        return Err(anyhow!(
+          "Only unconnected inputs can be promoted to a property"
+        ))
+      }
+    };
+    let input_name = node
+      .def()
+      .get_input(&input.key())
+      .map(|def| def.name.clone())
+      .unwrap_or_default();
+    let base = format!("{} {input_name}", node.display_name());
+    let position = node.area.min.to_vec2() - emath::vec2(NODE_PROMOTE_OFFSET, 0.);
+    let mut name = base.clone();
+    let mut n = 1;
+    while self.properties().any(|p| p.name == name) {
+      n += 1;
+      name = format!("{base} {n}");
+    }
+
+    let dt = value.data_type();
+    let property = NodeGraphProperty::new(&name, value);
+    let prop_node = crate::nodes::basic::PropertyNode::with_property(&property);
+    let property_id = self.add_property(property);
+    let mut node = Node::from_impl(Box::new(prop_node));
+    node.set_display_name(name);
+    node.set_position(position);
+    let node_id = self.add(node);
+    self.connect(input, OutputId::new(node_id, 0), dt)?;
+    Ok((property_id, node_id))
+  }
+
+  /// Replace every node of type `from` with a new node of type `to`.
+  ///
+  /// Sockets are matched by name, falling back to their index.  Connections and input
+  /// values are kept when the new socket accepts their data type.  The dropped
+  /// connections and values are returned.
+  /// The graph is left unchanged if a node can't be replaced.
+  pub fn replace_node_type(
+    &mut self,
+    reg: &NodeRegistry,
+    from: Uuid,
+    to: Uuid,
+  ) -> Result<NodeTypeReplacement> {
+    self.transaction(|graph| graph.try_replace_node_type(reg, from, to))
+  }
+
+  fn try_replace_node_type(
+    &mut self,
+    reg: &NodeRegistry,
+    from: Uuid,
+    to: Uuid,
+  ) -> Result<NodeTypeReplacement> {
+    let mut report = NodeTypeReplacement::default();
+    // Fail before changing anything if `to` isn't registered.
+    reg.new_by_id(to)?;
+    let ids: Vec<NodeId> = self
+      .nodes
+      .0
+      .values()
+      .filter(|node| node.def().id == from)
+      .map(|node| node.id)
+      .collect();
+    for id in ids {
+      let old = self.get(id)?;
+      let old_def = old.def().clone();
+      let inputs = (0..old.input_count() as u32)
+        .map(|idx| Ok((idx, old.get_input(idx)?)))
+        .collect::<Result<Vec<_>>>()?;
+      let outputs: Vec<_> = (0..old_def.outputs.len() as u32)
+        .map(|idx| (idx, self.output_consumers(OutputId::new(id, idx))))
+        .collect();
+
+      let mut node = reg.new_by_id(to)?;
+      node.id = id;
+      node.group_id = old.group_id;
+      node.area = old.area;
+      node.set_locked(old.is_locked());
+      if old.is_renamed() {
+        node.set_display_name(old.display_name());
+      }
+      let new_def = node.def().clone();
+      let input_count = node.input_count() as u32;
+
+      // Swap the node, dropping all of its connections.
+      for (idx, _) in &outputs {
+        self.disconnect_output(OutputId::new(id, *idx));
+      }
+      for (idx, _) in &inputs {
+        self.connections.remove(&InputId::new(id, *idx));
+      }
+      #[cfg(feature = "egui")]
+      self.ui_state.remove_node(id);
+      self.nodes.0.insert(id, node);
+      self.node_updated(id);
+      report.nodes.push(id);
+
+      // Restore the inputs.
+      for (idx, input) in inputs {
+        let new_idx = old_def
+          .inputs
+          .get_index(idx as usize)
+          .and_then(|(name, _)| new_def.inputs.get_index_of(name))
+          .map(|idx| idx as u32)
+          .or_else(|| (idx < input_count).then_some(idx));
+        let value = match input {
+          Input::Connect(output, _) => {
+            let dt = self.output_data_type(output);
+            match new_idx {
+              Some(new_idx) => {
+                let new_input = InputId::new(id, new_idx);
+                if self
+                  .set_input(new_input, Input::Connect(output, dt))
+                  .is_err()
+                {
+                  report.dropped.push((InputId::new(id, idx), output));
+                }
+              }
+              None => report.dropped.push((InputId::new(id, idx), output)),
+            }
+            continue;
+          }
+          Input::Value(value) => value,
+          Input::Disconnect => continue,
+        };
+        // Values of the wrong type keep the new node's default.
+        let restored = new_idx.is_some_and(|new_idx| {
+          self
+            .set_input(InputId::new(id, new_idx), Input::Value(value.clone()))
+            .is_ok()
+        });
+        if !restored {
+          report.dropped_values.push((InputId::new(id, idx), value));
+        }
+      }
+
+      // Restore the consumers of the outputs.
+      for (idx, consumers) in outputs {
+        let new_idx = old_def
+          .outputs
+          .get_index(idx as usize)
+          .and_then(|(name, _)| new_def.outputs.get_index_of(name))
+          .or_else(|| (idx < new_def.outputs.len() as u32).then_some(idx as usize));
+        let old_output = OutputId::new(id, idx);
+        for input in consumers {
+          let res = match new_idx {
+            Some(new_idx) => {
+              let output = OutputId::new(id, new_idx as u32);
+              let dt = self.output_data_type(output);
+              self
+                .set_input(input, Input::Connect(output, dt))
+                .map(|_| ())
+            }
+            None => Err(anyhow!("Missing output")),
+          };
+          if res.is_err() {
+            report.dropped.push((input, old_output));
+          }
+        }
+      }
+    }
+    Ok(report)
+  }
+
+  /// Count the nodes, connections, groups and properties in the graph.
+  pub fn stats(&self) -> GraphStats {
+    GraphStats {
+      nodes: self.nodes.0.len(),
+      connections: self.connections.len(),
+      groups: self.groups.0.len(),
+      properties: self.properties.0.len(),
+    }
+  }
+
+  /// Compare this graph to `other`.  Items only in `other` are reported as added.
+  pub fn diff(&self, other: &NodeGraph) -> GraphDiff {
+    let mut diff = GraphDiff::default();
+    for (id, node) in &self.nodes.0 {
+      let other_node = match other.nodes.0.get(id) {
+        Some(other_node) => other_node,
+        None => {
+          diff.removed_nodes.push(*id);
+          continue;
+        }
+      };
+      if serde_json::to_value(node).ok() != serde_json::to_value(other_node).ok() {
+        diff.changed_nodes.push(*id);
+      }
+      for name in node.def().parameters.keys() {
+        if let (Ok(old), Ok(new)) = (node.get_param(name), other_node.get_param(name)) {
+          if old != new {
+            diff.param_changes.push(ParamChange {
+              node: *id,
+              name: name.clone(),
+              old,
+              new,
+            });
+          }
+        }
+      }
+    }
+    diff.added_nodes = other
+      .nodes
+      .0
+      .keys()
+      .filter(|id| !self.nodes.0.contains_key(*id))
+      .copied()
+      .collect();
+
+    for (input, output) in &self.connections.inputs {
+      if other.connections.inputs.get(input) != Some(output) {
+        diff.removed_connections.push((*input, *output));
+      }
+    }
+    for (input, output) in &other.connections.inputs {
+      if self.connections.inputs.get(input) != Some(output) {
+        diff.added_connections.push((*input, *output));
+      }
+    }
+    diff
+  }
+
+  /// Returns the `changed` counter to detect when the graph needs to be recompiled.
+  pub fn changed_counter(&self) -> usize {
+    self.changed
+  }
+
+  // Inc. the `changed` counter to detect when the graph needs to be recompiled.
+  fn updated(&mut self) {
+    self.changed += 1;
+    self.spatial.invalidate();
+  }
+
+  /// Run the edit `f` and restore the graph if it fails, so edits made of multiple
+  /// fallible steps are all-or-nothing.
+  fn transaction<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R>) -> Result<R> {
+    let backup = self.clone();
+    let res = f(self);
+    if res.is_err() {
+      *self = backup;
+    }
+    res
+  }
+
+  // Mark a node as changed since the last compile.
+  fn node_updated(&mut self, id: NodeId) {
+    self.updated();
+    self.dirty.insert(id);
+  }
+
+  /// Returns the nodes that have changed since the last call to `take_dirty`.
+  pub fn dirty_nodes(&self) -> &HashSet<NodeId> {
+    &self.dirty
+  }
+
+  /// Take the set of changed nodes.  Used with `NodeGraphCompile::recompile`.
+  pub fn take_dirty(&mut self) -> HashSet<NodeId> {
+    core::mem::take(&mut self.dirty)
+  }
+
+  /// Compile the graph to a WGSL shader.
+  ///
+  /// Uses the standard block layout: `imports`, `bindings` and then the blocks of the
+  /// output nodes.  Use `NodeGraphCompile` directly for a custom layout.
+  pub fn compile_to_wgsl(&self) -> Result<String> {
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.define_block("fragment");
+    compile.compile_graph(self)?;
+    Ok(compile.dump())
+  }
+
+  /// Evaluate the graph's output node on the CPU.
+  ///
+  /// Uses a fresh `NodeGraphExecution` for each call.  Use `NodeGraphExecution` directly
+  /// to keep cached values or set context values between evaluations.
+  pub fn evaluate(&self) -> Result<Value> {
+    let id = self
+      .output()
+      .ok_or_else(|| anyhow!("Graph missing output node"))?;
+    self.evaluate_node(id)
+  }
+
+  /// Evaluate the first output of node `id` on the CPU.
+  pub fn evaluate_node(&self, id: NodeId) -> Result<Value> {
+    let def = self.get(id)?.def();
+    if !def.supports_eval {
+      return Err(anyhow!("`{}` nodes can only be compiled", def.name));
+    }
+    NodeGraphExecution::new().eval_node(self, id)
+  }
+
+  /// Returns `nodes` and all nodes connected (directly or indirectly) to their outputs.
+  pub fn downstream_nodes(&self, nodes: &HashSet<NodeId>) -> HashSet<NodeId> {
+    let mut found = HashSet::new();
+    let mut queue: VecDeque<NodeId> = nodes.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+      if !found.insert(id) {
+        continue;
+      }
+      let outputs = match self.nodes.0.get(&id) {
+        Some(node) => node.def().outputs.len(),
+        None => 0,
+      };
+      for idx in 0..outputs {
+        for input in self.connections.consumers(&OutputId::new(id, idx as u32)) {
+          queue.push_back(input.node());
+        }
+      }
+    }
+    found
+  }
+
+  /// Returns the nodes that don't feed (directly or indirectly) any of the graph's outputs.
+  ///
+  /// If the graph has no outputs, all nodes are unreachable.
+  pub fn unreachable_nodes(&self) -> Vec<NodeId> {
+    let mut found = HashSet::new();
+    let mut queue: VecDeque<NodeId> = self.outputs.values().copied().collect();
+    while let Some(id) = queue.pop_front() {
+      if !found.insert(id) {
+        continue;
+      }
+      let inputs = match self.nodes.0.get(&id) {
+        Some(node) => node.input_count(),
+        None => 0,
+      };
+      for idx in 0..inputs {
+        if let Some(output) = self.connections.get(&InputId::new(id, idx as u32)) {
+          queue.push_back(output.node());
+        }
+      }
+    }
+    self
+      .nodes
+      .0
+      .keys()
+      .filter(|id| !found.contains(*id))
+      .copied()
+      .collect()
+  }
+
+  /// Remove the nodes that don't feed any of the graph's outputs.  Does nothing
+  /// if the graph has no outputs.
+  pub fn remove_unreachable_nodes(&mut self) -> Vec<Node> {
+    if self.outputs.is_empty() {
+      return Vec::new();
+    }
+    self
+      .unreachable_nodes()
+      .into_iter()
+      .filter_map(|id| self.remove(id))
+      .collect()
+  }
+
+  pub fn add(&mut self, mut node: Node) -> NodeId {
+    if let Some(position) = &self.editor.add_node_at {
+      node.set_position(*position);
+    }
+    // Check for duplicate node ids.
+    if self.contains(node.id()) {
+      node.new_id();
+    }
+    let id = node.id();
+    self.node_updated(id);
+    self.nodes.0.insert(id, node);
+    id
+  }
+
+  pub fn remove(&mut self, id: NodeId) -> Option<Node> {
+    self.updated();
+    self.dirty.remove(&id);
+    // Remove all connections to the node.
+    let connections: Vec<(InputId, OutputId)> = self
+      .connections
+      .iter()
+      .filter(|(input, output)| input.node() == id || output.node() == id)
+      .map(|(input, output)| (*input, *output))
+      .collect();
+    for (input, output) in connections {
+      if output.node() == id && input.node() != id {
+        // Need to disconnect inputs from the nodes outputs.
+        if let Some(node) = self.nodes.0.get_mut(&input.node()) {
+          if let Err(err) = node.set_input(input, Input::Disconnect) {
+            log::warn!("Failed to disconnect from input node: {err:?}");
+          }
+        }
+        self.dirty.insert(input.node());
+      }
+      // We can just remove the nodes own inputs.
+      self.connections.remove(&input);
+    }
+    #[cfg(feature = "egui")]
+    {
+      // Remove all UI state for the node
+      self.ui_state.remove_node(id);
+    }
+    self.outputs.retain(|_, node| *node != id);
+    // Remove node.
+    self.nodes.0.shift_remove(&id)
+  }
+
+  pub fn contains(&self, id: NodeId) -> bool {
+    self.nodes.0.contains_key(&id)
+  }
+
+  pub fn get_input_id<I: Into<InputKey>>(&self, id: NodeId, idx: I) -> Result<InputId> {
+    let node = self.get(id)?;
+    let idx = node.get_input_idx(&idx.into())?;
+    Ok(InputId::new(id, idx))
+  }
+
+  pub fn get_node_input<I: Into<InputKey>>(&self, id: NodeId, idx: I) -> Result<Input> {
+    self.get(id).and_then(|n| n.get_input(idx.into()))
+  }
+
+  pub fn set_node_input<I: Into<InputKey>>(
+    &mut self,
+    id: NodeId,
+    key: I,
+    value: Input,
+  ) -> Result<Option<OutputId>> {
+    let key = key.into();
+    // Check the connection against the graph's connection policy.
+    if let Input::Connect(output_id, dt) = &value {
+      let node = self.get(id)?;
+      let input_dt = node
+        .def()
+        .get_input(&key)
+        .map(|def| def.value_type)
+        .ok_or_else(|| anyhow!("Invalid input key: {key:?}"))?;
+      let output_dt = match dt {
+        Some(dt) => Some(*dt),
+        None => self.output_data_type(*output_id),
+      };
+      if let Some(output_dt) = output_dt {
+        if !self.connection_policy.is_compatible(&input_dt, &output_dt) {
+          return Err(anyhow!(
+            "Incompatible connection: input {input_dt:?}, output {output_dt:?}"
+          ));
+        }
+      }
+    }
+    // Get node.
+    let node = self
+      .nodes
+      .0
+      .get_mut(&id)
+      .ok_or_else(|| anyhow!("Missing node: {id:?}"))?;
+    // Convert Input key to id.
+    let input_id = node.get_input_idx(&key).map(|idx| InputId::new(id, idx))?;
+    if input_id.idx as usize >= node.input_count() {
+      return Err(anyhow!("Invalid input key: {key:?}"));
+    }
+    let current = self.connections.get(&input_id);
+    match &value {
+      // The input was already disconnected.  No change.
+      Input::Disconnect if current.is_none() => return Ok(None),
+      // Already connected to the same output with the same data type.  No change.
+      // Without a data type the stored one is kept.
+      Input::Connect(output_id, dt) if current == Some(output_id) => {
+        let same_dt = match node.get_input(key.clone())? {
+          Input::Connect(_, old_dt) => dt.is_none() || old_dt == *dt,
+          _ => false,
+        };
+        if same_dt {
+          return Ok(None);
+        }
+      }
+      _ => (),
+    }
+    // Set the node input.  Rejected values/connections leave the connections unchanged.
+    let old = node.set_input(key, value.clone())?;
+    // Update connections.
+    match &value {
+      Input::Disconnect => {
+        self.connections.remove(&input_id);
+      }
+      Input::Connect(output_id, _) => {
+        self.connections.insert(input_id, *output_id);
+      }
+      _ => {}
+    }
+    // Mark graph as updated.
+    self.node_updated(id);
+    Ok(old)
+  }
+
+  pub fn get_node_param(&self, id: NodeId, name: &str) -> Result<ParameterValue> {
+    self.get(id)?.get_param(name)
+  }
+
+  pub fn set_node_param(&mut self, id: NodeId, name: &str, value: ParameterValue) -> Result<()> {
+    self
+      .nodes
+      .0
+      .get_mut(&id)
+      .ok_or_else(|| anyhow!("Missing node: {id:?}"))?
+      .set_param(name, value)?;
+    // Mark graph as updated.
+    self.node_updated(id);
+    Ok(())
+  }
+
+  pub fn set_input(&mut self, input_id: InputId, value: Input) -> Result<Option<OutputId>> {
+    self.set_node_input(input_id.node(), input_id, value)
+  }
+
+  pub fn disconnect(&mut self, input: InputId) -> Result<()> {
+    self.set_input(input, Input::Disconnect)?;
+    Ok(())
+  }
+
+  /// Disconnect all inputs connected to `output`.
+  pub fn disconnect_output(&mut self, output: OutputId) {
+    let inputs: Vec<InputId> = self.connections.consumers(&output).copied().collect();
+    for input in inputs {
+      if let Err(err) = self.disconnect(input) {
+        log::warn!("Failed to disconnect input[{input:?}]: {err:?}");
+      }
+    }
+  }
+
+  /// Returns all inputs connected to `output`.
+  pub fn output_consumers(&self, output: OutputId) -> Vec<InputId> {
+    self.connections.consumers(&output).copied().collect()
+  }
+
+  /// Connect `output` to `input`.  An existing connection to `input` is replaced.
+  ///
+  /// Returns the output that was connected to `input` before.
+  pub fn connect(
+    &mut self,
+    input: InputId,
+    output: OutputId,
+    dt: DataType,
+  ) -> Result<Option<OutputId>> {
+    self.set_input(input, Input::Connect(output, Some(dt)))
+  }
+
+  /// Connect `output` to `input`, inserting a `Convert` node between them when the
+  /// data types differ.  Returns the id of the inserted node.
+  pub fn connect_with_adapter(
+    &mut self,
+    input: InputId,
+    output: OutputId,
+    dt: DataType,
+  ) -> Result<Option<NodeId>> {
+    let input_node = self.get(input.node())?;
+    let input_dt = input_node
+      .def()
+      .inputs
+      .get_index(input.idx as usize)
+      .map(|(_, def)| def.value_type)
+      .ok_or_else(|| anyhow!("Invalid input: {input:?}"))?;
+    let convert = if input_dt == dt || dt.is_dynamic() {
+      None
+    } else {
+      crate::nodes::channel::ConvertNode::with_target(input_dt)
+    };
+    let Some(convert) = convert else {
+      self.connect(input, output, dt)?;
+      return Ok(None);
+    };
+    // Place the adapter between the two nodes.
+    let input_pos = input_node.area.min;
+    let output_pos = self.get(output.node())?.area.min;
+    let mut node = Node::from_impl(Box::new(convert));
+    node.set_position(((input_pos.to_vec2() + output_pos.to_vec2()) / 2.0).round());
+    let id = self.add(node);
+    let res = self
+      .connect(InputId::new(id, 0), output, dt)
+      .and_then(|_| self.connect(input, OutputId::new(id, 0), input_dt));
+    if let Err(err) = res {
+      self.remove(id);
+      return Err(err);
+    }
+    Ok(Some(id))
+  }
+
+  /// Find the sockets used to insert `node_id` into the connection to `input`.
+  ///
+  /// Returns the node's first input that accepts the connection's data type and its
+  /// first output accepted by `input`.  Only unconnected nodes can be inserted.
+  pub fn insertion_sockets(&self, node_id: NodeId, input: InputId) -> Option<(u32, u32)> {
+    let output = *self.connections.get(&input)?;
+    if input.node() == node_id || output.node() == node_id {
+      return None;
+    }
+    let connected = self
+      .connections
+      .iter()
+      .any(|(i, o)| i.node() == node_id || o.node() == node_id);
+    if connected {
+      return None;
+    }
+    let out_dt = self.output_data_type(output)?;
+    let in_dt = self
+      .get(input.node())
+      .ok()?
+      .def()
+      .inputs
+      .get_index(input.idx as usize)
+      .map(|(_, def)| def.value_type)?;
+    let def = self.get(node_id).ok()?.def();
+    let node_input = def
+      .inputs
+      .values()
+      .position(|def| def.value_type.is_compatible(&out_dt))?;
+    let node_output = def
+      .outputs
+      .values()
+      .position(|def| in_dt.is_compatible(&def.value_type))?;
+    Some((node_input as u32, node_output as u32))
+  }
+
+  /// Insert the unconnected node `node_id` into the connection to `input`.
+  ///
+  /// The connection's output is connected to the node and the node's output to `input`.
+  pub fn insert_on_connection(&mut self, node_id: NodeId, input: InputId) -> Result<()> {
+    let (node_input, node_output) = self
+      .insertion_sockets(node_id, input)
+      .ok_or_else(|| anyhow!("The node can't be inserted into the connection"))?;
+    let output = *self
+      .connections
+      .get(&input)
+      .ok_or_else(|| anyhow!("Input isn't connected: {input:?}"))?;
+    let types = self.infer_types();
+    let out_dt = types
+      .get(&output)
+      .copied()
+      .or_else(|| self.output_data_type(output))
+      .ok_or_else(|| anyhow!("Invalid output: {output:?}"))?;
+    let node_input = InputId::new(node_id, node_input);
+    self.connect(node_input, output, out_dt)?;
+    let node_output = OutputId::new(node_id, node_output);
+    let types = self.infer_types();
+    let node_dt = types
+      .get(&node_output)
+      .copied()
+      .or_else(|| self.output_data_type(node_output))
+      .ok_or_else(|| anyhow!("Invalid output: {node_output:?}"))?;
+    if let Err(err) = self.connect(input, node_output, node_dt) {
+      // Restore the old connection.
+      self.disconnect(node_input)?;
+      return Err(err);
+    }
+    Ok(())
+  }
+
+  /// The data type of a node's output from the node definition.
+  pub fn output_data_type(&self, id: OutputId) -> Option<DataType> {
+    let node = self.nodes.0.get(&id.node())?;
+    node
+      .def()
+      .outputs
+      .get_index(id.idx as usize)
+      .map(|(_, def)| def.value_type)
+  }
+
+  pub fn connection_policy(&self) -> ConnectionPolicy {
+    self.connection_policy
+  }
+
+  pub fn set_connection_policy(&mut self, policy: ConnectionPolicy) {
+    self.connection_policy = policy;
+  }
+
+  pub fn insert_adapters(&self) -> bool {
+    self.insert_adapters
+  }
+
+  /// Use `connect_with_adapter` for connections made in the editor.
+  pub fn set_insert_adapters(&mut self, insert: bool) {
+    self.insert_adapters = insert;
+  }
+
+  pub fn output_format(&self) -> OutputFormat {
+    self.output_format
+  }
+
+  /// Set the texture format the host should use for the fragment output.
+  pub fn set_output_format(&mut self, format: OutputFormat) {
+    self.output_format = format;
+    self.updated();
+  }
+
+  /// The filter used to highlight nodes on the canvas.
+  pub fn search(&self) -> &NodeFilter {
+    &self.search
+  }
+
+  pub fn set_search(&mut self, name: &str) {
+    self.search.name = name.to_string();
+  }
+
+  /// Nodes with names matching the search.  Empty when there is no search.
+  pub fn search_matches(&self) -> Vec<NodeId> {
+    if self.search.name.is_empty() {
+      return Vec::new();
+    }
+    self
+      .nodes
+      .0
+      .values()
+      .filter(|node| self.search.matches(node.display_name()))
+      .map(|node| node.id())
+      .collect()
+  }
+
+  /// Pan to the first node matching the search.
+  pub fn focus_search(&mut self) -> Option<NodeId> {
+    let id = *self.search_matches().first()?;
+    let area = self.get(id).ok()?.area;
+    self.fit_view(area);
+    Some(id)
+  }
+
+  /// The last error from an editor action (for example an invalid connection).
+  pub fn last_error(&self) -> Option<&str> {
+    self.last_error.as_ref().map(|(_, err)| err.as_str())
+  }
+
+  pub fn clear_error(&mut self) {
+    self.last_error = None;
+  }
+
+  fn set_error(&mut self, err: String) {
+    log::warn!("{err}");
+    self.last_error = Some((std::time::Instant::now(), err));
+  }
+
+  /// Get the connection points of a node's sockets in graph-space (the same space as the node's `area`).
+  ///
+  /// Computed from the node's `area` and socket counts, so it doesn't need the node to be rendered.
+  #[cfg(feature = "egui")]
+  pub fn socket_layout(&self, id: NodeId) -> Vec<(NodeSocketId, emath::Vec2)> {
+    let Some(node) = self.nodes.0.get(&id) else {
+      return Vec::new();
+    };
+    let def = node.def();
+    let area = node.area;
+    let row = |idx: usize| area.top() + NODE_SOCKET_FIRST_ROW + idx as f32 * NODE_SOCKET_ROW_HEIGHT;
+    let inputs = (0..node.input_count()).map(|idx| {
+      (
+        NodeSocketId::input(id, idx as u32),
+        emath::vec2(area.left(), row(idx)),
+      )
+    });
+    let outputs = (0..def.outputs.len()).map(|idx| {
+      (
+        NodeSocketId::output(id, idx as u32),
+        emath::vec2(area.right(), row(idx)),
+      )
+    });
+    inputs.chain(outputs).collect()
+  }
+
+  /// Describe the connections of a socket as `Node.Socket` labels.  For inputs this is
+  /// the connected output, for outputs all the inputs using it.
+  #[cfg(feature = "egui")]
+  pub fn socket_links(&self, id: NodeSocketId) -> Vec<String> {
+    let label = |node: NodeId, name: Option<&String>| {
+      let node = self
+        .nodes
+        .0
+        .get(&node)
+        .map(|n| n.display_name())
+        .unwrap_or("?");
+      format!("{node}.{}", name.map(|n| n.as_str()).unwrap_or("?"))
+    };
+    match id {
+      NodeSocketId::Input(input) => self
+        .connections
+        .get(&input)
+        .map(|output| {
+          let name = self
+            .nodes
+            .0
+            .get(&output.node())
+            .and_then(|n| n.def().outputs.get_index(output.idx as usize))
+            .map(|(name, _)| name);
+          label(output.node(), name)
+        })
+        .into_iter()
+        .collect(),
+      NodeSocketId::Output(output) => self
+        .connections
+        .consumers(&output)
+        .map(|input| {
+          let name = self
+            .nodes
+            .0
+            .get(&input.node())
+            .and_then(|n| n.def().get_input(&input.key()))
+            .map(|def| &def.name);
+          label(input.node(), name)
+        })
+        .collect(),
+    }
+  }
+
+  /// Zoom and scroll the editor view to show `rect` (in graph-space).
+  pub fn fit_view(&mut self, rect: emath::Rect) -> CameraState {
+    self.editor.fit_view(rect);
+    self.camera()
+  }
+
+  /// Get the editor's viewport.
+  pub fn camera(&self) -> CameraState {
+    self.editor.camera()
+  }
+
+  /// Restore the editor's viewport.  The zoom is clamped to the supported range.
+  pub fn set_camera(&mut self, camera: CameraState) {
+    self.editor.set_camera(camera);
+  }
+
+  /// Reset the editor's viewport to the default.
+  pub fn reset_camera(&mut self) {
+    self.set_camera(CameraState::default());
+  }
+
+  /// Margin around the nodes that limits panning the editor view.
+  pub fn pan_margin(&self) -> Option<f32> {
+    self.editor.pan_margin
+  }
+
+  /// Set the margin around the nodes that limits panning.  `None` allows panning over the whole canvas.
+  pub fn set_pan_margin(&mut self, margin: Option<f32>) {
+    self.editor.pan_margin = margin;
+  }
+
+  pub fn get(&self, id: NodeId) -> Result<&Node> {
+    self
+      .nodes
+      .0
+      .get(&id)
+      .ok_or_else(|| anyhow!("Missing node: {id:?}"))
+  }
+
   pub fn get_mut(&mut self, id: NodeId) -> Result<&mut Node> {
     self.updated();
     self
@@ -439,13 +2222,360 @@ impl NodeGraph {
       .ok_or_else(|| anyhow!("Missing node: {id:?}"))
   }
 
+  /// Set the default (fragment) output node.
   pub fn set_output(&mut self, output: Option<NodeId>) {
-    self.updated();
-    self.output = output;
+    self.set_output_named(FRAGMENT_OUTPUT, output);
   }
 
+  /// The default (fragment) output node.
   pub fn output(&self) -> Option<NodeId> {
-    self.output
+    self.output_named(FRAGMENT_OUTPUT)
+  }
+
+  pub fn set_vertex_output(&mut self, output: Option<NodeId>) {
+    self.set_output_named(VERTEX_OUTPUT, output);
+  }
+
+  pub fn vertex_output(&self) -> Option<NodeId> {
+    self.output_named(VERTEX_OUTPUT)
+  }
+
+  /// Set or clear a named output node.
+  pub fn set_output_named(&mut self, name: &str, output: Option<NodeId>) {
+    self.updated();
+    match output {
+      Some(id) => {
+        self.outputs.insert(name.to_string(), id);
+      }
+      None => {
+        self.outputs.shift_remove(name);
+      }
+    }
+  }
+
+  pub fn output_named(&self, name: &str) -> Option<NodeId> {
+    self.outputs.get(name).copied()
+  }
+
+  /// All named output nodes.
+  pub fn outputs(&self) -> &IndexMap<String, NodeId> {
+    &self.outputs
+  }
+
+  /// Evaluate the previews of all nodes that support it.  See `NodeImpl::preview`.
+  pub fn previews(&self) -> HashMap<NodeId, Value> {
+    let mut execution = NodeGraphExecution::new();
+    let mut previews = HashMap::new();
+    for (id, node) in &self.nodes.0 {
+      if !node.def().supports_eval {
+        continue;
+      }
+      if let Some(value) = node.preview(self, &mut execution) {
+        previews.insert(*id, value);
+      }
+    }
+    previews
+  }
+
+  /// Returns the node ids sorted so that each node comes after the nodes connected to its inputs.
+  ///
+  /// Nodes that are part of a recursive connection are appended at the end.
+  pub fn topological_order(&self) -> Vec<NodeId> {
+    let mut pending: HashMap<NodeId, usize> = HashMap::new();
+    let mut consumers: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for (input, output) in &self.connections {
+      if self.contains(output.node()) && self.contains(input.node()) {
+        *pending.entry(input.node()).or_default() += 1;
+        consumers
+          .entry(output.node())
+          .or_default()
+          .push(input.node());
+      }
+    }
+    let mut ready: VecDeque<NodeId> = self
+      .nodes
+      .0
+      .keys()
+      .filter(|id| !pending.contains_key(*id))
+      .copied()
+      .collect();
+    let mut order = Vec::with_capacity(self.nodes.0.len());
+    while let Some(id) = ready.pop_front() {
+      order.push(id);
+      for consumer in consumers.get(&id).into_iter().flatten() {
+        if let Some(count) = pending.get_mut(consumer) {
+          *count -= 1;
+          if *count == 0 {
+            pending.remove(consumer);
+            ready.push_back(*consumer);
+          }
+        }
+      }
+    }
+    if !pending.is_empty() {
+      log::warn!("Recursive node connections detected.");
+      for id in self.nodes.0.keys() {
+        if pending.contains_key(id) {
+          order.push(*id);
+        }
+      }
+    }
+    order
+  }
+
+  /// Arrange the nodes in layers from left to right following the data flow.
+  ///
+  /// A Sugiyama-style layout: each node is placed one layer after the nodes connected to
+  /// its inputs, the nodes in each layer are ordered to reduce crossing connections and
+  /// then the layers are stacked in columns.
+  pub fn auto_layout(&mut self) {
+    let order = self.topological_order();
+    // Adjacency lists of the connected nodes.
+    let mut incoming: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    let mut outgoing: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+    for (input, output) in &self.connections {
+      let (src, dst) = (output.node(), input.node());
+      if src != dst && self.contains(src) && self.contains(dst) {
+        incoming.entry(dst).or_default().push(src);
+        outgoing.entry(src).or_default().push(dst);
+      }
+    }
+
+    // Assign layers (longest path from the source nodes).
+    let mut layer_of: HashMap<NodeId, usize> = HashMap::new();
+    for id in &order {
+      let layer = incoming
+        .get(id)
+        .into_iter()
+        .flatten()
+        .filter_map(|src| layer_of.get(src).map(|l| l + 1))
+        .max()
+        .unwrap_or(0);
+      layer_of.insert(*id, layer);
+    }
+    let layer_count = layer_of.values().max().map_or(0, |l| l + 1);
+    let mut layers: Vec<Vec<NodeId>> = vec![Vec::new(); layer_count];
+    for id in &order {
+      layers[layer_of[id]].push(*id);
+    }
+
+    // Reduce crossings by sorting each layer by the average position of its neighbours.
+    let mut rank: HashMap<NodeId, f32> = HashMap::new();
+    for layer in &layers {
+      for (idx, id) in layer.iter().enumerate() {
+        rank.insert(*id, idx as f32);
+      }
+    }
+    for sweep in 0..AUTO_LAYOUT_SWEEPS {
+      let downward = sweep % 2 == 0;
+      let layer_ids: Vec<usize> = if downward {
+        (1..layer_count).collect()
+      } else {
+        (0..layer_count.saturating_sub(1)).rev().collect()
+      };
+      let neighbours = if downward { &incoming } else { &outgoing };
+      for l in layer_ids {
+        let barycenter = |id: &NodeId| {
+          let (sum, count) = neighbours
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter(|n| match downward {
+              true => layer_of[*n] < l,
+              false => layer_of[*n] > l,
+            })
+            .filter_map(|n| rank.get(n))
+            .fold((0.0, 0usize), |(sum, count), r| (sum + r, count + 1));
+          if count == 0 {
+            rank[id]
+          } else {
+            sum / count as f32
+          }
+        };
+        let mut keyed: Vec<(f32, NodeId)> =
+          layers[l].iter().map(|id| (barycenter(id), *id)).collect();
+        keyed.sort_by(|a, b| a.0.total_cmp(&b.0));
+        layers[l] = keyed.into_iter().map(|(_, id)| id).collect();
+        for (idx, id) in layers[l].iter().enumerate() {
+          rank.insert(*id, idx as f32);
+        }
+      }
+    }
+
+    // Place the layers in columns.
+    let size_of = |node: &Node| node.area.size().max(AUTO_LAYOUT_MIN_SIZE);
+    let mut x = 0.0;
+    for layer in &layers {
+      let mut y = 0.0;
+      let mut width: f32 = 0.0;
+      for id in layer {
+        if let Some(node) = self.nodes.0.get_mut(id) {
+          let size = size_of(node);
+          node.set_position(emath::vec2(x, y));
+          y += size.y + AUTO_LAYOUT_SPACING.y;
+          width = width.max(size.x);
+        }
+      }
+      x += width + AUTO_LAYOUT_SPACING.x;
+    }
+
+    let groups: Vec<NodeGroupId> = self.groups.0.keys().copied().collect();
+    for group_id in groups {
+      self.resize_group(group_id);
+    }
+    self.updated();
+  }
+
+  /// Infer the concrete data type of every node output.
+  ///
+  /// The nodes are visited in topological order, so dynamic outputs are resolved
+  /// through chains of dynamic nodes in a single pass.
+  pub fn infer_types(&self) -> HashMap<OutputId, DataType> {
+    let mut types = HashMap::new();
+    for id in self.topological_order() {
+      let node = match self.nodes.0.get(&id) {
+        Some(node) => node,
+        None => continue,
+      };
+      // The type of a property node comes from the property's current value.
+      if let Some(property) = node
+        .as_property()
+        .and_then(|node| self.property(node.property()).ok())
+      {
+        types.insert(OutputId::new(id, 0), property.value().data_type());
+        continue;
+      }
+      let def = node.def();
+      // Collect the types of the connected dynamic inputs.
+      let mut concrete_type = NodeConcreteType::default();
+      for idx in 0..node.input_count() {
+        let dynamic = def
+          .get_input(&InputKey::Idx(idx as u32))
+          .is_some_and(|input| input.value_type.is_dynamic());
+        if !dynamic {
+          continue;
+        }
+        let dt = self
+          .connections
+          .get(&InputId::new(id, idx as u32))
+          .and_then(|output| types.get(output));
+        if let Some(dt) = dt {
+          concrete_type.add_input_type(*dt);
+        }
+      }
+      for (idx, output) in def.outputs.values().enumerate() {
+        let dt = node
+          .output_concrete_type(idx as u32, &concrete_type, output.value_type)
+          .unwrap_or(output.value_type);
+        types.insert(OutputId::new(id, idx as u32), dt);
+      }
+    }
+    types
+  }
+
+  /// Sort nodes, groups, properties, connections and outputs by id/name.
+  ///
+  /// Insertion order leaks into the serialized graph, canonicalizing before saving
+  /// makes the same logical graph always produce the same output.
+  pub fn canonicalize(&mut self) {
+    self.properties.0.sort_keys();
+    self.nodes.0.sort_keys();
+    self.groups.0.sort_keys();
+    self.connections.sort();
+    self.outputs.sort_keys();
+  }
+
+  /// The node registry used by the editor to create nodes.
+  pub fn registry(&self) -> &NodeRegistry {
+    &self.node_finder.registry
+  }
+
+  /// Use `registry` (for example with runtime-registered nodes) to create nodes in the editor.
+  /// The registry is shared, so graphs can use the same customized registry.
+  pub fn set_registry(&mut self, registry: NodeRegistry) {
+    self.node_finder.registry = registry;
+  }
+
+  /// The node presets listed by the node finder.
+  pub fn presets(&self) -> &PresetLibrary {
+    &self.node_finder.presets
+  }
+
+  pub fn presets_mut(&mut self) -> &mut PresetLibrary {
+    &mut self.node_finder.presets
+  }
+
+  /// Save a node as a preset in the node finder's library.
+  pub fn save_preset(&mut self, id: NodeId, name: &str) -> Result<()> {
+    let preset = NodePreset::from_node(name, self.get(id)?)?;
+    self.node_finder.presets.add(preset);
+    Ok(())
+  }
+
+  /// Save the graph in a compact binary format (CBOR).
+  #[cfg(feature = "binary")]
+  pub fn save_bin<W: std::io::Write>(&self, writer: W) -> Result<()> {
+    ciborium::into_writer(self, writer)?;
+    Ok(())
+  }
+
+  /// Load a graph saved with `save_bin`.
+  #[cfg(feature = "binary")]
+  pub fn load_bin<R: std::io::Read>(reader: R) -> Result<Self> {
+    Ok(ciborium::from_reader(reader)?)
+  }
+
+  /// Export the graph structure in Graphviz DOT format.
+  ///
+  /// Edges are labeled with the output socket name and type and the input socket name.
+  pub fn to_dot(&self) -> String {
+    use std::fmt::Write;
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let types = self.infer_types();
+    let mut dot = String::from("digraph {\n  rankdir=LR;\n  node [shape=box];\n");
+    for id in self.topological_order() {
+      let Some(node) = self.nodes.0.get(&id) else {
+        continue;
+      };
+      let mut attrs = format!("label=\"{}\"", escape(node.display_name()));
+      if let Some((name, _)) = self.outputs.iter().find(|(_, out)| **out == id) {
+        let _ = write!(attrs, ", peripheries=2, xlabel=\"{}\"", escape(name));
+      }
+      let _ = writeln!(dot, "  \"{id}\" [{attrs}];");
+    }
+    for (input, output) in &self.connections {
+      let output_name = self
+        .nodes
+        .0
+        .get(&output.node())
+        .and_then(|n| n.def().outputs.get_index(output.idx as usize))
+        .map(|(name, _)| name.as_str())
+        .unwrap_or("?");
+      let input_name = self
+        .nodes
+        .0
+        .get(&input.node())
+        .and_then(|n| n.def().get_input(&input.key()))
+        .map(|input| input.name.as_str())
+        .unwrap_or("?");
+      let dt = types
+        .get(output)
+        .copied()
+        .or_else(|| self.output_data_type(*output));
+      let label = match dt {
+        Some(dt) => format!("{output_name} ({dt:?}) -> {input_name}"),
+        None => format!("{output_name} -> {input_name}"),
+      };
+      let _ = writeln!(
+        dot,
+        "  \"{}\" -> \"{}\" [label=\"{}\"];",
+        output.node(),
+        input.node(),
+        escape(&label)
+      );
+    }
+    dot.push_str("}\n");
+    dot
   }
 }
 
@@ -465,17 +2595,104 @@ impl NodeGraph {
     if let Some(pos) = ui.ctx().pointer_latest_pos() {
       self.editor.add_node_at = self.editor.graph_pointer_pos;
       self.node_finder.open_at(pos);
+      self.finder_connection = None;
+    }
+  }
+
+  /// The selected nodes and groups.
+  pub fn selection(&self) -> Vec<Uuid> {
+    self
+      .ui_state
+      .selected()
+      .into_iter()
+      .filter(|id| self.nodes.0.contains_key(id) || self.groups.0.contains_key(id))
+      .collect()
+  }
+
+  /// Get the editor's UI state, for example to save it in a sidecar file.
+  pub fn ui_snapshot(&self) -> GraphUiState {
+    GraphUiState {
+      camera: self.camera(),
+      selected: self.selection(),
+      details_node: self.details_node(),
+      details_pinned: self.is_details_pinned(),
+    }
+  }
+
+  /// Restore the editor's UI state.  Missing nodes and groups are ignored.
+  pub fn restore_ui(&mut self, state: GraphUiState) {
+    self.set_camera(state.camera);
+    self.ui_state.clear_selected();
+    for id in state.selected {
+      if self.nodes.0.contains_key(&id) || self.groups.0.contains_key(&id) {
+        self
+          .ui_state
+          .frame_state_mut(id, |frame| frame.selected = true);
+      }
+    }
+    self.set_details_node(state.details_node);
+    self.set_details_pinned(state.details_pinned);
+  }
+
+  /// Save the graph as JSON together with the editor's UI state.
+  pub fn save_with_ui<W: std::io::Write>(&self, writer: W) -> Result<()> {
+    let save = SaveGraphWithUi {
+      graph: self,
+      ui: self.ui_snapshot(),
+    };
+    serde_json::to_writer_pretty(writer, &save)?;
+    Ok(())
+  }
+
+  /// Load a graph saved with `save_with_ui`, restoring the UI state.  Plain graph files
+  /// are also accepted.
+  pub fn load_with_ui<R: std::io::Read>(reader: R) -> Result<Self> {
+    let load: LoadGraphWithUi = serde_json::from_reader(reader)?;
+    let mut graph = load.graph;
+    if let Some(ui) = load.ui {
+      graph.restore_ui(ui);
+    }
+    Ok(graph)
+  }
+
+  /// Take the selected nodes and groups, clearing the selection.
+  pub fn take_selection(&mut self) -> (Vec<NodeId>, Vec<NodeGroupId>) {
+    let (mut nodes, mut groups) = (Vec::new(), Vec::new());
+    for id in self.ui_state.take_selected() {
+      if self.nodes.0.contains_key(&id) {
+        nodes.push(id);
+      } else if self.groups.0.contains_key(&id) {
+        groups.push(id);
+      }
+    }
+    (nodes, groups)
+  }
+
+  /// Remove the selected nodes and groups.  The nodes of removed groups are kept,
+  /// unless they are also selected.
+  pub fn remove_selected(&mut self) {
+    let (nodes, groups) = self.take_selection();
+    for group_id in groups {
+      self.remove_group(group_id, false);
+    }
+    for node_id in nodes {
+      self.remove(node_id);
     }
   }
 
   pub fn group_selected_nodes(&mut self) -> Option<NodeGroupId> {
     let mut group = NodeGroup::new();
 
-    let mut empty = true;
-    for node_id in self.ui_state.take_selected() {
+    let (nodes, groups) = self.take_selection();
+    let empty = nodes.is_empty() && groups.is_empty();
+    for node_id in nodes {
       if let Some(node) = self.nodes.0.get_mut(&node_id) {
         group.add_node(node);
-        empty = false;
+      }
+    }
+    for group_id in groups {
+      if let Some(child) = self.groups.0.get_mut(&group_id) {
+        group.add_group(child);
       }
     }
 
@@ -504,30 +2721,76 @@ impl NodeGraph {
     egui::SidePanel::right("graph_details_panel")
       .min_width(150.0)
       .resizable(false)
-      .show_inside(ui, |ui| self.details_ui(ui));
+      .show_inside(ui, |ui| {
+        self.search_ui(ui);
+        ui.separator();
+        self.details_ui(ui);
+      });
+  }
+
+  /// Search box for highlighting nodes by name.  Press enter to pan to the first match.
+  pub fn search_ui(&mut self, ui: &mut egui::Ui) {
+    let resp = ui.add(egui::TextEdit::singleline(&mut self.search.name).hint_text("🔍 Find nodes"));
+    if resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+      self.focus_search();
+    }
+  }
+
+  /// The node shown in the details panel.
+  pub fn details_node(&self) -> Option<NodeId> {
+    self
+      .details_state
+      .selected_node
+      .filter(|id| self.nodes.0.contains_key(id))
+  }
+
+  /// Show `id` in the details panel.
+  pub fn set_details_node(&mut self, id: Option<NodeId>) {
+    self.details_state.selected_node = id;
+  }
+
+  pub fn is_details_pinned(&self) -> bool {
+    self.details_state.pinned
+  }
+
+  /// Pin the details panel to the current node.  Selecting other nodes doesn't change it.
+  pub fn set_details_pinned(&mut self, pinned: bool) {
+    self.details_state.pinned = pinned;
+  }
+
+  fn show_in_details(&mut self, id: Option<NodeId>) {
+    if !self.details_state.pinned || self.details_node().is_none() {
+      self.details_state.selected_node = id;
+    }
   }
 
   pub fn details_ui(&mut self, ui: &mut egui::Ui) {
     let mut updated = false;
-    if let Some(id) = self.details_state.selected_node {
+    if let Some(id) = self.details_node() {
+      let pinned = &mut self.details_state.pinned;
       if let Some(node) = self.nodes.0.get_mut(&id) {
         ui.vertical(|ui| {
           ui.horizontal(|ui| {
             ui.label("Name:");
             ui.text_edit_singleline(&mut node.name);
+            if node.is_renamed() && ui.small_button("Reset").clicked() {
+              node.reset_display_name();
+            }
+            ui.toggle_value(pinned, "📌")
+              .on_hover_text("Pin the details panel to this node");
           });
           if node.details_ui(ui, id) {
             updated = true;
           }
         });
       }
+      if updated {
+        self.node_updated(id);
+      }
     } else {
       // Show tips.
       ui.label("Click node to view/edit details");
     }
-    if updated {
-      self.updated();
-    }
   }
 
   pub fn show_graph(&mut self, ui: &mut egui::Ui) {
@@ -536,7 +2799,7 @@ impl NodeGraph {
 
   fn handle_clicked(&mut self, clear_on_click: bool) {
     if clear_on_click {
-      self.details_state.selected_node = None;
+      self.show_in_details(None);
       self.ui_state.clear_selected();
     }
   }
@@ -544,16 +2807,24 @@ impl NodeGraph {
   pub fn graph_ui(&mut self, ui: &mut egui::Ui) {
     // Show the node finder if it is open.
     if let Some(node) = self.node_finder.ui(ui) {
-      self.add(node);
+      let id = self.add(node);
+      if let Some(input) = self.finder_connection.take() {
+        if let Err(err) = self.insert_on_connection(id, input) {
+          self.set_error(format!("Failed to insert node: {err}"));
+        }
+      }
     }
 
     let mut scrolling = true;
     let mut selecting = true;
     let mut clear_selected = true;
+    let mut deselect = false;
     // Detect drag mode.
     // * Select nodes only in dragged area - Primary mouse button and no modifiers.
     // * Add nodes in dragged area to selected set - Primary mouse button + SHIFT.
+    // * Remove nodes in dragged area from selected set - Primary mouse button + ALT.
     // * Scroll - Primary mouse button + CTRL.
+    // * Pan - Middle mouse button or primary mouse button + SPACE.
     ui.input(|i| {
       // Enable scrolling when CTRL is down.
       if i.modifiers.ctrl {
@@ -569,7 +2840,30 @@ impl NodeGraph {
       if i.modifiers.shift {
         clear_selected = false;
       }
+      // When ALT is down deselect the nodes in the dragged area.
+      if i.modifiers.alt {
+        clear_selected = false;
+        deselect = true;
+      }
+    });
+
+    // Pan with the middle mouse button or SPACE + primary mouse button.
+    let typing = ui.ctx().wants_keyboard_input();
+    let pan_delta = ui.input(|i| {
+      let space = !typing && i.key_down(egui::Key::Space) && i.pointer.primary_down();
+      (i.pointer.middle_down() || space).then(|| i.pointer.delta())
     });
+    match pan_delta {
+      Some(delta) if self.editor.panning || ui.ui_contains_pointer() => {
+        self.editor.panning = true;
+        self.editor.pan(delta);
+        ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+        selecting = false;
+        scrolling = false;
+      }
+      Some(_) => (),
+      None => self.editor.panning = false,
+    }
 
     if ui.ui_contains_pointer() {
       // Use mouse wheel for zoom instead of scrolling.
@@ -608,7 +2902,9 @@ impl NodeGraph {
       let old_node_style = NodeStyle::get(ui);
 
       // Apply zoom to Ui style.
-      let node_style = NodeStyle::zoom_style(ui, zoom);
+      let mut node_style = NodeStyle::zoom_style(ui, zoom);
+      node_style.connection_policy = self.connection_policy;
+      node_style.set(ui);
 
       // Set node graph area.
       ui.set_width(size.x);
@@ -618,6 +2914,10 @@ impl NodeGraph {
       let origin = origin + ui_min;
       let state = self.ui_state.clone();
       state.load(ui, origin, ui_min, zoom);
+      // Connections shown in the tooltip of the hovered socket.
+      if let Some(socket) = state.take_hover_socket() {
+        state.set_hover_links(socket, self.socket_links(socket));
+      }
 
       // Convert pointer position to graph-space.  (Used for adding new nodes).
       let mut pointer_pos = emath::Pos2::default();
@@ -638,7 +2938,7 @@ impl NodeGraph {
         }
         state.selecting_mut(|selecting| {
           if resp.drag_started() {
-            selecting.drag_started(pointer_pos, clear_selected);
+            selecting.drag_started(pointer_pos, clear_selected, deselect);
             // Close the NodeFinder on clicks.
             self.node_finder.close();
           } else if resp.drag_stopped() {
@@ -653,16 +2953,27 @@ impl NodeGraph {
 
       // Render groups.
       let mut remove_group = None;
+      let mut collapse_group = None;
       let mut resize_groups = BTreeSet::new();
       let mut clicked_group = None;
-      for (group_id, group) in &mut self.groups.0 {
+      let mut move_group = None;
+      for idx in self.groups_by_depth() {
+        let locked = self
+          .groups
+          .0
+          .get_index(idx)
+          .is_some_and(|(group_id, _)| self.is_group_locked(*group_id));
+        let Some((group_id, group)) = self.groups.0.get_index_mut(idx) else {
+          continue;
+        };
+        state.frame_state_mut(*group_id, |frame| frame.locked = locked);
         match state.render(ui, group) {
           Some(NodeAction::Dragged(delta)) => {
-            let delta = delta / zoom;
-            for (_, node) in &mut self.nodes.0 {
-              if node.group_id == *group_id {
-                node.handle_move(delta);
-              }
+            move_group = Some((*group_id, delta / zoom));
+          }
+          Some(NodeAction::Resize) => {
+            if !group.parent_id.is_nil() {
+              resize_groups.insert(group.parent_id);
             }
           }
           Some(NodeAction::Clicked) => {
@@ -671,17 +2982,37 @@ impl NodeGraph {
           Some(NodeAction::Delete(nodes)) => {
             remove_group = Some((*group_id, nodes));
           }
+          Some(NodeAction::Collapse) => {
+            collapse_group = Some(*group_id);
+          }
           Some(NodeAction::JoinGroup(group_id)) => {
-            for node_id in self.ui_state.take_selected() {
+            let (nodes, groups) = self.take_selection();
+            for node_id in nodes {
               if let Some(node) = self.nodes.0.get_mut(&node_id) {
                 node.group_id = group_id;
               }
             }
+            for child_id in groups {
+              if child_id != group_id {
+                if let Err(err) = self.set_group_parent(child_id, group_id) {
+                  log::warn!("Failed to add group: {err:?}");
+                }
+              }
+            }
             resize_groups.insert(group_id);
           }
           _ => (),
         }
       }
+      if let Some((group_id, delta)) = move_group {
+        // Move the nested groups and nodes with the group.
+        self.move_group_contents(group_id, delta);
+        if let Ok(group) = self.get_group(group_id) {
+          if !group.parent_id.is_nil() {
+            resize_groups.insert(group.parent_id);
+          }
+        }
+      }
       if let Some(group_id) = clicked_group {
         self.handle_clicked(clear_selected);
         self.select_node(group_id, true);
@@ -689,18 +3020,63 @@ impl NodeGraph {
       if let Some((group_id, remove_nodes)) = remove_group {
         self.remove_group(group_id, remove_nodes);
       }
+      if let Some(group_id) = collapse_group {
+        if let Err(err) = self.collapse_group(group_id) {
+          log::warn!("Failed to collapse group: {err:?}");
+        }
+      }
 
       // Draw connections.
       let connection_style = NodeConnection::new(&node_style, ui_min);
       self.render_connections(ui, id, &state, connection_style);
 
+      // Re-infer the output types and previews when the graph has changed.
+      if state.inferred_counter() != Some(self.changed) {
+        state.set_inferred_types(self.changed, self.infer_types());
+        state.set_previews(self.previews());
+      }
+
       // Render nodes.
       let mut remove_node = None;
+      let mut expand_node = None;
+      let mut output_node = None;
       let mut updated = false;
       let mut clicked_node = None;
-      for (node_id, node) in &mut self.nodes.0 {
+      let mut dragged_node = None;
+      let searching = !self.search.name.is_empty();
+      let clip = ui.clip_rect();
+      let view = emath::Rect::from_min_max(
+        ((clip.min - origin).to_vec2() / zoom).to_pos2(),
+        ((clip.max - origin).to_vec2() / zoom).to_pos2(),
+      );
+      for idx in self.visible_nodes(view) {
+        let locked = self
+          .nodes
+          .0
+          .get_index(idx)
+          .is_some_and(|(_, node)| node.is_locked() || self.is_group_locked(node.group_id));
+        let Some((node_id, node)) = self.nodes.0.get_index_mut(idx) else {
+          continue;
+        };
+        let highlight = searching && self.search.matches(node.display_name());
+        let output = self.outputs.values().any(|id| id == node_id);
+        let fragment_output = self.outputs.get(FRAGMENT_OUTPUT) == Some(node_id);
+        state.frame_state_mut(*node_id, |frame| {
+          frame.highlight = highlight;
+          frame.locked = locked;
+          frame.output = output;
+          frame.fragment_output = fragment_output;
+        });
         match state.render(ui, node) {
-          Some(NodeAction::Dragged(_) | NodeAction::Resize) => {
+          Some(NodeAction::Dragged(_)) => {
+            self.spatial.invalidate();
+            if !node.group_id.is_nil() {
+              resize_groups.insert(node.group_id);
+            }
+            dragged_node = Some(*node_id);
+          }
+          Some(NodeAction::Resize) => {
+            self.spatial.invalidate();
             if !node.group_id.is_nil() {
               resize_groups.insert(node.group_id);
             }
@@ -711,27 +3087,76 @@ impl NodeGraph {
           Some(NodeAction::Delete(_)) => {
             remove_node = Some(*node_id);
           }
+          Some(NodeAction::Expand) => {
+            expand_node = Some(*node_id);
+          }
+          Some(NodeAction::SetOutput) => {
+            output_node = Some(*node_id);
+          }
           Some(NodeAction::LeaveGroup(group_id)) => {
             resize_groups.insert(group_id);
           }
           _ => (),
         }
-        updated |= node.updated;
+        if node.updated {
+          self.dirty.insert(*node_id);
+          updated = true;
+        }
+      }
+      if let Some(node_id) = output_node {
+        self.set_output(Some(node_id));
+      }
+      if let Some(node_id) = clicked_node {
+        self.handle_clicked(clear_selected);
+        self.show_in_details(Some(node_id));
+        self.select_node(node_id, true);
+      }
+      // Dropping a dragged node onto a connection inserts it.
+      if dragged_node.is_some() {
+        self.dragging_node = dragged_node;
+      } else if let Some(node_id) = self.dragging_node {
+        if !ui.input(|i| i.pointer.primary_down()) {
+          self.dragging_node = None;
+          let target = self
+            .hover_connection
+            .filter(|input| self.insertion_sockets(node_id, *input).is_some());
+          if let Some(input) = target {
+            match self.insert_on_connection(node_id, input) {
+              Ok(()) => self.clear_error(),
+              Err(err) => self.set_error(format!("Failed to insert node: {err}")),
+            }
+          }
+        }
       }
-      if let Some(node_id) = clicked_node {
-        self.handle_clicked(clear_selected);
-        self.details_state.selected_node = Some(node_id);
-        self.select_node(node_id, true);
+
+      // Promote the inputs requested from their context menu.
+      for input in state.take_promote() {
+        if let Err(err) = self.promote_to_property(input) {
+          self.set_error(format!("Failed to promote input: {err}"));
+        }
+      }
+
+      // Disconnect the sockets requested with the keyboard.
+      for socket in state.take_disconnect() {
+        match socket {
+          NodeSocketId::Input(input) => {
+            if let Err(err) = self.disconnect(input) {
+              self.set_error(format!("Failed to disconnect: {err}"));
+            }
+          }
+          NodeSocketId::Output(output) => self.disconnect_output(output),
+        }
       }
 
       // Check for outputs that have changed their data types.
       let outputs = state.take_updated_outputs();
       if outputs.len() > 0 {
         // Update any node that is connected to the changed outputs.
-        for (input, output) in self.connections.0.iter() {
-          if outputs.contains(output) {
+        for output in &outputs {
+          for input in self.connections.consumers(output) {
             if let Some(node) = self.nodes.0.get_mut(&input.node()) {
               node.updated = true;
+              self.dirty.insert(input.node());
             }
           }
         }
@@ -744,6 +3169,11 @@ impl NodeGraph {
       if let Some(node_id) = remove_node {
         self.remove(node_id);
       }
+      if let Some(node_id) = expand_node {
+        if let Err(err) = self.expand_subgraph(node_id) {
+          log::warn!("Failed to expand subgraph: {err:?}");
+        }
+      }
       for group_id in resize_groups {
         self.resize_group(group_id);
       }
@@ -762,6 +3192,16 @@ impl NodeGraph {
     });
     // Save scroll offset and de-zoom it.
     self.editor.scroll_offset = out.state.offset / zoom;
+    self.editor.view_size = out.inner_rect.size();
+    // Keep the nodes from being scrolled out of view.
+    let nodes = self
+      .nodes
+      .0
+      .values()
+      .fold(emath::Rect::NOTHING, |area, node| area.union(node.area));
+    self
+      .editor
+      .clamp_scroll_offset(nodes, out.inner_rect.size() / zoom);
 
     if let Some(resp) = out.inner {
       resp.context_menu(|ui| self.context_menu(ui));
@@ -769,6 +3209,32 @@ impl NodeGraph {
         self.menu_state = None;
       }
     }
+
+    self.error_toast_ui(ui, out.inner_rect);
+  }
+
+  /// Show the last error at the bottom of the graph view for a few seconds.
+  fn error_toast_ui(&mut self, ui: &mut egui::Ui, rect: emath::Rect) {
+    let Some((time, err)) = &self.last_error else {
+      return;
+    };
+    let elapsed = time.elapsed();
+    if elapsed >= ERROR_TOAST_DURATION {
+      self.last_error = None;
+      return;
+    }
+    egui::Area::new(ui.id().with("error_toast"))
+      .order(egui::Order::Foreground)
+      .pivot(egui::Align2::CENTER_BOTTOM)
+      .fixed_pos(rect.center_bottom() - emath::vec2(0., 10.))
+      .interactable(false)
+      .show(ui.ctx(), |ui| {
+        egui::Frame::popup(ui.style()).show(ui, |ui| {
+          ui.colored_label(ui.visuals().error_fg_color, err);
+        });
+      });
+    ui.ctx()
+      .request_repaint_after(ERROR_TOAST_DURATION - elapsed);
   }
 
   fn context_menu(&mut self, ui: &mut egui::Ui) {
@@ -780,12 +3246,22 @@ impl NodeGraph {
       .clone();
     if ui.button("Create node").clicked() {
       self.open_node_finder(ui);
+      // Insert the new node into the connection under the pointer.
+      self.finder_connection = state.hover_connection;
       ui.close_menu();
     }
     if self.has_selected() && ui.button("Group Nodes").clicked() {
       self.group_selected_nodes();
       ui.close_menu();
     }
+    if self.has_selected() && ui.button("Delete selected").clicked() {
+      self.remove_selected();
+      ui.close_menu();
+    }
+    if !self.outputs.is_empty() && ui.button("Remove unused").clicked() {
+      self.remove_unreachable_nodes();
+      ui.close_menu();
+    }
     if let Some(input) = state.hover_connection {
       if ui.button("Delete connection").clicked() {
         if let Err(err) = self.disconnect(input) {
@@ -813,13 +3289,19 @@ impl NodeGraph {
         if let Some((src, dst)) = drag.take_sockets() {
           if let Some((dst, dt)) = dst {
             // Connect.
-            if let Err(err) = self.connect(src, dst, dt) {
-              log::warn!("Failed to connect input[{src:?}] to output[{dst:?}]: {err:?}");
+            let res = if self.insert_adapters {
+              self.connect_with_adapter(src, dst, dt).map(|_| ())
+            } else {
+              self.connect(src, dst, dt).map(|_| ())
+            };
+            match res {
+              Ok(()) => self.clear_error(),
+              Err(err) => self.set_error(format!("Failed to connect: {err}")),
             }
           } else {
             // Disconnect
             if let Err(err) = self.disconnect(src) {
-              log::warn!("Failed to disconnect input[{src:?}]: {err:?}");
+              self.set_error(format!("Failed to disconnect: {err}"));
             }
           }
         }
@@ -846,7 +3328,7 @@ impl NodeGraph {
           let (start, end, color) = if let Some(input_id) = src.id.as_input_id() {
             // If the dragged socket is an input, then remove it's current connection.
             if let Err(err) = self.disconnect(input_id) {
-              log::warn!("Failed to disconnect input[{input_id:?}]: {err:?}");
+              self.set_error(format!("Failed to disconnect: {err}"));
             }
             (conn.to_ui_pos(src.center), dst, color)
           } else {
@@ -860,15 +3342,17 @@ impl NodeGraph {
 
     // Draw connections.
     self.hover_connection = None;
-    for (input, output) in &self.connections.0 {
+    let dim = conn.dim_unselected && state.has_selected();
+    for (input, output) in &self.connections {
       let meta = state.get_connection_meta(input, output);
       if let Some((in_meta, out_meta)) = meta {
         let start = conn.to_ui_pos(in_meta.center);
         let end = conn.to_ui_pos(out_meta.center);
-        if conn
-          .draw(ui, start, end, Some(out_meta.color), true)
-          .is_some()
-        {
+        let mut color = out_meta.color;
+        if dim && !state.is_selected(input.node()) && !state.is_selected(output.node()) {
+          color = color.gamma_multiply(0.25);
+        }
+        if conn.draw(ui, start, end, Some(color), true).is_some() {
           self.hover_connection = Some(*input);
         }
       }
@@ -882,6 +3366,7 @@ pub struct NodeGraphEditor {
   pub title: String,
   pub size: emath::Vec2,
   pub graph: NodeGraph,
+  theme: Option<Arc<GraphTheme>>,
 }
 
 #[cfg(feature = "egui")]
@@ -891,6 +3376,7 @@ impl Default for NodeGraphEditor {
       title: "Graph editor".to_string(),
       size: (900., 500.).into(),
       graph: Default::default(),
+      theme: None,
     }
   }
 }
@@ -908,9 +3394,1307 @@ impl NodeGraphEditor {
     Ok(())
   }
 
+  /// The theme set with `set_theme`.  Without a theme the editor uses the current
+  /// egui visuals.
+  pub fn theme(&self) -> Option<&GraphTheme> {
+    self.theme.as_deref()
+  }
+
+  pub fn set_theme(&mut self, theme: GraphTheme) {
+    self.theme = Some(Arc::new(theme));
+  }
+
   pub fn show(&mut self, ctx: &egui::Context) {
     egui::Window::new(&self.title)
       .default_size(self.size)
-      .show(ctx, |ui| self.graph.show(ui));
+      .show(ctx, |ui| {
+        if let Some(theme) = &self.theme {
+          theme.apply(ui);
+        }
+        self.graph.show(ui)
+      });
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn infer_dynamic_chain() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let add = reg.new_by_name("Add")?;
+    let node1 = graph.add(add.duplicate());
+    graph.set_node_input(node1, "A", vec3.into())?;
+    let node2 = graph.add(add.duplicate());
+    graph.set_node_input(node2, "A", node1.into())?;
+    let node3 = graph.add(add.duplicate());
+    graph.set_node_input(node3, "A", node2.into())?;
+    graph.set_node_input(node3, "B", 1.0.into())?;
+
+    let order = graph.topological_order();
+    let pos = |id| order.iter().position(|n| *n == id);
+    assert!(pos(vec3) < pos(node1));
+    assert!(pos(node1) < pos(node2));
+    assert!(pos(node2) < pos(node3));
+
+    let types = graph.infer_types();
+    assert_eq!(types.get(&node3.into()), Some(&DataType::Vec3));
+    Ok(())
+  }
+
+  #[test]
+  fn infer_swizzle_mask() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec4 = graph.add(reg.new_by_name("Vector 4")?);
+    let swizzle = graph.add(reg.new_by_name("Swizzle")?);
+    graph.set_node_input(swizzle, "Input", vec4.into())?;
+    graph.set_node_param(swizzle, "Swizzle", ParameterValue::Text("xy".into()))?;
+    assert_eq!(
+      graph.infer_types().get(&swizzle.into()),
+      Some(&DataType::Vec2)
+    );
+
+    graph.set_node_param(swizzle, "Swizzle", ParameterValue::Text("z".into()))?;
+    assert_eq!(
+      graph.infer_types().get(&swizzle.into()),
+      Some(&DataType::F32)
+    );
+
+    // Single components of integer inputs keep their type, like the compiled code.
+    graph.set_node_param(swizzle, "Swizzle", ParameterValue::Text("x".into()))?;
+    let mut concrete_type = NodeConcreteType::default();
+    concrete_type.add_input_type(DataType::I32);
+    let dt = graph
+      .get(swizzle)?
+      .output_concrete_type(0, &concrete_type, DataType::DynamicVector);
+    assert_eq!(dt, Some(DataType::I32));
+    let mask = SwizzleMask("x".into());
+    let compiled = mask.compile(CompiledValue {
+      value: "1".into(),
+      dt: DataType::I32,
+    })?;
+    assert_eq!(Some(compiled.dt), dt);
+    Ok(())
+  }
+
+  #[test]
+  fn evaluate() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    assert!(graph.evaluate().is_err());
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.set_output(Some(add));
+    assert_eq!(graph.evaluate()?, Value::F32(5.0));
+    assert_eq!(graph.evaluate_node(add)?, Value::F32(5.0));
+    assert!(graph.evaluate_node(float).is_ok());
+
+    let uv = graph.add(reg.new_by_name("Uv")?);
+    let err = graph.evaluate_node(uv).unwrap_err();
+    assert_eq!(err.to_string(), "`Uv` nodes can only be compiled");
+    graph.set_output(Some(uv));
+    let err = graph.evaluate().unwrap_err();
+    assert_eq!(err.to_string(), "`Uv` nodes can only be compiled");
+    Ok(())
+  }
+
+  #[test]
+  fn compile_to_wgsl() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    assert!(graph.compile_to_wgsl().is_err());
+    let float = graph.add(reg.new_by_name("Float")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", float.into())?;
+    graph.set_output(Some(frag));
+
+    // Same as compiling with the standard block layout.
+    let mut compile = NodeGraphCompile::new();
+    compile.define_block("imports");
+    compile.define_block("bindings");
+    compile.define_block("fragment");
+    compile.compile_graph(&graph)?;
+    let code = graph.compile_to_wgsl()?;
+    assert_eq!(code, compile.dump());
+    assert!(code.contains("@fragment"));
+    Ok(())
+  }
+
+  #[test]
+  fn pan_bounds() {
+    let mut editor = EditorState::default();
+    let origin = editor.origin;
+    let nodes = emath::Rect::from_min_size(emath::Pos2::ZERO, emath::vec2(100., 100.));
+    let view = emath::vec2(400., 300.);
+
+    // Panning stops at the node bounds plus the margin.
+    editor.scroll_offset = emath::Vec2::ZERO;
+    editor.clamp_scroll_offset(nodes, view);
+    assert_eq!(editor.scroll_offset, origin + emath::vec2(-200., -200.));
+    editor.scroll_offset = editor.size;
+    editor.clamp_scroll_offset(nodes, view);
+    assert_eq!(editor.scroll_offset, origin + emath::vec2(-100., 0.));
+
+    // Offsets inside the bounds are kept.
+    let inside = origin + emath::vec2(-150., -100.);
+    editor.scroll_offset = inside;
+    editor.clamp_scroll_offset(nodes, view);
+    assert_eq!(editor.scroll_offset, inside);
+
+    // No bounds without nodes or margin.
+    editor.scroll_offset = emath::Vec2::ZERO;
+    editor.clamp_scroll_offset(emath::Rect::NOTHING, view);
+    assert_eq!(editor.scroll_offset, emath::Vec2::ZERO);
+    editor.pan_margin = None;
+    editor.clamp_scroll_offset(nodes, view);
+    assert_eq!(editor.scroll_offset, emath::Vec2::ZERO);
+  }
+
+  #[test]
+  fn previews() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec4 = graph.add(reg.new_by_name("Vector 4")?);
+    let color = Value::Vec4([1., 0.5, 0., 1.].into());
+    graph.set_node_param(vec4, "Value", ParameterValue::Value(color.clone()))?;
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", vec4.into())?;
+    graph.add(reg.new_by_name("Add")?);
+
+    // Only the output node has a preview.
+    let previews = graph.previews();
+    assert_eq!(previews.len(), 1);
+    assert_eq!(previews.get(&frag), Some(&color));
+
+    // No preview for colors that can only be compiled.
+    let uv = graph.add(reg.new_by_name("Uv")?);
+    graph.set_node_input(frag, "Color", uv.into())?;
+    assert!(graph.previews().is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn promote_to_property() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    graph.set_output(Some(add));
+
+    let (prop_id, prop_node) = graph.promote_to_property(InputId::new(add, 0))?;
+    let property = graph.property(prop_id)?;
+    assert_eq!(property.name(), "Add A");
+    assert_eq!(property.value(), &Value::F32(2.0));
+    assert_eq!(graph.get(prop_node)?.display_name(), "Add A");
+    assert!(matches!(
+      graph.get_node_input(add, 0)?,
+      Input::Connect(output, Some(DataType::F32)) if output == OutputId::new(prop_node, 0)
+    ));
+    assert_eq!(graph.evaluate()?, Value::F32(5.0));
+
+    graph.property_mut(prop_id)?.set_value(Value::F32(4.0));
+    assert_eq!(graph.evaluate()?, Value::F32(7.0));
+
+    // Connected inputs can't be promoted and names stay unique.
+    assert!(graph.promote_to_property(InputId::new(add, 0)).is_err());
+    let (prop_id, _) = graph.promote_to_property(InputId::new(add, 1))?;
+    assert_eq!(graph.property(prop_id)?.name(), "Add B");
+    let add2 = graph.add(reg.new_by_name("Add")?);
+    let (prop_id, _) = graph.promote_to_property(InputId::new(add2, 0))?;
+    assert_eq!(graph.property(prop_id)?.name(), "Add A 2");
+
+    // Property nodes are only created by promoting, they aren't in the node finder.
+    let prop_def = graph.get(prop_node)?.def().id;
+    assert!(!reg
+      .matching_nodes(&NodeFilter::default())
+      .contains(&prop_def));
+    Ok(())
+  }
+
+  #[test]
+  fn property_value_type() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    let (prop_id, prop_node) = graph.promote_to_property(InputId::new(add, 0))?;
+    let output = OutputId::new(prop_node, 0);
+    assert_eq!(graph.infer_types().get(&output), Some(&DataType::F32));
+
+    graph
+      .property_mut(prop_id)?
+      .set_value(Value::Vec2(glam::Vec2::ONE));
+    assert_eq!(graph.infer_types().get(&output), Some(&DataType::Vec2));
+    Ok(())
+  }
+
+  #[test]
+  fn merge_properties() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    let (prop_a, _) = graph.promote_to_property(InputId::new(add, 0))?;
+
+    // `other` has a property with the same name and one with the same id.
+    let mut other = NodeGraph::new();
+    let add = other.add(reg.new_by_name("Add")?);
+    other.set_node_input(add, "A", 5.0.into())?;
+    other.set_node_input(add, "B", 3.0.into())?;
+    other.promote_to_property(InputId::new(add, 0))?;
+    let (prop_b, _) = other.promote_to_property(InputId::new(add, 1))?;
+    let mut property = other.property(prop_b)?.clone();
+    other.properties.0.shift_remove(&prop_b);
+    property.id = prop_a;
+    other.add_property(property);
+    for node in other.nodes.0.values_mut() {
+      if let Some(node) = node.as_property_mut() {
+        if node.property() == prop_b {
+          node.set_property(prop_a);
+        }
+      }
+    }
+
+    let ids = graph.merge(other, emath::vec2(0., 300.))?;
+    assert_eq!(graph.properties().count(), 2);
+    let mut used = Vec::new();
+    for id in ids {
+      if let Some(node) = graph.get(id)?.as_property() {
+        used.push(graph.property(node.property())?.name().to_string());
+      }
+    }
+    used.sort();
+    assert_eq!(used, vec!["Add A", "Add B"]);
+    // The merged node uses the existing `Add A` property.
+    assert_eq!(graph.property(prop_a)?.value(), &Value::F32(2.0));
+    Ok(())
+  }
+
+  #[test]
+  #[cfg(feature = "egui")]
+  fn socket_links() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let sub = graph.add(reg.new_by_name("Subtract")?);
+    graph.set_input(InputId::new(add, 0), float.into())?;
+    graph.set_input(InputId::new(sub, 1), float.into())?;
+
+    assert_eq!(
+      graph.socket_links(NodeSocketId::input(add, 0)),
+      vec!["Float.Out".to_string()]
+    );
+    assert!(graph.socket_links(NodeSocketId::input(add, 1)).is_empty());
+    let mut consumers = graph.socket_links(NodeSocketId::output(float, 0));
+    consumers.sort();
+    assert_eq!(consumers, vec!["Add.A", "Subtract.B"]);
+    Ok(())
+  }
+
+  #[test]
+  fn clone_fresh() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_param(float, "Value", 2.0.into())?;
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    graph.set_output(Some(add));
+    let group_id = graph.add_group(NodeGroup::new());
+    graph.get_mut(add)?.group_id = group_id;
+
+    let copy = graph.clone_fresh()?;
+    assert_ne!(copy.id, graph.id);
+    assert_eq!(copy.stats(), graph.stats());
+    let ids: Vec<NodeId> = copy.nodes.0.keys().copied().collect();
+    assert!(ids.iter().all(|id| !graph.contains(*id)));
+    let new_add = copy.output().expect("output node");
+    assert_eq!(copy.get(new_add)?.display_name(), "Add");
+    assert_ne!(copy.get(new_add)?.group_id, group_id);
+    assert!(copy.get_group(copy.get(new_add)?.group_id).is_ok());
+    assert_eq!(copy.evaluate()?, Value::F32(5.0));
+
+    // The copies are independent.
+    graph.set_node_input(add, "B", 1.0.into())?;
+    assert_eq!(graph.evaluate()?, Value::F32(3.0));
+    assert_eq!(copy.evaluate()?, Value::F32(5.0));
+
+    // Removing the output node also removes the output.
+    let mut removed = graph.clone_fresh()?;
+    let output = removed.output().expect("output node");
+    removed.remove(output);
+    assert_eq!(removed.output(), None);
+    assert!(removed.clone_fresh()?.output().is_none());
+
+    // Dangling ids are reported instead of panicking.
+    graph
+      .outputs
+      .insert(VERTEX_OUTPUT.to_string(), Uuid::new_v4());
+    assert!(graph.clone_fresh().is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn shared_registry() -> Result<()> {
+    let reg = NodeRegistry::new();
+    let mut graph1 = NodeGraph::new();
+    let mut graph2 = NodeGraph::new();
+    graph1.set_registry(reg.clone());
+    graph2.set_registry(reg.clone());
+    assert!(graph1.registry().nodes().is_empty());
+
+    // Nodes registered later are visible to all graphs using the registry.
+    let def = NodeRegistry::build().new_by_name("Add")?.def().clone();
+    reg.register(&def);
+    assert_eq!(graph1.registry().new_by_name("Add")?.def().id, def.id);
+    assert_eq!(graph2.registry().new_by_name("Add")?.def().id, def.id);
+    Ok(())
+  }
+
+  #[test]
+  fn replace_node_type() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let type_id = |name: &str| -> Result<Uuid> { Ok(reg.new_by_name(name)?.def().id) };
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_param(float, "Value", 5.0.into())?;
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_input(InputId::new(add, 0), float.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    let sum = graph.add(reg.new_by_name("Sum")?);
+    graph.set_node_input(sum, 0, add.into())?;
+    graph.set_node_input(sum, 1, 1.0.into())?;
+    assert_eq!(graph.evaluate_node(sum)?, Value::F32(9.0));
+
+    let report = graph.replace_node_type(&reg, type_id("Add")?, type_id("Subtract")?)?;
+    assert_eq!(report.nodes, vec![add]);
+    assert!(report.dropped.is_empty());
+    assert!(report.dropped_values.is_empty());
+    assert_eq!(graph.get(add)?.def().name, "Subtract");
+    assert_eq!(graph.evaluate_node(sum)?, Value::F32(3.0));
+
+    // `Boolean` has no inputs, its output still fits the `Sum` input.
+    let report = graph.replace_node_type(&reg, type_id("Subtract")?, type_id("Boolean")?)?;
+    assert_eq!(
+      report.dropped,
+      vec![(InputId::new(add, 0), OutputId::new(float, 0))]
+    );
+    assert_eq!(
+      report.dropped_values,
+      vec![(InputId::new(add, 1), Value::F32(3.0))]
+    );
+    assert_eq!(graph.evaluate_node(sum)?, Value::F32(1.0));
+    assert!(graph
+      .replace_node_type(&reg, type_id("Boolean")?, Uuid::new_v4())
+      .is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn variadic_inputs() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let sum = graph.add(reg.new_by_name("Sum")?);
+    assert_eq!(graph.get(sum)?.input_count(), 2);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    graph.set_node_input(sum, 0, 1.0.into())?;
+    graph.set_node_input(sum, 1, add.into())?;
+    assert_eq!(graph.evaluate_node(sum)?, Value::F32(6.0));
+    // Inputs past the end of the group are rejected.
+    assert!(graph.set_node_input(sum, 2, add.into()).is_err());
+    assert!(graph.connections.get(&InputId::new(sum, 2)).is_none());
+
+    let mut node = nodes::math::SumNode::new();
+    node.values.push(4.0);
+    let sum = graph.add(Node::from_impl(Box::new(node)));
+    let def = graph.get(sum)?.def();
+    assert_eq!(
+      def.get_input(&InputKey::Idx(2)).map(|d| d.value_type),
+      Some(DataType::F32)
+    );
+    let loaded: NodeGraph = serde_json::from_str(&serde_json::to_string(&graph)?)?;
+    assert_eq!(loaded.get(sum)?.input_count(), 3);
+    assert_eq!(loaded.evaluate_node(sum)?, Value::F32(4.0));
+    Ok(())
+  }
+
+  #[test]
+  fn validate_set_input() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let curve = graph.add(reg.new_by_name("Curve")?);
+    let err = graph
+      .set_node_input(curve, "Input", glam::Vec4::ONE.into())
+      .unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "Wrong data type for input `Input`: expected F32 got Vec4"
+    );
+    // Lossless conversions are allowed and the value is converted like a connection.
+    graph.set_node_input(curve, "Input", 1i32.into())?;
+    assert!(matches!(
+      graph.get_node_input(curve, "Input")?,
+      Input::Value(Value::F32(v)) if v == 1.0
+    ));
+    let tiling = graph.add(reg.new_by_name("Tiling And Offset")?);
+    graph.set_node_input(tiling, "Tiling", 2.0.into())?;
+    assert!(matches!(
+      graph.get_node_input(tiling, "Tiling")?,
+      Input::Value(Value::Vec2(v)) if v == glam::Vec2::splat(2.0)
+    ));
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+
+    // Connections with an incompatible data type are rejected.
+    let output = OutputId::new(vec3, 0);
+    let node = graph.get_mut(curve)?;
+    assert!(node
+      .set_input("Input", Input::Connect(output, Some(DataType::Mat4)))
+      .is_err());
+    let input = Input::Connect(output, Some(DataType::Vec3));
+    graph.set_node_input(curve, "Input", input)?;
+    assert!(graph.connections.get(&InputId::new(curve, 0)).is_some());
+    Ok(())
+  }
+
+  #[test]
+  fn search_matches() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.add(reg.new_by_name("Add")?);
+    assert!(graph.search_matches().is_empty());
+
+    graph.set_search("flo");
+    assert_eq!(graph.search_matches(), vec![float]);
+    assert_eq!(graph.focus_search(), Some(float));
+    Ok(())
+  }
+
+  #[test]
+  fn unreachable_nodes() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float.into())?;
+    let orphan = graph.add(reg.new_by_name("Add")?);
+    let unused = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_input(orphan, "A", unused.into())?;
+    graph.set_output(Some(add));
+
+    let mut unreachable = graph.unreachable_nodes();
+    unreachable.sort();
+    let mut expected = vec![orphan, unused];
+    expected.sort();
+    assert_eq!(unreachable, expected);
+
+    assert_eq!(graph.remove_unreachable_nodes().len(), 2);
+    assert!(graph.contains(float) && graph.contains(add));
+    assert!(graph.unreachable_nodes().is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn connect_with_adapter() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    graph.set_node_param(float, "Value", 2.0.into())?;
+    let tiling = graph.add(reg.new_by_name("Tiling And Offset")?);
+    let input = graph.get_input_id(tiling, "Tiling")?;
+
+    let convert = graph
+      .connect_with_adapter(input, float.into(), DataType::F32)?
+      .expect("convert node");
+    assert_eq!(graph.get(convert)?.def().name, "Convert");
+    assert_eq!(
+      graph.output_consumers(float.into()),
+      vec![InputId::new(convert, 0)]
+    );
+    let value = NodeGraphExecution::new().eval_node(&graph, convert)?;
+    assert_eq!(value, Value::Vec2(glam::Vec2::new(2.0, 2.0)));
+
+    // Matching types are connected directly.
+    let float2 = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Multiply")?);
+    let input = graph.get_input_id(add, "A")?;
+    assert!(graph
+      .connect_with_adapter(input, float2.into(), DataType::F32)?
+      .is_none());
+    Ok(())
+  }
+
+  #[test]
+  fn strict_connection_policy() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let tiling = graph.add(reg.new_by_name("Tiling And Offset")?);
+    let input = graph.get_input_id(tiling, "Tiling")?;
+
+    // Scalar -> Vector is allowed by default.
+    assert_eq!(graph.connect(input, float.into(), DataType::F32)?, None);
+    // Replacing a connection returns the old output.
+    let float2 = graph.add(reg.new_by_name("Float")?);
+    assert_eq!(
+      graph.connect(input, float2.into(), DataType::F32)?,
+      Some(float.into())
+    );
+    assert_eq!(graph.output_consumers(float.into()), vec![]);
+    // Connecting the same output again isn't a change.
+    let changed = graph.changed_counter();
+    assert_eq!(graph.connect(input, float2.into(), DataType::F32)?, None);
+    assert_eq!(graph.changed_counter(), changed);
+    assert!(graph.take_dirty().contains(&tiling));
+    assert_eq!(graph.set_node_input(tiling, "Tiling", float2.into())?, None);
+    assert!(graph.take_dirty().is_empty());
+    // A different data type replaces the stored one.
+    assert_eq!(
+      graph.connect(input, float2.into(), DataType::Vec2)?,
+      Some(float2.into())
+    );
+    assert!(graph.take_dirty().contains(&tiling));
+    assert!(matches!(
+      graph.get_node_input(tiling, "Tiling")?,
+      Input::Connect(output, Some(DataType::Vec2)) if output == float2.into()
+    ));
+    graph.disconnect(input)?;
+
+    graph.set_connection_policy(ConnectionPolicy::Strict);
+    assert!(graph.connect(input, float.into(), DataType::F32).is_err());
+    assert!(graph
+      .set_node_input(tiling, "Tiling", float.into())
+      .is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn disconnect_output_fan_out() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = reg.new_by_name("Add")?;
+    let src = graph.add(add.duplicate());
+    let node1 = graph.add(add.duplicate());
+    let node2 = graph.add(add.duplicate());
+    graph.set_node_input(node1, "A", src.into())?;
+    graph.set_node_input(node1, "B", src.into())?;
+    graph.set_node_input(node2, "A", src.into())?;
+    assert_eq!(graph.output_consumers(src.into()).len(), 3);
+
+    // Re-connecting an input moves it to the new output.
+    graph.set_node_input(node1, "B", node2.into())?;
+    assert_eq!(graph.output_consumers(src.into()).len(), 2);
+    assert_eq!(graph.output_consumers(node2.into()).len(), 1);
+
+    graph.disconnect_output(src.into());
+    assert!(graph.output_consumers(src.into()).is_empty());
+    assert!(matches!(graph.get_node_input(node1, "A")?, Input::Value(_)));
+
+    // Removing a node removes the connections from it's outputs.
+    graph.remove(node2);
+    assert!(graph.output_consumers(node2.into()).is_empty());
+    Ok(())
+  }
+
+  #[test]
+  fn load_legacy_output() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+
+    let json = serde_json::to_string(&graph)?;
+    let legacy = json.replace(
+      &format!(r#""outputs":{{"fragment":"{frag}"}}"#),
+      &format!(r#""output":"{frag}""#),
+    );
+    assert_ne!(json, legacy);
+    let graph: NodeGraph = serde_json::from_str(&legacy)?;
+    assert_eq!(graph.output(), Some(frag));
+    Ok(())
+  }
+
+  #[test]
+  fn canonicalize() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(add, "A", vec3.into())?;
+    graph.set_node_input(add, "B", vec3.into())?;
+    graph.set_node_input(frag, "Color", add.into())?;
+    graph.set_output(Some(frag));
+
+    // Same graph with a different node and connection order.
+    let mut other = graph.clone();
+    let node = other.remove(vec3).expect("node exists");
+    other.add(node);
+    other.set_node_input(add, "B", vec3.into())?;
+    other.set_node_input(add, "A", vec3.into())?;
+    assert_ne!(
+      serde_json::to_string(&graph)?,
+      serde_json::to_string(&other)?
+    );
+
+    graph.canonicalize();
+    other.canonicalize();
+    assert_eq!(
+      serde_json::to_string(&graph)?,
+      serde_json::to_string(&other)?
+    );
+    Ok(())
+  }
+
+  #[cfg(feature = "binary")]
+  #[test]
+  fn save_load_bin() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_param(
+      vec3,
+      "Value",
+      ParameterValue::Value(Value::Vec3([1., 2., 3.].into())),
+    )?;
+    graph.set_node_input(add, "A", vec3.into())?;
+    graph.set_node_input(add, "B", Value::F32(0.5).into())?;
+    graph.set_node_input(frag, "Color", add.into())?;
+    graph.set_output(Some(frag));
+
+    let json = serde_json::to_vec(&graph)?;
+    let mut bin = Vec::new();
+    graph.save_bin(&mut bin)?;
+    assert!(!bin.is_empty());
+    assert!(
+      bin.len() < json.len(),
+      "json: {} bytes, binary: {} bytes",
+      json.len(),
+      bin.len()
+    );
+
+    let loaded = NodeGraph::load_bin(bin.as_slice())?;
+    assert_eq!(loaded.output(), Some(frag));
+    assert_eq!(serde_json::to_vec(&loaded)?, json);
+    assert!(matches!(
+      loaded.get_node_input(add, "A")?,
+      Input::Connect(id, _) if id.node() == vec3
+    ));
+    Ok(())
+  }
+
+  #[test]
+  fn to_dot() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", vec3.into())?;
+    graph.set_output(Some(add));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph {"));
+    assert!(dot.contains(&format!(r#""{vec3}" [label="Vector 3"];"#)));
+    assert!(dot.contains(&format!(
+      r#""{vec3}" -> "{add}" [label="Out (Vec3) -> A"];"#
+    )));
+    Ok(())
+  }
+
+  #[test]
+  fn camera_state() -> Result<()> {
+    let mut graph = NodeGraph::new();
+    let camera = CameraState {
+      zoom: 0.8,
+      scroll_offset: emath::vec2(1200., 3400.),
+    };
+    graph.set_camera(camera);
+    assert_eq!(graph.camera(), camera);
+
+    // The camera can be saved separately from the graph.
+    let json = serde_json::to_string(&graph.camera())?;
+    graph.reset_camera();
+    assert_eq!(graph.camera(), CameraState::default());
+    graph.set_camera(serde_json::from_str(&json)?);
+    assert_eq!(graph.camera(), camera);
+
+    graph.set_camera(CameraState {
+      zoom: 5.0,
+      ..camera
+    });
+    assert_eq!(graph.camera().zoom, 1.0);
+    Ok(())
+  }
+
+  #[test]
+  #[cfg(feature = "egui")]
+  fn save_with_ui() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    let float = graph.add(reg.new_by_name("Float")?);
+    let camera = CameraState {
+      zoom: 0.8,
+      scroll_offset: emath::vec2(1200., 3400.),
+    };
+    graph.set_camera(camera);
+    graph.select_node(add, true);
+    graph.set_details_node(Some(float));
+    graph.set_details_pinned(true);
+    let state = graph.ui_snapshot();
+    assert_eq!(state.selected, vec![add]);
+
+    let mut buf = Vec::new();
+    graph.save_with_ui(&mut buf)?;
+    let loaded = NodeGraph::load_with_ui(buf.as_slice())?;
+    assert_eq!(loaded.ui_snapshot(), state);
+    assert!(loaded.contains(add) && loaded.contains(float));
+
+    // The file is still a plain graph and plain graphs load without UI state.
+    let plain: NodeGraph = serde_json::from_slice(&buf)?;
+    assert!(plain.selection().is_empty());
+    let json = serde_json::to_vec(&plain)?;
+    let loaded = NodeGraph::load_with_ui(json.as_slice())?;
+    assert!(loaded.selection().is_empty());
+    assert_eq!(loaded.camera(), camera);
+    Ok(())
+  }
+
+  #[test]
+  fn display_name() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let node = graph.get_mut(vec3)?;
+    assert!(!node.is_renamed());
+    node.set_display_name("Position");
+    assert_eq!(node.display_name(), "Position");
+    assert!(node.is_renamed());
+
+    graph.set_search("position");
+    assert_eq!(graph.search_matches(), vec![vec3]);
+    assert!(graph.to_dot().contains(r#"[label="Position"]"#));
+
+    let node = graph.get_mut(vec3)?;
+    node.set_display_name("");
+    assert_eq!(node.display_name(), "Vector 3");
+    assert!(!node.is_renamed());
+    Ok(())
+  }
+
+  #[test]
+  fn node_lock() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let node = graph.get_mut(vec3)?;
+    assert!(!node.is_locked());
+    assert!(node.movable());
+    node.set_locked(true);
+    let area = node.rect();
+    node.handle_move(emath::vec2(10., 10.));
+    assert_eq!(node.rect(), area);
+
+    let loaded: NodeGraph = serde_json::from_str(&serde_json::to_string(&graph)?)?;
+    assert!(loaded.get(vec3)?.is_locked());
+    Ok(())
+  }
+
+  #[test]
+  fn fixed_size_nodes() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    let curve = graph.add(reg.new_by_name("Curve")?);
+    let node = graph.get(add)?;
+    assert!(node.auto_size() && node.def().auto_size);
+    assert!(!node.resizable());
+
+    // Fixed size nodes fit their contents once.
+    let node = graph.get_mut(curve)?;
+    assert!(!node.auto_size() && !node.def().auto_size);
+    assert!(node.resizable());
+    assert!(NodeFrame::auto_size(node));
+    node.set_rect(emath::Rect::from_min_size(
+      emath::pos2(0., 0.),
+      emath::vec2(300., 200.),
+    ));
+    assert!(!NodeFrame::auto_size(node));
+
+    let loaded: NodeGraph = serde_json::from_str(&serde_json::to_string(&graph)?)?;
+    let node = loaded.get(curve)?;
+    assert!(!NodeFrame::auto_size(node));
+    assert_eq!(node.rect().size(), emath::vec2(300., 200.));
+    Ok(())
+  }
+
+  #[test]
+  fn pin_details() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.show_in_details(Some(float));
+    assert_eq!(graph.details_node(), Some(float));
+    graph.show_in_details(Some(add));
+    assert_eq!(graph.details_node(), Some(add));
+
+    graph.set_details_pinned(true);
+    graph.show_in_details(Some(float));
+    graph.handle_clicked(true);
+    assert_eq!(graph.details_node(), Some(add));
+
+    // Removing the pinned node frees the panel.
+    graph.remove(add);
+    assert_eq!(graph.details_node(), None);
+    graph.show_in_details(Some(float));
+    assert_eq!(graph.details_node(), Some(float));
+    Ok(())
+  }
+
+  #[test]
+  fn node_snapshot() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut node = reg.new_by_name("Swizzle")?;
+    node.set_input("Input", glam::Vec3::new(1., 2., 3.).into())?;
+    node.set_param("Swizzle", ParameterValue::Text("zy".into()))?;
+    let snapshot = node.snapshot()?;
+
+    let mut other = reg.new_by_name("Swizzle")?;
+    other.restore(snapshot.clone())?;
+    assert_eq!(other.snapshot()?, snapshot);
+    assert_eq!(
+      other.get_param("Swizzle")?,
+      ParameterValue::Text("zy".into())
+    );
+    // Snapshots of other node types are rejected.
+    assert!(reg.new_by_name("Float")?.restore(snapshot).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn group_color_and_lock() -> Result<()> {
+    let mut graph = NodeGraph::new();
+    let group_id = graph.add_group(NodeGroup::new());
+    let group = graph.get_group_mut(group_id)?;
+    group.set_area(emath::Rect::from_min_size(
+      emath::pos2(0., 0.),
+      emath::vec2(100., 100.),
+    ));
+    group.set_color(ecolor::Color32::DARK_BLUE);
+    group.set_locked(true);
+
+    let loaded: NodeGraph = serde_json::from_str(&serde_json::to_string(&graph)?)?;
+    let group = loaded.get_group(group_id)?;
+    assert_eq!(group.color(), ecolor::Color32::DARK_BLUE);
+    assert!(group.is_locked());
+
+    // Groups saved before the options existed use the defaults.
+    let group: NodeGroup = serde_json::from_str(
+      r#"{"id":"00000000-0000-0000-0000-000000000001","title":"","area":{"min":{"x":0,"y":0},"max":{"x":1,"y":1}}}"#,
+    )?;
+    assert_eq!(group.color(), ecolor::Color32::from_gray(10));
+    assert!(!group.is_locked());
+    Ok(())
+  }
+
+  #[test]
+  fn nested_groups() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let outer = graph.add_group(NodeGroup::new());
+    let inner = graph.add_group(NodeGroup::new());
+    graph.get_mut(float)?.group_id = inner;
+    graph.set_group_parent(inner, outer)?;
+    assert_eq!(graph.group_descendants(outer), vec![inner]);
+    assert_eq!(graph.group_ancestors(inner), vec![outer]);
+    assert!(graph.set_group_parent(outer, inner).is_err());
+
+    // Resizing bubbles up to the parent group.
+    graph.resize_group(inner);
+    let node_area = graph.get(float)?.area;
+    assert!(graph.get_group(inner)?.area().contains_rect(node_area));
+    assert!(graph
+      .get_group(outer)?
+      .area()
+      .contains_rect(graph.get_group(inner)?.area()));
+
+    // Moving the outer group moves the nested group and its nodes.
+    let delta = emath::vec2(100., 50.);
+    let inner_area = graph.get_group(inner)?.area();
+    graph.move_group_contents(outer, delta);
+    assert_eq!(graph.get(float)?.area, node_area.translate(delta));
+    assert_eq!(graph.get_group(inner)?.area(), inner_area.translate(delta));
+
+    // Locking the outer group locks the nested group.
+    graph.get_group_mut(outer)?.set_locked(true);
+    assert!(graph.is_group_locked(inner));
+
+    // Removing the outer group keeps its contents.
+    graph.remove_group(outer, false);
+    assert!(graph.get_group(inner)?.parent_id.is_nil());
+    graph.remove_group(inner, true);
+    assert!(!graph.contains(float));
+    Ok(())
+  }
+
+  #[test]
+  #[cfg(feature = "egui")]
+  fn socket_layout() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    graph.get_mut(vec3)?.set_position(emath::vec2(400., 0.));
+    // Before rendering, the layout is based on the node's current area.
+    let layout = graph.socket_layout(add);
+    assert_eq!(layout.len(), 3);
+    assert_eq!(layout[0].0, NodeSocketId::input(add, 0));
+
+    // Render the graph and compare with the socket positions from the UI.
+    let ctx = egui::Context::default();
+    for _ in 0..3 {
+      let _ = ctx.run(Default::default(), |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| graph.graph_ui(ui));
+      });
+    }
+    for id in [add, vec3] {
+      for (socket_id, pos) in graph.socket_layout(id) {
+        let socket = graph
+          .ui_state
+          .get_socket(&socket_id)
+          .expect("Socket rendered");
+        let rendered = socket.center - graph.editor.origin;
+        assert!(
+          (rendered - pos).length() < 1.0,
+          "{socket_id:?}: {rendered:?} != {pos:?}"
+        );
+      }
+    }
+    Ok(())
+  }
+
+  #[test]
+  #[cfg(feature = "egui")]
+  fn fragment_output_frame() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    let vertex = graph.add(reg.new_by_name("Vertex")?);
+    graph.get_mut(vertex)?.set_position(emath::vec2(400., 0.));
+    graph.set_output(Some(frag));
+    graph.set_vertex_output(Some(vertex));
+
+    let ctx = egui::Context::default();
+    let _ = ctx.run(Default::default(), |ctx| {
+      egui::CentralPanel::default().show(ctx, |ui| graph.graph_ui(ui));
+    });
+    // The vertex output can still be made the graph output.
+    let frame = graph.ui_state.frame_state(vertex);
+    assert!(frame.output);
+    assert!(!frame.fragment_output);
+    let frame = graph.ui_state.frame_state(frag);
+    assert!(frame.output);
+    assert!(frame.fragment_output);
+    Ok(())
+  }
+
+  #[test]
+  fn auto_layout() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let a = graph.add(reg.new_by_name("Float")?);
+    let b = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    // Connect in the opposite order of the nodes to force a crossing.
+    graph.set_node_input(add, "A", b.into())?;
+    graph.set_node_input(add, "B", a.into())?;
+    graph.set_node_input(frag, "Color", add.into())?;
+    graph.auto_layout();
+
+    let pos = |id: NodeId| graph.get(id).map(|node| node.area.min);
+    // Each node is to the right of its inputs.
+    assert!(pos(a)?.x < pos(add)?.x && pos(b)?.x < pos(add)?.x);
+    assert!(pos(add)?.x < pos(frag)?.x);
+    // The sources are in the same layer, without overlapping.
+    assert_eq!(pos(a)?.x, pos(b)?.x);
+    assert!((pos(a)?.y - pos(b)?.y).abs() >= AUTO_LAYOUT_SPACING.y);
+    Ok(())
+  }
+
+  #[test]
+  fn insert_on_connection() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", vec3.into())?;
+    let input = InputId::new(frag, 0);
+
+    let swizzle = graph.add(reg.new_by_name("Swizzle")?);
+    assert_eq!(graph.insertion_sockets(swizzle, input), Some((0, 0)));
+    // A node's own connection.
+    assert_eq!(graph.insertion_sockets(vec3, input), None);
+    // Nodes without a compatible input can't be inserted.
+    let float = graph.add(reg.new_by_name("Float")?);
+    assert_eq!(graph.insertion_sockets(float, input), None);
+    assert!(graph.insert_on_connection(float, input).is_err());
+
+    graph.insert_on_connection(swizzle, input)?;
+    assert_eq!(
+      graph.connections.get(&input),
+      Some(&OutputId::new(swizzle, 0))
+    );
+    assert_eq!(
+      graph.connections.get(&InputId::new(swizzle, 0)),
+      Some(&OutputId::new(vec3, 0))
+    );
+    // Connected nodes can't be inserted again.
+    assert_eq!(graph.insertion_sockets(swizzle, input), None);
+    Ok(())
+  }
+
+  #[test]
+  fn diff() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let swizzle = graph.add(reg.new_by_name("Swizzle")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", vec3.into())?;
+    assert!(graph.diff(&graph.clone()).is_empty());
+
+    let mut other = graph.clone();
+    other.remove(vec3);
+    let vec4 = other.add(reg.new_by_name("Vector 4")?);
+    other.set_node_input(frag, "Color", vec4.into())?;
+    other.set_node_param(swizzle, "Swizzle", ParameterValue::Text("xy".into()))?;
+
+    let diff = graph.diff(&other);
+    assert_eq!(diff.added_nodes, vec![vec4]);
+    assert_eq!(diff.removed_nodes, vec![vec3]);
+    assert!(diff.changed_nodes.contains(&swizzle));
+    assert_eq!(
+      diff.added_connections,
+      vec![(InputId::new(frag, 0), OutputId::new(vec4, 0))]
+    );
+    assert_eq!(
+      diff.removed_connections,
+      vec![(InputId::new(frag, 0), OutputId::new(vec3, 0))]
+    );
+    assert_eq!(diff.param_changes.len(), 1);
+    assert_eq!(diff.param_changes[0].node, swizzle);
+    assert_eq!(diff.param_changes[0].new, ParameterValue::Text("xy".into()));
+    Ok(())
+  }
+
+  #[test]
+  fn merge() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_output(Some(frag));
+
+    let mut other = NodeGraph::new();
+    let mut vec3 = reg.new_by_name("Vector 3")?;
+    vec3.set_position(emath::vec2(100., 100.));
+    let vec3 = other.add(vec3);
+    let mut group = NodeGroup::new();
+    let mut swizzle = reg.new_by_name("Swizzle")?;
+    swizzle.set_position(emath::vec2(300., 150.));
+    group.add_node(&mut swizzle);
+    other.add_group(group);
+    let swizzle = other.add(swizzle);
+    let input = other.get_input_id(swizzle, "Input")?;
+    other.connect(input, vec3.into(), DataType::Vec3)?;
+    let mut other_frag = reg.new_by_name("Fragment")?;
+    other_frag.set_position(emath::vec2(500., 100.));
+    let other_frag = other.add(other_frag);
+    other.set_output(Some(other_frag));
+
+    let ids = graph.merge(other.clone(), emath::vec2(1000., 0.))?;
+    assert_eq!(ids.len(), 3);
+    assert!(ids.iter().all(|id| !other.contains(*id)));
+    // The destination keeps its output.
+    assert_eq!(graph.output(), Some(frag));
+
+    let stats = graph.stats();
+    assert_eq!((stats.nodes, stats.connections, stats.groups), (4, 1, 1));
+    let (new_vec3, new_swizzle) = (ids[0], ids[1]);
+    assert_eq!(
+      graph
+        .get_input_id(new_swizzle, "Input")
+        .ok()
+        .and_then(|input| graph.connections.get(&input)),
+      Some(&OutputId::new(new_vec3, 0))
+    );
+    // The connection keeps its data type.
+    assert!(matches!(
+      graph.get_node_input(new_swizzle, "Input")?,
+      Input::Connect(_, Some(DataType::Vec3))
+    ));
+    let vec3_area = graph.get(new_vec3)?.area;
+    assert_eq!(vec3_area.min, emath::pos2(1000., 0.));
+    let swizzle = graph.get(new_swizzle)?;
+    assert_eq!(swizzle.area.min, emath::pos2(1200., 50.));
+    assert!(graph.get_group(swizzle.group_id).is_ok());
+
+    // Dangling ids are reported and nothing is merged.
+    other
+      .outputs
+      .insert(VERTEX_OUTPUT.to_string(), Uuid::new_v4());
+    assert!(graph.merge(other, emath::Vec2::ZERO).is_err());
+    assert_eq!(graph.stats(), stats);
+    Ok(())
+  }
+
+  #[test]
+  fn stats() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    assert_eq!(graph.stats(), GraphStats::default());
+    let vec3 = graph.add(reg.new_by_name("Vector 3")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(frag, "Color", vec3.into())?;
+    graph.add_group(NodeGroup::new());
+    let stats = graph.stats();
+    assert_eq!(
+      stats,
+      GraphStats {
+        nodes: 2,
+        connections: 1,
+        groups: 1,
+        properties: 0,
+      }
+    );
+    assert_eq!(
+      stats.to_string(),
+      "nodes: 2, connections: 1, groups: 1, properties: 0"
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn middle_mouse_pan() {
+    let ctx = egui::Context::default();
+    let mut graph = NodeGraph::new();
+    let start = graph.editor.scroll_offset;
+    let zoom = graph.editor.zoom;
+    let mut frame = |events| {
+      let input = egui::RawInput {
+        events,
+        screen_rect: Some(emath::Rect::from_min_size(
+          emath::Pos2::ZERO,
+          emath::vec2(800., 600.),
+        )),
+        ..Default::default()
+      };
+      let _ = ctx.run(input, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| graph.show(ui));
+      });
+    };
+    let pos = emath::pos2(100., 100.);
+    frame(vec![egui::Event::PointerMoved(pos)]);
+    frame(vec![egui::Event::PointerButton {
+      pos,
+      button: egui::PointerButton::Middle,
+      pressed: true,
+      modifiers: egui::Modifiers::NONE,
+    }]);
+    frame(vec![egui::Event::PointerMoved(pos + emath::vec2(50., 20.))]);
+    frame(vec![egui::Event::PointerButton {
+      pos,
+      button: egui::PointerButton::Middle,
+      pressed: false,
+      modifiers: egui::Modifiers::NONE,
+    }]);
+    let moved = start - graph.editor.scroll_offset;
+    assert!(
+      (moved - emath::vec2(50., 20.) / zoom).length() < 0.01,
+      "{moved:?}"
+    );
+    assert!(!graph.editor.panning);
+  }
+
+  #[test]
+  fn selection_nodes_and_groups() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let mut group = NodeGroup::new();
+    group.add_node(graph.get_mut(add)?);
+    let group1 = graph.add_group(group);
+    let group2 = graph.add_group(NodeGroup::new());
+    graph.select_node(float, true);
+    graph.select_node(group1, true);
+    graph.select_node(group2, true);
+    assert_eq!(graph.take_selection(), (vec![float], vec![group1, group2]));
+    assert!(!graph.has_selected());
+
+    graph.select_node(float, true);
+    graph.select_node(group1, true);
+    graph.remove_selected();
+    assert!(!graph.contains(float));
+    // The nodes of a removed group are kept.
+    assert!(graph.contains(add));
+    assert!(graph.get_group(group1).is_err());
+    assert!(graph.get_group(group2).is_ok());
+    Ok(())
+  }
+
+  #[test]
+  fn node_finder_keyboard() {
+    let key = |key| egui::RawInput {
+      events: vec![egui::Event::Key {
+        key,
+        physical_key: None,
+        pressed: true,
+        repeat: false,
+        modifiers: egui::Modifiers::NONE,
+      }],
+      ..Default::default()
+    };
+    let ctx = egui::Context::default();
+    let mut finder = NodeFinder::default();
+    let frame = |finder: &mut NodeFinder, input| {
+      let mut node = None;
+      let _ = ctx.run(input, |ctx| {
+        egui::CentralPanel::default().show(ctx, |ui| node = finder.ui(ui));
+      });
+      node
+    };
+    finder.open_at(emath::pos2(0., 0.));
+    finder.node_filter.name = "vector".to_string();
+    let matches = finder.registry.matching_nodes(&finder.node_filter);
+    assert!(matches.len() > 1);
+    // Enter doesn't pick anything without a highlighted node.
+    assert!(frame(&mut finder, key(egui::Key::Enter)).is_none());
+    assert!(frame(&mut finder, key(egui::Key::ArrowDown)).is_none());
+    assert!(frame(&mut finder, key(egui::Key::ArrowDown)).is_none());
+    assert_eq!(finder.highlight, Some(1));
+    let node = frame(&mut finder, key(egui::Key::Enter)).expect("Picked node");
+    assert_eq!(node.def().id, matches[1]);
+    assert!(!finder.open);
+
+    // A single match is picked with Enter.
+    finder.open_at(emath::pos2(0., 0.));
+    finder.node_filter.name = "float array".to_string();
+    let node = frame(&mut finder, key(egui::Key::Enter)).expect("Picked node");
+    assert_eq!(node.def().name, "Float Array");
   }
 }
@@ -13,8 +13,20 @@ pub const NODE_GROUP_MARGIN: f32 = 50.0;
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NodeGroup {
   pub id: NodeGroupId,
+  /// The group containing this group.  Nil for top-level groups.
+  #[serde(default)]
+  pub parent_id: NodeGroupId,
   title: String,
   area: emath::Rect,
+  #[serde(default = "default_group_color")]
+  color: ecolor::Color32,
+  /// Locked groups can't be moved or resized, including their nodes.
+  #[serde(default)]
+  locked: bool,
+}
+
+fn default_group_color() -> ecolor::Color32 {
+  ecolor::Color32::from_gray(10)
 }
 
 impl GetId for NodeGroup {
@@ -27,18 +39,55 @@ impl NodeGroup {
   pub fn new() -> Self {
     Self {
       id: Uuid::new_v4(),
+      parent_id: Uuid::nil(),
       title: "".to_string(),
       area: emath::Rect::NOTHING,
+      color: default_group_color(),
+      locked: false,
     }
   }
 
+  pub fn title(&self) -> &str {
+    &self.title
+  }
+
+  pub fn area(&self) -> emath::Rect {
+    self.area
+  }
+
+  pub fn translate(&mut self, delta: emath::Vec2) {
+    self.area = self.area.translate(delta);
+  }
+
+  pub fn color(&self) -> ecolor::Color32 {
+    self.color
+  }
+
+  pub fn set_color(&mut self, color: ecolor::Color32) {
+    self.color = color;
+  }
+
+  pub fn is_locked(&self) -> bool {
+    self.locked
+  }
+
+  pub fn set_locked(&mut self, locked: bool) {
+    self.locked = locked;
+  }
+
   pub fn set_area(&mut self, area: emath::Rect) {
     self.area = area.expand(NODE_GROUP_MARGIN);
   }
 
   pub fn add_node(&mut self, node: &mut Node) {
     node.group_id = self.id;
-    self.area = self.area.union(node.rect().expand(NODE_GROUP_MARGIN));
+    self.area = self.area.union(node.area.expand(NODE_GROUP_MARGIN));
+  }
+
+  /// Make `group` a child of this group.
+  pub fn add_group(&mut self, group: &mut NodeGroup) {
+    group.parent_id = self.id;
+    self.area = self.area.union(group.area.expand(NODE_GROUP_MARGIN));
   }
 }
 
@@ -63,11 +112,35 @@ impl NodeFrame for NodeGroup {
   /// Frame style
   fn frame_style(&self) -> NodeFrameStyle {
     NodeFrameStyle {
-      fill: egui::Color32::from_gray(10),
+      fill: self.color,
       ..Default::default()
     }
   }
 
+  fn themed_frame_style(&self, theme: &NodeFrameStyle) -> NodeFrameStyle {
+    NodeFrameStyle {
+      fill: self.color,
+      ..theme.clone()
+    }
+  }
+
+  /// Box selections inside the group shouldn't select it.
+  fn select_contained(&self) -> bool {
+    true
+  }
+
+  fn resizable(&self) -> bool {
+    !self.locked
+  }
+
+  fn movable(&self) -> bool {
+    !self.locked
+  }
+
+  fn is_locked(&self) -> bool {
+    self.locked
+  }
+
   /// Handle events and context menu.
   fn handle_resp(
     &mut self,
@@ -79,7 +152,7 @@ impl NodeFrame for NodeGroup {
     let mut action = None;
     if resp.clicked() {
       action = Some(NodeAction::Clicked);
-    } else if resp.dragged() {
+    } else if resp.dragged() && !frame.locked {
       if frame.is_dragging() {
         action = Some(NodeAction::Dragged(resp.drag_delta()));
       } else {
@@ -87,10 +160,24 @@ impl NodeFrame for NodeGroup {
       }
     }
     resp.context_menu(|ui| {
+      ui.horizontal(|ui| {
+        ui.label("Color");
+        egui::color_picker::color_edit_button_srgba(
+          ui,
+          &mut self.color,
+          egui::color_picker::Alpha::Opaque,
+        );
+      });
+      ui.checkbox(&mut self.locked, "Locked");
+      ui.separator();
       if ui.button("Add selected nodes").clicked() {
         action = Some(NodeAction::JoinGroup(self.id));
         ui.close_menu();
       }
+      if ui.button("Collapse to subgraph").clicked() {
+        action = Some(NodeAction::Collapse);
+        ui.close_menu();
+      }
       if ui.button("Delete group").clicked() {
         action = Some(NodeAction::Delete(false));
         ui.close_menu();
@@ -0,0 +1,310 @@
+//! Import graphs from a ShaderGraph-style JSON format.
+//!
+//! Only a subset of the format is supported:
+//!
+//! ```json
+//! {
+//!   "nodes": [
+//!     {
+//!       "id": "a",
+//!       "type": "UnityEditor.ShaderGraph.Vector1Node",
+//!       "position": { "x": 0.0, "y": 0.0 },
+//!       "params": { "Value": 0.5 }
+//!     },
+//!     {
+//!       "id": "b",
+//!       "type": "UnityEditor.ShaderGraph.AddNode",
+//!       "inputs": { "B": [1.0, 2.0, 3.0] }
+//!     }
+//!   ],
+//!   "edges": [
+//!     { "output": { "node": "a", "slot": "Out" }, "input": { "node": "b", "slot": 0 } }
+//!   ],
+//!   "outputs": { "fragment": "c" }
+//! }
+//! ```
+//!
+//! * Node types are matched by the last part of the type name with the `Node` suffix
+//!   removed, with a few renames (`Vector1` -> `Float`).
+//! * Slots can be referenced by name or index.
+//! * Input and parameter values can be numbers, booleans, strings or arrays of 2-4 numbers.
+//! * Unknown node types become `Placeholder` nodes, edges to them are skipped.
+use std::collections::HashMap;
+use std::path::Path;
+
+use glam::{Vec2, Vec3, Vec4};
+use serde::Deserialize;
+
+use anyhow::{anyhow, Result};
+
+use crate::*;
+
+impl_node! {
+  mod placeholder_node {
+    NodeInfo {
+      name: "Placeholder",
+      category: ["Import"],
+    }
+
+    /// Placeholder for an unknown imported node.
+    #[derive(Default)]
+    pub struct PlaceholderNode {
+    }
+
+    impl PlaceholderNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for PlaceholderNode {
+      fn compile(&self, _graph: &NodeGraph, _compile: &mut NodeGraphCompile, _id: NodeId) -> Result<()> {
+        Err(anyhow!("Placeholder nodes can't be compiled"))
+      }
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ImportSlot {
+  Idx(u32),
+  Name(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportPosition {
+  x: f32,
+  y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportNode {
+  id: String,
+  #[serde(rename = "type")]
+  node_type: String,
+  #[serde(default)]
+  name: Option<String>,
+  #[serde(default)]
+  position: Option<ImportPosition>,
+  #[serde(default)]
+  inputs: HashMap<String, serde_json::Value>,
+  #[serde(default)]
+  params: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportSocket {
+  node: String,
+  slot: ImportSlot,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportEdge {
+  output: ImportSocket,
+  input: ImportSocket,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportGraph {
+  #[serde(default)]
+  nodes: Vec<ImportNode>,
+  #[serde(default)]
+  edges: Vec<ImportEdge>,
+  #[serde(default)]
+  outputs: HashMap<String, String>,
+}
+
+/// Map an external node type name to a registered node name.
+fn node_name(node_type: &str) -> String {
+  let name = node_type.rsplit('.').next().unwrap_or(node_type);
+  let name = name.strip_suffix("Node").unwrap_or(name);
+  match name {
+    "Vector1" => "Float",
+    "Vector2" => "Vector 2",
+    "Vector3" => "Vector 3",
+    "Vector4" => "Vector 4",
+    "Minimum" => "Min",
+    "Maximum" => "Max",
+    "SquareRoot" => "Square Root",
+    "SampleTexture2D" => "Texture Sample",
+    "TilingAndOffset" => "Tiling And Offset",
+    "ViewDirection" => "View Direction",
+    "UV" => "Uv",
+    name => name,
+  }
+  .to_string()
+}
+
+/// Find a slot by index, name or case-insensitive name.
+fn find_slot<'a>(slot: &ImportSlot, mut names: impl Iterator<Item = &'a String>) -> Option<u32> {
+  match slot {
+    ImportSlot::Idx(idx) => Some(*idx),
+    ImportSlot::Name(name) => names
+      .position(|n| n.eq_ignore_ascii_case(name))
+      .map(|idx| idx as u32),
+  }
+}
+
+fn import_value(value: &serde_json::Value) -> Result<Value> {
+  use serde_json::Value as Json;
+  match value {
+    Json::Number(n) => Ok(Value::F32(n.as_f64().unwrap_or_default() as f32)),
+    Json::Bool(b) => Ok(Value::F32(if *b { 1.0 } else { 0.0 })),
+    Json::Array(values) => {
+      let v = values
+        .iter()
+        .map(|v| {
+          v.as_f64()
+            .map(|v| v as f32)
+            .ok_or_else(|| anyhow!("Expected a number got: {v}"))
+        })
+        .collect::<Result<Vec<f32>>>()?;
+      match v.len() {
+        2 => Ok(Value::Vec2(Vec2::from_slice(&v))),
+        3 => Ok(Value::Vec3(Vec3::from_slice(&v))),
+        4 => Ok(Value::Vec4(Vec4::from_slice(&v))),
+        len => Err(anyhow!("Unsupported vector length: {len}")),
+      }
+    }
+    value => Err(anyhow!("Unsupported value: {value}")),
+  }
+}
+
+fn import_param(def: &ParameterDefinition, value: &serde_json::Value) -> Result<ParameterValue> {
+  match (&def.param_type, value) {
+    (ParameterDataType::Select(_, _), serde_json::Value::String(s)) => {
+      Ok(ParameterValue::Selected(s.clone()))
+    }
+    (
+      ParameterDataType::Text(_) | ParameterDataType::MultilineText(_),
+      serde_json::Value::String(s),
+    ) => Ok(ParameterValue::Text(s.clone())),
+    (ParameterDataType::Curve, serde_json::Value::Array(points)) => {
+      let points = points
+        .iter()
+        .map(|p| match import_value(p)? {
+          Value::Vec2(p) => Ok(p),
+          p => Err(anyhow!("Expected a curve point got: {p:?}")),
+        })
+        .collect::<Result<Vec<Vec2>>>()?;
+      Ok(ParameterValue::Curve(points))
+    }
+    (ParameterDataType::Value(dt), value) => {
+      Ok(ParameterValue::Value(import_value(value)?.convert(*dt)?))
+    }
+    (param_type, value) => Err(anyhow!(
+      "Unsupported parameter value: expected {param_type:?} got {value}"
+    )),
+  }
+}
+
+/// Import a graph from a ShaderGraph-style JSON string.  See the module docs for the format.
+pub fn import_graph(reg: &NodeRegistry, json: &str) -> Result<NodeGraph> {
+  let import: ImportGraph = serde_json::from_str(json)?;
+  let mut graph = NodeGraph::new();
+  let mut ids = HashMap::new();
+  for node in &import.nodes {
+    let name = node_name(&node.node_type);
+    let mut new_node = match reg.new_by_name(&name) {
+      Ok(new_node) => new_node,
+      Err(_) => {
+        log::warn!("Unknown node type: {}", node.node_type);
+        let mut placeholder = reg.new_by_name("Placeholder")?;
+        placeholder.name = format!("Unknown: {}", node.node_type);
+        placeholder
+      }
+    };
+    if let Some(name) = &node.name {
+      new_node.name = name.clone();
+    }
+    if let Some(pos) = &node.position {
+      new_node.set_position(emath::vec2(pos.x, pos.y));
+    }
+    for (name, value) in &node.params {
+      let res = new_node
+        .def()
+        .get_parameter(name)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unknown parameter"))
+        .and_then(|def| import_param(&def, value))
+        .and_then(|value| new_node.set_param(name, value));
+      if let Err(err) = res {
+        log::warn!(
+          "Failed to import parameter {name:?} of node {:?}: {err:?}",
+          node.id
+        );
+      }
+    }
+    let id = graph.add(new_node);
+    for (name, value) in &node.inputs {
+      let res = import_value(value).and_then(|value| graph.set_node_input(id, name, value.into()));
+      if let Err(err) = res {
+        log::warn!(
+          "Failed to import input {name:?} of node {:?}: {err:?}",
+          node.id
+        );
+      }
+    }
+    ids.insert(node.id.clone(), id);
+  }
+
+  for edge in &import.edges {
+    let res = (|| -> Result<()> {
+      let output_node = *ids
+        .get(&edge.output.node)
+        .ok_or_else(|| anyhow!("Unknown output node"))?;
+      let input_node = *ids
+        .get(&edge.input.node)
+        .ok_or_else(|| anyhow!("Unknown input node"))?;
+      let output_idx = find_slot(
+        &edge.output.slot,
+        graph.get(output_node)?.def().outputs.keys(),
+      )
+      .ok_or_else(|| anyhow!("Unknown output slot"))?;
+      let input_idx = find_slot(&edge.input.slot, graph.get(input_node)?.def().inputs.keys())
+        .ok_or_else(|| anyhow!("Unknown input slot"))?;
+      let output = OutputId::new(output_node, output_idx);
+      let dt = graph
+        .output_data_type(output)
+        .ok_or_else(|| anyhow!("Unknown output slot"))?;
+      graph.connect(InputId::new(input_node, input_idx), output, dt)?;
+      Ok(())
+    })();
+    if let Err(err) = res {
+      log::warn!("Failed to import edge {edge:?}: {err:?}");
+    }
+  }
+
+  for (name, node) in &import.outputs {
+    match ids.get(node) {
+      Some(id) => graph.set_output_named(name, Some(*id)),
+      None => log::warn!("Unknown output node {node:?} for output {name:?}"),
+    }
+  }
+  Ok(graph)
+}
+
+/// Import a graph from a ShaderGraph-style JSON file.
+pub fn import_graph_file<P: AsRef<Path>>(reg: &NodeRegistry, path: P) -> Result<NodeGraph> {
+  let json = std::fs::read_to_string(path)?;
+  import_graph(reg, &json)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn import_sample() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let graph = import_graph(&reg, include_str!("../tests/data/shadergraph_sample.json"))?;
+    let frag = graph.output().expect("fragment output");
+    assert_eq!(graph.get(frag)?.name, "Fragment");
+
+    let mut execution = NodeGraphExecution::new();
+    let value = execution.eval_graph(&graph)?;
+    assert_eq!(value, Value::Vec4(Vec4::new(1.5, 2.5, 3.5, 1.0)));
+    Ok(())
+  }
+}
@@ -4,6 +4,9 @@ use anyhow::{anyhow, Result};
 use crate::ui::*;
 use crate::*;
 
+/// Identifies a node input by index or by name.
+///
+/// Names can be either the input's display name (`"Color"`) or its field name (`"color"`).
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub enum InputKey {
   Idx(u32),
@@ -103,13 +106,35 @@ impl<T: ValueType, const N: u32> InputTyped<T, N> {
         }
         value
       }
-      None => self.value.compile()?,
+      None => compile.literal(self.value.compile()?),
     };
     // Make sure the value is in our type.
     value.convert(self.value.data_type())?;
     Ok(value)
   }
 
+  /// Evaluate the input value.  The CPU version of `resolve`.
+  pub fn eval_value(
+    &self,
+    concrete_type: &mut NodeConcreteType,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+  ) -> Result<Value> {
+    let value = match &self.connected {
+      Some((id, _)) => {
+        let value = execution.eval_output(graph, *id)?;
+        if self.is_dynamic() {
+          // Collect info about dynamic inputs.
+          concrete_type.add_input_type(value.data_type());
+        }
+        value
+      }
+      None => self.value.to_value(),
+    };
+    // Make sure the value is in our type.
+    value.convert(self.value.data_type())
+  }
+
   pub fn compile(
     &self,
     graph: &NodeGraph,
@@ -117,7 +142,7 @@ impl<T: ValueType, const N: u32> InputTyped<T, N> {
   ) -> Result<CompiledValue> {
     let mut value = match &self.connected {
       Some((id, _)) => compile.resolve_output(graph, *id)?,
-      None => self.value.compile()?,
+      None => compile.literal(self.value.compile()?),
     };
     // Make sure the value is in our type.
     value.convert(self.value.data_type())?;
@@ -151,34 +176,80 @@ impl<T: ValueType, const N: u32> InputTyped<T, N> {
     ui: &mut egui::Ui,
     id: NodeId,
     details: bool,
+  ) -> bool {
+    self.socket_ui(N, concrete_type, def, ui, id, details)
+  }
+
+  /// Render the input using socket index `idx`.
+  #[cfg(feature = "egui")]
+  fn socket_ui(
+    &mut self,
+    idx: u32,
+    concrete_type: &mut NodeConcreteType,
+    def: &InputDefinition,
+    ui: &mut egui::Ui,
+    id: NodeId,
+    details: bool,
   ) -> bool {
     let mut changed = false;
+    // Resolve connected dynamic inputs in both views, the parameters are validated against them.
+    let connected = self.connected.map(|(output_id, dt)| {
+      if self.is_dynamic() {
+        let dt = NodeGraphMeta::get(ui).and_then(|g| g.resolve_output(&output_id));
+        if let Some(dt) = dt {
+          concrete_type.add_input_type(dt);
+        }
+        (output_id, dt)
+      } else {
+        (output_id, dt)
+      }
+    });
     ui.horizontal(|ui| {
       if details {
         ui.collapsing(&def.name, |ui| {
           changed = self.value.ui(ui);
         });
       } else {
-        match self.connected {
-          Some((output_id, mut dt)) => {
-            if self.is_dynamic() {
-              dt = NodeGraphMeta::get(ui).and_then(|g| g.resolve_output(&output_id));
-              if let Some(dt) = dt {
-                concrete_type.add_input_type(dt);
-              }
-            }
-            let mut socket = NodeSocket::input(id, N, true, def);
+        match connected {
+          Some((output_id, dt)) => {
+            let mut socket = NodeSocket::input(id, idx, true, def);
             if let Some(dt) = dt {
               socket.set_data_type(dt);
             }
+            // Warn about connections that drop data.
+            let warning = if self.is_dynamic() {
+              None
+            } else {
+              NodeGraphMeta::get(ui)
+                .and_then(|g| g.resolve_output(&output_id))
+                .or(dt)
+                .and_then(|out_dt| out_dt.lossy_warning(&self.value.data_type()))
+            };
             ui.add(socket);
-            ui.label(&def.name);
+            match warning {
+              Some(warning) => {
+                let color = ui.visuals().warn_fg_color;
+                ui.label(egui::RichText::new(&def.name).color(color))
+                  .on_hover_text(warning);
+              }
+              None => {
+                ui.label(&def.name);
+              }
+            }
           }
           None => {
-            ui.add(NodeSocket::input(id, N, false, def));
-            ui.collapsing(&def.name, |ui| {
+            ui.add(NodeSocket::input(id, idx, false, def));
+            let resp = ui.collapsing(&def.name, |ui| {
               changed = self.value.ui(ui);
             });
+            resp.header_response.context_menu(|ui| {
+              if ui.button("Promote to property").clicked() {
+                if let Some(meta) = NodeGraphMeta::get(ui) {
+                  meta.request_promote(InputId::new(id, idx));
+                }
+                ui.close_menu();
+              }
+            });
           }
         }
       }
@@ -190,12 +261,156 @@ impl<T: ValueType, const N: u32> InputTyped<T, N> {
 impl<T: ValueType + Clone + Default, const N: u32> InputTyped<T, N> {
   pub fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution) -> Result<T> {
     match &self.connected {
-      Some((OutputId { node: id, .. }, _)) => {
+      Some((id, _)) => {
         let mut val = T::default();
-        val.set_value(execution.eval_node(graph, *id)?)?;
+        val.set_value(execution.eval_output(graph, *id)?)?;
         Ok(val)
       }
       None => Ok(self.value.clone()),
     }
   }
 }
+
+/// A variadic group of inputs.  Used for the last input field of a node (`Inputs<T>`).
+///
+/// The inputs use the indices `N..N + len`.
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct InputsTyped<T, const N: u32> {
+  inputs: Vec<InputTyped<T, N>>,
+}
+
+impl<T: ValueType + Default, const N: u32> InputsTyped<T, N> {
+  /// Create a group with `count` inputs.
+  pub fn new(count: usize) -> Self {
+    Self {
+      inputs: (0..count).map(|_| InputTyped::new(T::default())).collect(),
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.inputs.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.inputs.is_empty()
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = &InputTyped<T, N>> {
+    self.inputs.iter()
+  }
+
+  /// Append an input.
+  pub fn push(&mut self, value: T) {
+    self.inputs.push(InputTyped::new(value));
+  }
+
+  /// Remove the last input.  Connected inputs need to be disconnected first.
+  pub fn pop(&mut self) -> Result<()> {
+    match self.inputs.last() {
+      Some(input) if input.is_connected() => Err(anyhow!("Can't remove a connected input")),
+      Some(_) => {
+        self.inputs.pop();
+        Ok(())
+      }
+      None => Err(anyhow!("No inputs to remove")),
+    }
+  }
+
+  /// Returns true if node input `idx` is part of this group.
+  pub fn contains(&self, idx: u32) -> bool {
+    idx
+      .checked_sub(N)
+      .is_some_and(|idx| (idx as usize) < self.inputs.len())
+  }
+
+  /// Get node input `idx`.
+  pub fn get_input(&self, idx: u32) -> Result<Input> {
+    idx
+      .checked_sub(N)
+      .and_then(|idx| self.inputs.get(idx as usize))
+      .map(|input| input.as_input())
+      .ok_or_else(|| anyhow!("Invalid input index: {idx}"))
+  }
+
+  /// Set node input `idx`.
+  pub fn set_input(&mut self, idx: u32, input: Input) -> Result<Option<OutputId>> {
+    idx
+      .checked_sub(N)
+      .and_then(|idx| self.inputs.get_mut(idx as usize))
+      .ok_or_else(|| anyhow!("Invalid input index: {idx}"))?
+      .set_input(input)
+  }
+
+  pub fn resolve(
+    &self,
+    concrete_type: &mut NodeConcreteType,
+    graph: &NodeGraph,
+    compile: &mut NodeGraphCompile,
+  ) -> Result<Vec<CompiledValue>> {
+    self
+      .inputs
+      .iter()
+      .map(|input| input.resolve(concrete_type, graph, compile))
+      .collect()
+  }
+
+  /// Evaluate the input values.  The CPU version of `resolve`.
+  pub fn eval_values(
+    &self,
+    concrete_type: &mut NodeConcreteType,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+  ) -> Result<Vec<Value>> {
+    self
+      .inputs
+      .iter()
+      .map(|input| input.eval_value(concrete_type, graph, execution))
+      .collect()
+  }
+
+  pub fn compile(
+    &self,
+    graph: &NodeGraph,
+    compile: &mut NodeGraphCompile,
+  ) -> Result<Vec<CompiledValue>> {
+    self
+      .inputs
+      .iter()
+      .map(|input| input.compile(graph, compile))
+      .collect()
+  }
+
+  #[cfg(feature = "egui")]
+  pub fn ui(
+    &mut self,
+    concrete_type: &mut NodeConcreteType,
+    def: &InputDefinition,
+    ui: &mut egui::Ui,
+    id: NodeId,
+    details: bool,
+  ) -> bool {
+    let mut changed = false;
+    for (idx, input) in self.inputs.iter_mut().enumerate() {
+      let mut def = def.clone();
+      def.name = format!("{} {}", def.name, idx + 1);
+      if input.socket_ui(N + idx as u32, concrete_type, &def, ui, id, details) {
+        changed = true;
+      }
+    }
+    ui.horizontal(|ui| {
+      if ui.small_button("+").on_hover_text("Add input").clicked() {
+        self.push(T::default());
+        changed = true;
+      }
+      let removable = self
+        .inputs
+        .last()
+        .is_some_and(|input| !input.is_connected());
+      if removable && ui.small_button("-").on_hover_text("Remove input").clicked() {
+        changed = self.pop().is_ok();
+      }
+    });
+    changed
+  }
+}
@@ -14,6 +14,8 @@ pub mod registry;
 pub use registry::*;
 pub mod graph;
 pub use graph::*;
+pub mod spatial;
+pub use spatial::*;
 pub mod macros;
 
 pub mod color;
@@ -21,11 +23,22 @@ pub use color::*;
 
 pub mod eval;
 pub use eval::*;
+pub mod profile;
+pub use profile::*;
 pub mod compile;
 pub use compile::*;
+pub mod template;
+pub use template::*;
+pub mod subgraph;
+pub use subgraph::*;
+pub mod preset;
+pub use preset::*;
 
 pub mod nodes;
 
+#[cfg(feature = "import")]
+pub mod import;
+
 // pre-export for use in `impl_node` macro.
 #[doc(hidden)]
 pub extern crate heck;
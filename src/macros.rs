@@ -5,25 +5,91 @@ macro_rules! replace_expr {
 
 #[macro_export]
 macro_rules! impl_enum_parameter_type {
-  // Parse Parameter enum types.
-  ($(#[$param_enum_meta:meta])*
-  pub enum $param_enum_name:ident {
-    $(
-      $(#[$variant_meta:meta])*
-      $variant_name:ident
-    ),+ $(,)?
-  }) => {
+  // Split the variants into their `cfg` and other attributes, label, aliases and name.
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*] $label:tt $aliases:tt
+    # [cfg $($cfg_args:tt)*] $($rest:tt)*
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header $default [$($variants)*]
+      [$($cfg)* #[cfg $($cfg_args)*]] [$($meta)*] $label $aliases $($rest)*);
+  };
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*] $label:tt $aliases:tt
+    # [default] $($rest:tt)*
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header [default] [$($variants)*]
+      [$($cfg)*] [$($meta)* #[default]] $label $aliases $($rest)*);
+  };
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*] $label:tt $aliases:tt
+    # [label = $variant_label:literal] $($rest:tt)*
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header $default [$($variants)*]
+      [$($cfg)*] [$($meta)*] [$variant_label] $aliases $($rest)*);
+  };
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*] $label:tt [$($aliases:tt)*]
+    # [alias = $variant_alias:literal] $($rest:tt)*
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header $default [$($variants)*]
+      [$($cfg)*] [$($meta)*] $label [$($aliases)* $variant_alias] $($rest)*);
+  };
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*] $label:tt $aliases:tt
+    # [$($attr:tt)*] $($rest:tt)*
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header $default [$($variants)*]
+      [$($cfg)*] [$($meta)* #[$($attr)*]] $label $aliases $($rest)*);
+  };
+  (@parse $header:tt $default:tt [$($variants:tt)*] [$($cfg:tt)*] [$($meta:tt)*]
+    [$($variant_label:literal)?] [$($variant_alias:literal)*]
+    $variant_name:ident $(, $($rest:tt)*)?
+  ) => {
+    $crate::impl_enum_parameter_type!(@parse $header $default
+      [$($variants)* { [$($cfg)*] [$($meta)*] $variant_name [$($variant_label)?] [$($variant_alias)*] }]
+      [] [] [] [] $($($rest)*)?);
+  };
+  (@parse $header:tt $default:tt $variants:tt [] [] [] []) => {
+    $crate::impl_enum_parameter_type!(@impl $header $default $variants);
+  };
+  // Make the first variant the default if no variant is marked with `#[default]`.
+  (@impl $header:tt []
+    [{ $cfg:tt [$($meta:tt)*] $($first:tt)* } $($variants:tt)*]
+  ) => {
+    $crate::impl_enum_parameter_type!(@impl $header [default]
+      [{ $cfg [#[default] $($meta)*] $($first)* } $($variants)*]);
+  };
+  // The display label of a variant, defaults to the variant name.
+  (@label $variant_name:ident) => {
+    stringify!($variant_name)
+  };
+  (@label $variant_name:ident $variant_label:literal) => {
+    $variant_label
+  };
+  (@impl [$(#[$param_enum_meta:meta])* $param_enum_name:ident] [default] [$({
+    [$(#[$variant_cfg:meta])*]
+    [$(#[$variant_meta:meta])*]
+    $variant_name:ident [$($variant_label:literal)?] [$($variant_alias:literal)*]
+  })+]) => {
     $(#[$param_enum_meta])*
     #[derive(Copy, Clone, Debug, Default)]
     #[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
     pub enum $param_enum_name {
-      #[default]
       $(
+        $(#[$variant_cfg])*
         $(#[$variant_meta])*
+        $(#[serde(alias = $variant_alias)])*
         $variant_name
       ),+
     }
 
+    impl $param_enum_name {
+      /// The display label of the variant.
+      pub fn label(&self) -> &'static str {
+        match self {
+          $(
+            $(#[$variant_cfg])*
+            Self::$variant_name => $crate::impl_enum_parameter_type!(@label $variant_name $($variant_label)?)
+          ),+
+        }
+      }
+    }
+
     impl ParameterType for $param_enum_name {
       fn get_param(&self) -> ParameterValue {
         ParameterValue::Selected(format!("{self:?}"))
@@ -33,7 +99,8 @@ macro_rules! impl_enum_parameter_type {
         match value {
           ParameterValue::Selected(val) => match val.as_str() {
             $(
-              stringify!($variant_name) => {
+              $(#[$variant_cfg])*
+              stringify!($variant_name) $(| $variant_alias)* => {
                 *self = Self::$variant_name;
                 Ok(())
               }
@@ -47,13 +114,40 @@ macro_rules! impl_enum_parameter_type {
       }
 
       fn parameter_data_type() -> ParameterDataType {
-        ParameterDataType::select(&[
+        ParameterDataType::select_with_labels(&[
           $(
+            $(#[$variant_cfg])*
             stringify!($variant_name)
           ),+
+        ], &[
+          $(
+            $(#[$variant_cfg])*
+            Self::$variant_name.label()
+          ),+
         ])
       }
+
+      fn parameter_aliases() -> Vec<String> {
+        let mut aliases: Vec<&'static str> = Vec::new();
+        $(
+          $(#[$variant_cfg])*
+          aliases.extend_from_slice(&[$($variant_alias),*]);
+        )+
+        aliases.into_iter().map(String::from).collect()
+      }
     }
+  };
+  // Parse Parameter enum types.
+  //
+  // Variants can have any attributes.  A display label can be given with
+  // `#[label = "Label"]` and old names that are still accepted with
+  // `#[alias = "OldName"]`.
+  ($(#[$param_enum_meta:meta])*
+  pub enum $param_enum_name:ident {
+    $($variants:tt)*
+  }) => {
+    $crate::impl_enum_parameter_type!(@parse [$(#[$param_enum_meta])* $param_enum_name] [] [] [] [] [] []
+      $($variants)*);
   }
 }
 
@@ -74,6 +168,7 @@ macro_rules! impl_node {
         []
         [0] [0] [0]
         []
+        []
         {}
         {}
         {}
@@ -90,6 +185,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -109,6 +205,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         { $( $node_impl )* }
@@ -126,6 +223,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -155,6 +253,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         { $( $node_impl )* }
@@ -172,6 +271,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       []
       {}
       { $( $node_impl:tt )* }
@@ -193,6 +293,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         []
         {
           #[doc = $node_struct_doc]
@@ -217,6 +318,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       []
       {}
       { $( $node_impl:tt )* }
@@ -237,6 +339,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         []
         {
           #[doc = ""]
@@ -261,13 +364,14 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
       { $( $node_trait_impl:tt )* }
       ___internal_parse_fields {
           $( #[$field_meta:meta] )*
-          $field_vis:vis $field_name:ident : Input<$field_ty:ident> $(Color($color:tt))?,
+          $field_vis:vis $field_name:ident : Input<$field_ty:ident> $(Color($color:tt))? $(= $default:expr)?,
           $($unparsed_fields:tt)*
       }
       $($rest:tt)*
@@ -280,15 +384,63 @@ macro_rules! impl_node {
         { $( $extra_code )* }
         [
           $( $node_inputs )*
-          $field_name : $field_ty Idx($( $count_inputs )*) $(Color($color))?,
+          $field_name : $field_ty Idx($( $count_inputs )*) $(Color($color))? $(Default($default))?,
         ]
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* + 1 ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ ]
         [
           $( $node_struct_fields )*
           $( #[$field_meta] )*
-          $field_vis $field_name : InputTyped<$field_ty, { $( $count_inputs )* }>,
+          $field_vis $field_name : InputTyped<$field_ty, { $( $count_inputs )* }> $(= $default)?,
+        ]
+        { $( $node_struct )* }
+        { $( $node_impl )* }
+        { $( $node_trait_impl )* }
+        ___internal_parse_fields {
+            $($unparsed_fields)*
+        }
+        $($rest)*
+      }
+    }
+  };
+  // Parse Node variadic input group.  Must be the last input field.
+  (@normalize_fields
+    mod $mod_name:ident {
+      { $( $node_info:tt )* }
+      { $( $extra_code:tt )* }
+      [ $( $node_inputs:tt )* ]
+      [ $( $node_parameters:tt )* ]
+      [ $( $node_outputs:tt )* ]
+      [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ ]
+      [ $( $node_struct_fields:tt )* ]
+      { $( $node_struct:tt )* }
+      { $( $node_impl:tt )* }
+      { $( $node_trait_impl:tt )* }
+      ___internal_parse_fields {
+          $( #[$field_meta:meta] )*
+          $field_vis:vis $field_name:ident : Inputs<$field_ty:ident>,
+          $($unparsed_fields:tt)*
+      }
+      $($rest:tt)*
+    }
+  ) => {
+    $crate::impl_node! {
+      @normalize_fields
+      mod $mod_name {
+        { $( $node_info )* }
+        { $( $extra_code )* }
+        [ $( $node_inputs )* ]
+        [ $( $node_parameters )* ]
+        [ $( $node_outputs )* ]
+        [ $( $count_inputs )* + 1 ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $field_name : $field_ty Idx($( $count_inputs )*) ]
+        [
+          $( $node_struct_fields )*
+          $( #[$field_meta] )*
+          $field_vis $field_name : InputsTyped<$field_ty, { $( $count_inputs )* }>,
         ]
         { $( $node_struct )* }
         { $( $node_impl )* }
@@ -309,13 +461,15 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
       { $( $node_trait_impl:tt )* }
       ___internal_parse_fields {
           $( #[$field_meta:meta] )*
-          $field_vis:vis $field_name:ident : Param<$field_ty:ident>,
+          $field_vis:vis $field_name:ident : Param<$field_ty:ident>
+            $(Range($range_min:expr, $range_max:expr))? $(Clamp($clamp_min:expr, $clamp_max:expr))?,
           $($unparsed_fields:tt)*
       }
       $($rest:tt)*
@@ -329,10 +483,11 @@ macro_rules! impl_node {
         [ $( $node_inputs )* ]
         [
           $( $node_parameters )*
-          $field_name : $field_ty,
+          $field_name : $field_ty $(Range($range_min, $range_max))? $(Clamp($clamp_min, $clamp_max))?,
         ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [
           $( $node_struct_fields )*
           $( #[$field_meta] )*
@@ -357,6 +512,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -381,6 +537,7 @@ macro_rules! impl_node {
           $field_name : $field_ty Idx($( $count_outputs )*) $(Color($color))?,
         ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* + 1 ]
+        [ $( $node_variadic )* ]
         [
           $( $node_struct_fields )*
           $( #[$field_meta] )*
@@ -406,6 +563,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -427,6 +585,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [
           $( $node_struct_fields )*
           $( #[$field_meta] )*
@@ -451,6 +610,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -468,6 +628,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         { $( $node_impl )* }
@@ -485,6 +646,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -505,6 +667,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         {
@@ -529,6 +692,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -549,6 +713,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         { $( $node_impl )* }
@@ -571,6 +736,7 @@ macro_rules! impl_node {
       [ $( $node_parameters:tt )* ]
       [ $( $node_outputs:tt )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
+      [ $( $node_variadic:tt )* ]
       [ $( $node_struct_fields:tt )* ]
       { $( $node_struct:tt )* }
       { $( $node_impl:tt )* }
@@ -591,6 +757,7 @@ macro_rules! impl_node {
         [ $( $node_parameters )* ]
         [ $( $node_outputs )* ]
         [ $( $count_inputs )* ] [ $( $count_params )* ] [ $( $count_outputs )* ]
+        [ $( $node_variadic )* ]
         [ $( $node_struct_fields )* ]
         { $( $node_struct )* }
         {
@@ -623,6 +790,9 @@ macro_rules! impl_node {
         $(
           , deprecated: $node_deprecated:expr
         )?
+        $(
+          , auto_size: $node_auto_size:expr
+        )?
         $(
           , custom: {
             $( $custom_field_name:ident: $custom_field_value:literal ),*
@@ -632,11 +802,17 @@ macro_rules! impl_node {
         $(,)?
       }
       { $( $extra_code:tt )* }
-      [ $( $field_input_name:ident: $field_input_ty:ident Idx($field_input_idx:expr) $(Color($field_input_color:tt))?,)* ]
-      [ $( $field_param_name:ident: $field_param_ty:ident, )* ]
+      [ $( $field_input_name:ident: $field_input_ty:ident Idx($field_input_idx:expr) $(Color($field_input_color:tt))? $(Default($field_input_default:expr))?,)* ]
+      [ $( $field_param_name:ident: $field_param_ty:ident
+        $(Range($field_param_min:expr, $field_param_max:expr))?
+        $(Clamp($field_param_clamp_min:expr, $field_param_clamp_max:expr))?, )* ]
       [ $( $field_output_name:ident: $field_output_ty:ident Idx($field_output_idx:expr) $(Color($field_output_color:tt))?, )* ]
       [ $( $count_inputs:tt )* ] [ $( $count_params:tt )* ] [ $( $count_outputs:tt )* ]
-      [ $( $node_struct_fields:tt )* ]
+      [ $( $variadic_input_name:ident: $variadic_input_ty:ident Idx($variadic_input_idx:expr) )? ]
+      [ $(
+        $( #[$struct_field_meta:meta] )*
+        $struct_field_vis:vis $struct_field_name:ident : $struct_field_ty:ty $(= $struct_field_default:expr)?,
+      )* ]
       {
         #[doc = $node_struct_doc:expr]
         $(#[$node_struct_attr:meta])*
@@ -676,11 +852,33 @@ macro_rules! impl_node {
           $(
             def.deprecated = $node_deprecated;
           )?
+          $(
+            def.auto_size = $node_auto_size;
+          )?
           def.inputs = [
             $( InputDefinition::typed::<$field_input_ty>(stringify!($field_input_name)) ),*
           ].into();
+          $(
+            let (name, input) = InputDefinition::typed::<$variadic_input_ty>(stringify!($variadic_input_name));
+            def.inputs.insert(name, input);
+            def.variadic_inputs = true;
+          )?
           def.parameters = [
-            $( ParameterDefinition::typed::<$field_param_ty>(stringify!($field_param_name)) ),*
+            $({
+              #[allow(unused_mut)]
+              let (name, mut param) =
+                ParameterDefinition::typed::<$field_param_ty>(stringify!($field_param_name));
+              $(
+                param.range = Some($crate::ParameterRange::new($field_param_min, $field_param_max));
+              )?
+              $(
+                param.range = Some($crate::ParameterRange::clamped(
+                  $field_param_clamp_min,
+                  $field_param_clamp_max,
+                ));
+              )?
+              (name, param)
+            }),*
           ].into();
           def.outputs = [
             $( OutputDefinition::typed::<$field_output_ty>(stringify!($field_output_name)) ),*
@@ -691,6 +889,9 @@ macro_rules! impl_node {
             )*
           )?
           $( def.package = $node_package.to_string(); )?
+          let impl_fns: &[&str] = &$crate::impl_node!(@impl_fn_names [] $( $ty_node_impl_fns )*);
+          def.supports_eval = impl_fns.iter().any(|f| *f == "eval" || *f == "eval_output");
+          def.supports_compile = impl_fns.contains(&"compile");
           // Save source file to help with debugging duplicates (uuid clashes).
           def.source_file = file!().to_string();
 
@@ -702,6 +903,15 @@ macro_rules! impl_node {
               }
             )?
           )*
+          // Set custom input defaults
+          $(
+            $(
+              {
+                let default: $field_input_ty = $field_input_default;
+                def.set_input_default($field_input_idx, Some($crate::ValueType::to_value(&default)));
+              }
+            )?
+          )*
           $(
             $(
               {
@@ -724,7 +934,15 @@ macro_rules! impl_node {
       #[derive(Clone, Debug)]
       #[derive($crate::serde::Serialize, $crate::serde::Deserialize)]
       pub struct $node_ty_name {
-        $( $node_struct_fields )*
+        $(
+          $( #[$struct_field_meta] )*
+          $struct_field_vis $struct_field_name : $struct_field_ty,
+        )*
+      }
+
+      $crate::impl_node! {
+        @impl_default
+        $node_ty_name [ $( $struct_field_name [ $( $struct_field_default )? ] )* ]
       }
 
       $( $node_impl )*
@@ -756,6 +974,11 @@ macro_rules! impl_node {
                 Ok(self.$field_input_name.as_input())
               }
             )*
+            $(
+              idx if self.$variadic_input_name.contains(idx) => {
+                self.$variadic_input_name.get_input(idx)
+              }
+            )?
             _ => Err(anyhow::anyhow!("Invalid input key: {key:?}")),
           }
         }
@@ -765,17 +988,31 @@ macro_rules! impl_node {
             #[allow(non_upper_case_globals)]
             const $field_input_name: u32 = $field_input_idx;
           )*
+          let idx = self.get_input_idx(key)?;
+          let _value = match DEFINITION.get_input(&InputKey::Idx(idx)) {
+            Some(def) => def.check(_value)?,
+            None => _value,
+          };
           #[allow(non_upper_case_globals)]
-          match self.get_input_idx(key)? {
+          match idx {
             $(
               $field_input_name => {
                 self.$field_input_name.set_input(_value)
               }
             )*
+            $(
+              idx if self.$variadic_input_name.contains(idx) => {
+                self.$variadic_input_name.set_input(idx, _value)
+              }
+            )?
             _ => Err(anyhow::anyhow!("Invalid input key: {key:?}")),
           }
         }
 
+        fn input_count(&self) -> usize {
+          DEFINITION.inputs.len() $( - 1 + self.$variadic_input_name.len() )?
+        }
+
         fn get_param(&self, name: &str) -> Result<ParameterValue> {
           match self.def().get_parameter(name) {
             Some(param) => match param.field_name.as_str() {
@@ -795,6 +1032,7 @@ macro_rules! impl_node {
             Some(param) => match param.field_name.as_str() {
               $(
                 stringify!($field_param_name) => {
+                  let _value = param.check(_value)?;
                   self.$field_param_name.set_param(_value)
                 }
               )*
@@ -815,6 +1053,13 @@ macro_rules! impl_node {
               }
             }
           )*
+          $(
+            if let Some(def) = _defs.next() {
+              if self.$variadic_input_name.ui(_concrete_type, def, _ui, _id, _details) {
+                _updated = true;
+              }
+            }
+          )?
           _updated
         }
 
@@ -823,7 +1068,8 @@ macro_rules! impl_node {
           let mut _defs = DEFINITION.outputs.values();
           $(
             if let Some(def) = _defs.next() {
-              self.$field_output_name.ui(_concrete_type, def, _ui, _id, _details);
+              let dt = self.output_concrete_type(self.$field_output_name.idx(), _concrete_type, def.value_type);
+              self.$field_output_name.ui(dt, def, _ui, _id, _details);
             }
           )*
           false
@@ -838,6 +1084,9 @@ macro_rules! impl_node {
               if self.$field_param_name.parameter_ui(def, _ui, _id, _details) {
                 _updated = true;
               }
+              if let Err(err) = self.$field_param_name.validate_concrete_type(_concrete_type) {
+                _ui.colored_label(_ui.visuals().error_fg_color, err.to_string());
+              }
             }
           )*
           _updated
@@ -850,6 +1099,46 @@ macro_rules! impl_node {
     }
     pub use $mod_name::$node_ty_name;
   };
+  // Implement `Default` using the input default values.  Only done if an input has a
+  // default value, those nodes can't derive `Default`.
+  (@impl_default $node_ty_name:ident $fields:tt) => {
+    $crate::impl_node!(@impl_default_scan $node_ty_name $fields $fields);
+  };
+  (@impl_default_scan $node_ty_name:ident $fields:tt []) => {};
+  (@impl_default_scan $node_ty_name:ident $fields:tt [ $_name:ident [] $( $rest:tt )* ]) => {
+    $crate::impl_node!(@impl_default_scan $node_ty_name $fields [ $( $rest )* ]);
+  };
+  (@impl_default_scan $node_ty_name:ident
+    [ $( $field_name:ident [ $( $field_default:expr )? ] )* ]
+    [ $_name:ident [ $_default:expr ] $( $rest:tt )* ]
+  ) => {
+    impl Default for $node_ty_name {
+      fn default() -> Self {
+        Self {
+          $(
+            $field_name: $crate::impl_node!(@field_default $( $field_default )?),
+          )*
+        }
+      }
+    }
+  };
+  (@field_default) => {
+    Default::default()
+  };
+  (@field_default $default:expr) => {
+    $crate::InputTyped::new($default)
+  };
+  // Collect the names of the `NodeImpl` methods implemented by the node.
+  (@impl_fn_names [ $( $fn_names:expr ),* ]) => {
+    [ $( $fn_names ),* ]
+  };
+  (@impl_fn_names [ $( $fn_names:expr ),* ]
+    $(#[$fn_meta:meta])*
+    fn $fn_name:ident ( $( $fn_args:tt )* ) $( -> $fn_ret:ty )? { $( $fn_body:tt )* }
+    $($rest:tt)*
+  ) => {
+    $crate::impl_node!(@impl_fn_names [ $( $fn_names, )* stringify!($fn_name) ] $($rest)*)
+  };
   // Implement compile inputs helper.  No inputs.
   (@impl_resolve_inputs
     $node_ty_name:ident []
@@ -866,6 +1155,16 @@ macro_rules! impl_node {
         self.$field_input_name.compile(graph, compile)
       }
     }
+
+    impl $node_ty_name {
+      #[allow(dead_code)]
+      pub fn eval_inputs(&self, graph: &$crate::NodeGraph, execution: &mut $crate::NodeGraphExecution)
+        -> Result<$crate::Value>
+      {
+        let mut concrete_type = $crate::NodeConcreteType::default();
+        self.$field_input_name.eval_value(&mut concrete_type, graph, execution)
+      }
+    }
   };
   // Implement compile inputs helper.
   (@impl_resolve_inputs
@@ -895,6 +1194,31 @@ macro_rules! impl_node {
           $field_input_name
         ),*))
       }
+
+      #[allow(dead_code)]
+      pub fn eval_inputs(&self, graph: &$crate::NodeGraph, execution: &mut $crate::NodeGraphExecution)
+        -> Result<($(
+            $crate::replace_expr!(
+              $field_input_name
+              $crate::Value
+            )
+      ),*)>
+      {
+        let mut concrete_type = $crate::NodeConcreteType::default();
+        $(
+          let $field_input_name = self.$field_input_name.eval_value(&mut concrete_type, graph, execution)?;
+        )*
+        $(
+          let $field_input_name = if concrete_type.has_dynamic() && self.$field_input_name.is_dynamic() {
+            concrete_type.convert_value($field_input_name)?
+          } else {
+            $field_input_name
+          };
+        )*
+        Ok(($(
+          $field_input_name
+        ),*))
+      }
     }
   }
 }
@@ -906,6 +1230,22 @@ mod test {
 
   use crate::*;
 
+  impl_enum_parameter_type!(
+    /// Variants with their own attributes.
+    #[derive(PartialEq, Eq)]
+    pub enum Quality {
+      Low,
+      #[default]
+      #[serde(rename = "medium")]
+      #[label = "Medium Quality"]
+      Medium,
+      #[cfg(any())]
+      Disabled,
+      #[alias = "Best"]
+      High,
+    }
+  );
+
   impl_node! {
     mod test_node {
       NodeInfo {
@@ -917,30 +1257,38 @@ mod test {
           test_custom_field1: "Test value",
           // custom field 2.  All values are converted to `String`.
           test_custom_field2: 1234,
+          // custom field 3.  Read with `NodeDefinition::custom_bool`.
+          test_custom_field3: true,
         },
       }
 
       /// Document for `Op` parameter enum.
       pub enum Op {
         /// Math add op description.
+        #[label = "Add (+)"]
         Add,
         /// Math sub op description.
+        #[alias = "Subtract"]
+        #[alias = "Minus"]
         Sub,
       }
 
       /// Node short description.
       ///
       /// Longer node docs...
-      #[derive(Default)]
       pub struct TestNode {
         /// Input `color`.
         pub color: Input<Vec2>,
         /// Input `scale`.
-        pub scale: Input<f32>,
+        pub scale: Input<f32> = 1.0,
         /// Parameter `param`.
         pub param: Param<Vec2>,
         /// Parameter `op`.
         pub op: Param<Op>,
+        /// Parameter `amount`.
+        pub amount: Param<f32> Range(0.0, 1.0),
+        /// Parameter `offset`.
+        pub offset: Param<Vec2> Clamp(-1.0, 1.0),
         /// Output `color`.
         pub out: Output<Vec2>,
         // Internal node field.
@@ -973,6 +1321,15 @@ mod test {
     node.set_input("Scale", 3.14.into()).unwrap();
     node.set_param("Param", Vec2::new(1.0, 2.0).into()).unwrap();
     node.set_param("Op", "Sub".into()).unwrap();
+    node.set_param("Amount", 0.5.into()).unwrap();
+    let def = node.def();
+    assert_eq!(def.custom("test_custom_field1"), Some("Test value"));
+    assert_eq!(def.custom_i64("test_custom_field2"), Some(1234));
+    assert_eq!(def.custom_f64("test_custom_field2"), Some(1234.0));
+    assert_eq!(def.custom_bool("test_custom_field3"), Some(true));
+    assert_eq!(def.custom_i64("test_custom_field1"), None);
+    assert!(!def.supports_eval);
+    assert!(def.supports_compile);
     println!(" - node: {:#?}", node);
     println!("   - def: {:#?}", node.def());
     println!(
@@ -980,4 +1337,117 @@ mod test {
       serde_json::to_string_pretty(&node).unwrap()
     );
   }
+
+  #[test]
+  fn input_defaults() {
+    let def = TestNode::new().def().clone();
+    assert_eq!(def.inputs["Scale"].default_value(), Value::F32(1.0));
+    assert_eq!(def.inputs["Color"].default, None);
+    // New nodes from the definition use the defaults.
+    let node = def.new_node().unwrap();
+    assert!(matches!(
+      node.get_node_input(&"Scale".into()).unwrap(),
+      Input::Value(Value::F32(v)) if v == 1.0
+    ));
+    assert!(matches!(
+      node.get_node_input(&"Color".into()).unwrap(),
+      Input::Value(Value::Vec2(v)) if v == Vec2::ZERO
+    ));
+    // The struct's constructor uses them too.
+    assert!(matches!(
+      TestNode::new().get_node_input(&"Scale".into()).unwrap(),
+      Input::Value(Value::F32(v)) if v == 1.0
+    ));
+  }
+
+  #[test]
+  fn enum_labels() {
+    let mut node = TestNode::new();
+    let param = node.def().get_parameter("Op").expect("Op param");
+    assert_eq!(param.param_type.select_label("Add"), "Add (+)");
+    assert_eq!(param.param_type.select_label("Sub"), "Sub");
+    assert_eq!(test_node::Op::Add.label(), "Add (+)");
+    // Values still use the variant names.
+    assert_eq!(node.get_param("Op").unwrap(), ParameterValue::from("Add"));
+    assert!(node.set_param("Op", "Add (+)".into()).is_err());
+    node.set_param("Op", "Sub".into()).unwrap();
+    assert_eq!(serde_json::to_value(node.op).unwrap(), "Sub");
+    // No labels given.
+    assert_eq!(
+      CoordSpace::parameter_data_type(),
+      ParameterDataType::select(&["Object", "View", "World", "Tangent"])
+    );
+  }
+
+  #[test]
+  fn enum_variant_attributes() {
+    assert_eq!(Quality::default(), Quality::Medium);
+    assert_eq!(serde_json::to_value(Quality::Medium).unwrap(), "medium");
+    assert_eq!(Quality::Medium.label(), "Medium Quality");
+    assert_eq!(
+      Quality::parameter_data_type(),
+      ParameterDataType::select_with_labels(
+        &["Low", "Medium", "High"],
+        &["Low", "Medium Quality", "High"]
+      )
+    );
+    let mut quality = Quality::Low;
+    quality.set_param("Best".into()).unwrap();
+    assert_eq!(quality, Quality::High);
+  }
+
+  #[test]
+  fn enum_aliases() {
+    let mut node = TestNode::new();
+    node.set_param("Op", "Subtract".into()).unwrap();
+    assert_eq!(node.get_param("Op").unwrap(), ParameterValue::from("Sub"));
+    node.set_param("Op", "Add".into()).unwrap();
+    node.set_param("Op", "Minus".into()).unwrap();
+    assert_eq!(node.get_param("Op").unwrap(), ParameterValue::from("Sub"));
+    assert!(node.set_param("Op", "Mul".into()).is_err());
+
+    // Old files using the renamed variant still load.
+    let mut json = serde_json::to_value(&node).unwrap();
+    json["op"] = "Subtract".into();
+    let node: TestNode = serde_json::from_value(json).unwrap();
+    assert_eq!(node.get_param("Op").unwrap(), ParameterValue::from("Sub"));
+    // Aliases aren't shown in the ui.
+    let param = node.def().get_parameter("Op").expect("Op param");
+    assert_eq!(param.aliases, ["Subtract", "Minus"]);
+    assert!(param.check("Minus".into()).is_ok());
+    assert!(param.check("Mul".into()).is_err());
+    assert_eq!(
+      param.param_type,
+      ParameterDataType::select_with_labels(&["Add", "Sub"], &["Add (+)", "Sub"])
+    );
+  }
+
+  #[test]
+  fn param_range() {
+    let mut node = TestNode::new();
+    let def = node.def();
+    assert_eq!(
+      def.get_parameter("Amount").and_then(|p| p.range()),
+      Some(ParameterRange::new(0.0, 1.0))
+    );
+    assert_eq!(def.get_parameter("Param").and_then(|p| p.range()), None);
+
+    // Out of range values are rejected and the old value is kept.
+    node.set_param("Amount", 0.25.into()).unwrap();
+    let err = node.set_param("Amount", 2.0.into()).unwrap_err();
+    assert!(err.to_string().contains("out of range"), "{err}");
+    assert_eq!(node.amount, 0.25);
+    // Wrong types are rejected.
+    assert!(node.set_param("Amount", Vec2::ONE.into()).is_err());
+
+    // Clamped parameters are clamped per component.
+    node
+      .set_param("Offset", Vec2::new(3.0, -0.5).into())
+      .unwrap();
+    assert_eq!(node.offset, Vec2::new(1.0, -0.5));
+    node
+      .set_param("Offset", Vec2::new(-5.0, 0.0).into())
+      .unwrap();
+    assert_eq!(node.offset, Vec2::new(-1.0, 0.0));
+  }
 }
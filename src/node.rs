@@ -19,6 +19,8 @@ pub const NAMESPACE_NODE_IMPL: Uuid = uuid::uuid!("9dee91a8-5af8-11ee-948b-5364d
 pub struct NodeConcreteType {
   /// The minimum size of connected Vector/Matrix.  Scalars are not counted.
   pub min: Option<DynamicSize>,
+  /// The data type of the connected scalars.  `F32` if they have different types.
+  pub scalar: Option<DataType>,
   pub scalars: usize,
   pub vectors: usize,
   pub matrixes: usize,
@@ -48,6 +50,26 @@ impl NodeConcreteType {
     }
   }
 
+  /// Resolve the concrete type of a dynamic output.  Returns `None` for non-dynamic types.
+  pub fn resolve_output_type(&self, dt: DataType) -> Option<DataType> {
+    match dt {
+      DataType::Dynamic => self.data_type(),
+      DataType::DynamicVector => match self.min {
+        Some(DynamicSize::D2) => Some(DataType::Vec2),
+        Some(DynamicSize::D3) => Some(DataType::Vec3),
+        Some(DynamicSize::D4) => Some(DataType::Vec4),
+        _ => Some(DataType::F32),
+      },
+      DataType::DynamicMatrix => match self.min {
+        Some(DynamicSize::D2) => Some(DataType::Mat2),
+        Some(DynamicSize::D3) => Some(DataType::Mat3),
+        Some(DynamicSize::D4) => Some(DataType::Mat4),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
   pub fn convert(&self, value: &mut CompiledValue) -> Result<()> {
     if let Some(min) = self.min {
       let (vec_dt, mat_dt) = match min {
@@ -69,11 +91,37 @@ impl NodeConcreteType {
     }
   }
 
+  /// Convert an evaluated value.  The CPU version of `convert`.
+  pub fn convert_value(&self, value: Value) -> Result<Value> {
+    if let Some(min) = self.min {
+      let (vec_dt, mat_dt) = match min {
+        DynamicSize::D3 => (DataType::Vec3, DataType::Mat3),
+        DynamicSize::D4 => (DataType::Vec4, DataType::Mat4),
+        _ => (DataType::Vec2, DataType::Mat2),
+      };
+      let dt = value.data_type();
+      match dt.class() {
+        DataTypeClass::Scalar => value.convert(vec_dt),
+        DataTypeClass::Vector => value.convert(vec_dt),
+        DataTypeClass::Matrix => value.convert(mat_dt),
+        class => Err(anyhow!(
+          "Unsupported data type conversion: class={class:?}, dt={dt:?}"
+        )),
+      }
+    } else {
+      Ok(value)
+    }
+  }
+
   pub fn add_input_type(&mut self, dt: DataType) {
     let min = self.min.unwrap_or(DynamicSize::D4).len();
     match dt {
       DataType::I32 | DataType::U32 | DataType::F32 => {
         self.scalars += 1;
+        self.scalar = match self.scalar {
+          Some(scalar) if scalar != dt => Some(DataType::F32),
+          _ => Some(dt),
+        };
         // Don't update the `min` for Scalars.
       }
       DataType::Vec2 => {
@@ -115,7 +163,9 @@ impl NodeConcreteType {
   }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub struct InputId {
   pub node: NodeId,
   pub idx: u32,
@@ -135,7 +185,9 @@ impl InputId {
   }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[derive(
+  Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
 pub struct OutputId {
   pub node: NodeId,
   pub idx: u32,
@@ -166,6 +218,46 @@ pub trait NodeImpl: fmt::Debug + erased_serde::Serialize + Send + Sync {
     false
   }
 
+  /// Returns false if `eval` depends on more than the node's inputs and parameters.
+  /// The outputs of pure nodes are cached by `NodeGraphExecution`.
+  fn is_pure(&self) -> bool {
+    true
+  }
+
+  /// Returns the subgraph if this is a `SubGraphNode`.
+  fn as_subgraph(&self) -> Option<&SubGraphNode> {
+    None
+  }
+
+  /// Returns the property node if this is a `PropertyNode`.
+  fn as_property(&self) -> Option<&crate::nodes::basic::PropertyNode> {
+    None
+  }
+
+  fn as_property_mut(&mut self) -> Option<&mut crate::nodes::basic::PropertyNode> {
+    None
+  }
+
+  /// Called when the node gets a new id.  Nodes containing other nodes must give them
+  /// new ids too, otherwise copies of the node share their compiled and cached values.
+  fn renew_ids(&mut self) {}
+
+  /// Resize the node to fit its contents.  Defaults to `NodeDefinition::auto_size`.
+  fn auto_size(&self) -> bool {
+    self.def().auto_size
+  }
+
+  /// Resolve the concrete type of output `idx`.  Nodes whose output type depends on
+  /// their parameters (not just their dynamic inputs) need to override this.
+  fn output_concrete_type(
+    &self,
+    _idx: u32,
+    concrete_type: &NodeConcreteType,
+    dt: DataType,
+  ) -> Option<DataType> {
+    concrete_type.resolve_output_type(dt)
+  }
+
   fn get_input_idx(&self, key: &InputKey) -> Result<u32> {
     match key {
       InputKey::Idx(idx) => Ok(*idx),
@@ -173,6 +265,12 @@ pub trait NodeImpl: fmt::Debug + erased_serde::Serialize + Send + Sync {
     }
   }
 
+  /// The number of inputs.  Nodes with a variadic input group can have more inputs
+  /// than their definition.
+  fn input_count(&self) -> usize {
+    self.def().inputs.len()
+  }
+
   fn get_node_input(&self, _idx: &InputKey) -> Result<Input> {
     Err(anyhow!("This node doesn't support `get_input`"))
   }
@@ -198,6 +296,24 @@ pub trait NodeImpl: fmt::Debug + erased_serde::Serialize + Send + Sync {
     Err(anyhow!("This node doesn't support `eval`."))
   }
 
+  /// Evaluate a value to preview in the editor.  Used by output nodes to show a swatch.
+  fn preview(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution) -> Option<Value> {
+    None
+  }
+
+  /// Evaluate one of the node's outputs.  Nodes with multiple outputs need to override this.
+  fn eval_output(
+    &self,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+    id: OutputId,
+  ) -> Result<Value> {
+    match id.idx {
+      0 => self.eval(graph, execution, id.node),
+      idx => Err(anyhow!("This node doesn't support `eval` of output {idx}.")),
+    }
+  }
+
   fn compile(
     &self,
     _graph: &NodeGraph,
@@ -267,6 +383,10 @@ pub trait NodeImpl: fmt::Debug + erased_serde::Serialize + Send + Sync {
             updated = true;
           }
         }
+        if let Some(value) = NodeGraphMeta::get(ui).and_then(|g| g.preview(id)) {
+          ui.separator();
+          value.swatch_ui(ui, 48.0 * zoom);
+        }
       });
     }
     updated
@@ -341,6 +461,24 @@ pub struct LoadNodeState {
   pub node_type: Uuid,
   pub node: serde_json::Value,
   pub area: emath::Rect,
+  #[serde(default)]
+  pub locked: bool,
+}
+
+/// Nodes are loaded from a `serde_json::Value`, so for non human-readable formats
+/// serialize the node state as a JSON value to keep it loadable.
+#[allow(clippy::borrowed_box)]
+fn serialize_node_state<S>(node: &Box<dyn NodeImpl>, serializer: S) -> Result<S::Ok, S::Error>
+where
+  S: Serializer,
+{
+  if serializer.is_human_readable() {
+    node.serialize(serializer)
+  } else {
+    serde_json::to_value(node)
+      .map_err(serde::ser::Error::custom)?
+      .serialize(serializer)
+  }
 }
 
 #[derive(Clone, Debug, serde::Serialize)]
@@ -349,8 +487,15 @@ pub struct Node {
   pub group_id: NodeGroupId,
   pub name: String,
   node_type: Uuid,
+  #[serde(serialize_with = "serialize_node_state")]
   node: Box<dyn NodeImpl>,
   pub area: emath::Rect,
+  /// Locked nodes can't be moved or edited.
+  #[serde(default)]
+  locked: bool,
+  /// The size of a fixed size node was set, either by fitting its contents or loading it.
+  #[serde(skip)]
+  sized: bool,
   #[serde(skip)]
   pub updated: bool,
 }
@@ -382,10 +527,29 @@ impl Node {
       node_type: def.id,
       node: def.new_node()?,
       area: emath::Rect::from_min_size([0., 0.].into(), [10., 10.].into()),
+      locked: false,
+      sized: false,
       updated: true,
     })
   }
 
+  /// Create a node from a node implementation.
+  pub fn from_impl(node: Box<dyn NodeImpl>) -> Self {
+    let def = node.def();
+    let (name, node_type) = (def.name.clone(), def.id);
+    Self {
+      id: Uuid::new_v4(),
+      group_id: Uuid::nil(),
+      name,
+      node_type,
+      node,
+      area: emath::Rect::from_min_size([0., 0.].into(), [10., 10.].into()),
+      locked: false,
+      sized: false,
+      updated: true,
+    }
+  }
+
   pub fn load(def: &NodeDefinition, data: LoadNodeState) -> Result<Self> {
     Ok(Self {
       id: data.id,
@@ -394,16 +558,56 @@ impl Node {
       node_type: data.node_type,
       node: def.load_node(data.node)?,
       area: data.area,
+      locked: data.locked,
+      sized: true,
       updated: true,
     })
   }
 
+  pub fn def(&self) -> &NodeDefinition {
+    self.node.def()
+  }
+
+  /// The name shown in the title bar.  Defaults to the node type's name.
+  pub fn display_name(&self) -> &str {
+    &self.name
+  }
+
+  /// Rename the node.  An empty name resets it to the node type's name.
+  pub fn set_display_name(&mut self, name: impl Into<String>) {
+    let name = name.into();
+    if name.is_empty() {
+      self.reset_display_name();
+    } else {
+      self.name = name;
+    }
+  }
+
+  /// Reset the name to the node type's name.
+  pub fn reset_display_name(&mut self) {
+    self.name = self.def().name.clone();
+  }
+
+  /// Returns true if the node was renamed.
+  pub fn is_renamed(&self) -> bool {
+    self.name != self.def().name
+  }
+
+  pub fn is_locked(&self) -> bool {
+    self.locked
+  }
+
+  pub fn set_locked(&mut self, locked: bool) {
+    self.locked = locked;
+  }
+
   pub fn set_position(&mut self, position: emath::Vec2) {
     self.area = emath::Rect::from_min_size(position.to_pos2(), self.area.size());
   }
 
   pub(crate) fn new_id(&mut self) {
     self.id = Uuid::new_v4();
+    self.node.renew_ids();
   }
 
   /// Clone node with a new uuid.
@@ -422,6 +626,19 @@ impl Node {
     self.node.eval(graph, execution, id)
   }
 
+  pub fn eval_output(
+    &self,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+    id: OutputId,
+  ) -> Result<Value> {
+    self.node.eval_output(graph, execution, id)
+  }
+
+  pub fn preview(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution) -> Option<Value> {
+    self.node.preview(graph, execution)
+  }
+
   pub fn compile(
     &self,
     graph: &NodeGraph,
@@ -435,6 +652,71 @@ impl Node {
     self.node.cache_output()
   }
 
+  pub fn is_pure(&self) -> bool {
+    self.node.is_pure()
+  }
+
+  pub fn input_count(&self) -> usize {
+    self.node.input_count()
+  }
+
+  pub fn as_subgraph(&self) -> Option<&SubGraphNode> {
+    self.node.as_subgraph()
+  }
+
+  pub fn as_property(&self) -> Option<&crate::nodes::basic::PropertyNode> {
+    self.node.as_property()
+  }
+
+  pub fn as_property_mut(&mut self) -> Option<&mut crate::nodes::basic::PropertyNode> {
+    self.node.as_property_mut()
+  }
+
+  /// Point a property node at the new id of its property.
+  pub(crate) fn remap_property(&mut self, ids: &std::collections::HashMap<Uuid, Uuid>) {
+    if let Some(node) = self.node.as_property_mut() {
+      if let Some(id) = ids.get(&node.property()) {
+        node.set_property(*id);
+      }
+    }
+  }
+
+  /// Returns true if the node is resized to fit its contents.  Other nodes have a
+  /// fixed size, which is fitted to the contents when the node is first shown.
+  pub fn auto_size(&self) -> bool {
+    self.node.auto_size()
+  }
+
+  pub fn output_concrete_type(
+    &self,
+    idx: u32,
+    concrete_type: &NodeConcreteType,
+    dt: DataType,
+  ) -> Option<DataType> {
+    self.node.output_concrete_type(idx, concrete_type, dt)
+  }
+
+  /// The serialized node state, loadable with `NodeDefinition::load_node`.
+  pub fn node_state(&self) -> Result<serde_json::Value> {
+    Ok(serde_json::to_value(&self.node)?)
+  }
+
+  /// Snapshot the node's inputs and parameters.  Useful for golden tests of node state.
+  ///
+  /// Same as `node_state`, use `restore` to load it back.
+  pub fn snapshot(&self) -> Result<serde_json::Value> {
+    self.node_state()
+  }
+
+  /// Restore the node's state from a `snapshot`.
+  ///
+  /// Only the node is updated, connections stored in the graph are not changed.
+  pub fn restore(&mut self, state: serde_json::Value) -> Result<()> {
+    self.node = self.def().load_node(state)?;
+    self.updated = true;
+    Ok(())
+  }
+
   pub fn get_input_idx(&self, idx: &InputKey) -> Result<u32> {
     self.node.get_input_idx(idx)
   }
@@ -448,6 +730,15 @@ impl Node {
     self.node.set_node_input(&idx.into(), value)
   }
 
+  pub fn get_param(&self, name: &str) -> Result<ParameterValue> {
+    self.node.get_param(name)
+  }
+
+  pub fn set_param(&mut self, name: &str, value: ParameterValue) -> Result<()> {
+    self.updated = true;
+    self.node.set_param(name, value)
+  }
+
   #[cfg(feature = "egui")]
   pub fn details_ui(&mut self, ui: &mut egui::Ui, id: NodeId) -> bool {
     self.node.details_ui(ui, id)
@@ -461,7 +752,7 @@ impl NodeFrame for Node {
   }
 
   fn set_title(&mut self, title: String) {
-    self.name = title;
+    self.set_display_name(title);
   }
 
   fn take_updated(&mut self, state: &mut NodeFrameState) -> bool {
@@ -476,14 +767,30 @@ impl NodeFrame for Node {
 
   fn set_rect(&mut self, rect: emath::Rect) {
     self.area = rect;
+    self.sized = true;
   }
 
   fn auto_size(&self) -> bool {
-    true
+    self.node.auto_size() || !self.sized
   }
 
   fn resizable(&self) -> bool {
-    false
+    !self.node.auto_size()
+  }
+
+  /// Keep all socket rows visible.
+  fn min_size(&self, node_style: &NodeStyle) -> emath::Vec2 {
+    let rows = self.input_count().max(self.def().outputs.len());
+    let height = NODE_SOCKET_FIRST_ROW + rows as f32 * NODE_SOCKET_ROW_HEIGHT;
+    emath::vec2(node_style.node_min_size.x / node_style.zoom, height)
+  }
+
+  fn movable(&self) -> bool {
+    !self.locked
+  }
+
+  fn is_locked(&self) -> bool {
+    self.locked
   }
 
   fn contents_ui(&mut self, ui: &mut egui::Ui, node_style: NodeStyle) {
@@ -491,9 +798,11 @@ impl NodeFrame for Node {
       .fill(egui::Color32::from_gray(63))
       .show(ui, |ui| {
         ui.set_min_width(node_style.node_min_size.x);
-        if self.node.node_ui(ui, self.id) {
-          self.updated = true;
-        }
+        ui.add_enabled_ui(!self.locked, |ui| {
+          if self.node.node_ui(ui, self.id) {
+            self.updated = true;
+          }
+        });
       });
   }
 
@@ -508,7 +817,7 @@ impl NodeFrame for Node {
     let mut action = None;
     if resp.clicked() {
       action = Some(NodeAction::Clicked);
-    } else if resp.dragged() {
+    } else if resp.dragged() && !frame.locked {
       if frame.is_dragging() {
         action = Some(NodeAction::Dragged(resp.drag_delta()));
       } else {
@@ -516,10 +825,27 @@ impl NodeFrame for Node {
       }
     }
     resp.context_menu(|ui| {
+      if ui.checkbox(&mut self.locked, "Locked").changed() {
+        self.updated = true;
+      }
+      ui.separator();
       if ui.button("Delete").clicked() {
         action = Some(NodeAction::Delete(false));
         ui.close_menu();
       }
+      if self.is_renamed() && ui.button("Reset name").clicked() {
+        self.reset_display_name();
+        self.updated = true;
+        ui.close_menu();
+      }
+      if !frame.fragment_output && ui.button("Set as graph output").clicked() {
+        action = Some(NodeAction::SetOutput);
+        ui.close_menu();
+      }
+      if self.as_subgraph().is_some() && ui.button("Expand subgraph").clicked() {
+        action = Some(NodeAction::Expand);
+        ui.close_menu();
+      }
       if !self.group_id.is_nil() {
         if ui.button("Remove from group").clicked() {
           action = Some(NodeAction::LeaveGroup(self.group_id));
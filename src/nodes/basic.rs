@@ -1,6 +1,7 @@
 use glam::{Vec2, Vec3, Vec4};
 
 use anyhow::Result;
+use uuid::Uuid;
 
 use crate::*;
 
@@ -27,6 +28,10 @@ impl_node! {
     }
 
     impl NodeImpl for BoolNode {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(Value::F32(if self.value { 1.0 } else { 0.0 }))
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = if self.value {
           "1.0"
@@ -39,6 +44,42 @@ impl_node! {
   }
 }
 
+impl_node! {
+  mod static_bool_node {
+    NodeInfo {
+      name: "Static Boolean",
+      category: ["Input", "Basic"],
+    }
+
+    /// A boolean compiled as a shader constant.  Nodes like `Branch` only compile the
+    /// code for its value, which is cheaper than a runtime branch for shader variants.
+    #[derive(Default)]
+    pub struct StaticBoolNode {
+      /// Boolean constant.
+      pub value: Param<bool>,
+      /// Output.
+      pub out: Output<f32>,
+    }
+
+    impl StaticBoolNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for StaticBoolNode {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(Value::F32(if self.value { 1.0 } else { 0.0 }))
+      }
+
+      fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let value = Value::F32(if self.value { 1.0 } else { 0.0 });
+        self.out.compile_const(compile, id, "static_bool_node", value)
+      }
+    }
+  }
+}
+
 impl_node! {
   mod color_node {
     NodeInfo {
@@ -61,6 +102,10 @@ impl_node! {
     }
 
     impl NodeImpl for ColorNode {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = self.value.compile()?.to_string();
         self.out.compile(compile, id, "color_node", value, DataType::Vec4)
@@ -91,6 +136,10 @@ impl_node! {
     }
 
     impl NodeImpl for FloatNode {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = self.value.compile()?.to_string();
         self.out.compile(compile, id, "float_node", value, DataType::F32)
@@ -121,6 +170,10 @@ impl_node! {
     }
 
     impl NodeImpl for Vec2Node {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = self.value.compile()?.to_string();
         self.out.compile(compile, id, "vector2_node", value, DataType::Vec2)
@@ -151,6 +204,10 @@ impl_node! {
     }
 
     impl NodeImpl for Vec3Node {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = self.value.compile()?.to_string();
         self.out.compile(compile, id, "vector3_node", value, DataType::Vec3)
@@ -181,6 +238,10 @@ impl_node! {
     }
 
     impl NodeImpl for Vec4Node {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
       fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let value = self.value.compile()?.to_string();
         self.out.compile(compile, id, "vector4_node", value, DataType::Vec4)
@@ -188,3 +249,103 @@ impl_node! {
     }
   }
 }
+
+impl_node! {
+  mod float_array_node {
+    NodeInfo {
+      name: "Float Array",
+      category: ["Input", "Basic"],
+    }
+
+    /// A constant array of floats.  Useful for LUTs and kernel weights.
+    #[derive(Default)]
+    pub struct FloatArrayNode {
+      pub value: Param<F32Array>,
+      /// Output.
+      pub out: Output<F32Array>,
+    }
+
+    impl FloatArrayNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for FloatArrayNode {
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(self.value.to_value())
+      }
+
+      fn compile(&self, _graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        // A constant, so the array length is known by the nodes using it.
+        self.out.compile_const(compile, id, "float_array_node", self.value.to_value())
+      }
+    }
+  }
+}
+
+impl_node! {
+  mod property_node {
+    NodeInfo {
+      name: "Property",
+      category: ["Input", "Basic"],
+      // Created by promoting an input to a property, hide it from the node finder.
+      deprecated: true,
+    }
+
+    /// The value of a graph property.
+    pub struct PropertyNode {
+      /// Graph property id.
+      property: Uuid,
+      /// Output.
+      pub out: Output<Dynamic>,
+    }
+
+    #[allow(clippy::new_without_default)]
+    impl PropertyNode {
+      pub fn new() -> Self {
+        Self {
+          property: Uuid::nil(),
+          out: Default::default(),
+        }
+      }
+
+      /// Create a node that outputs the value of `property`.
+      pub fn with_property(property: &NodeGraphProperty) -> Self {
+        Self {
+          property: property.id(),
+          ..Self::new()
+        }
+      }
+
+      pub fn property(&self) -> Uuid {
+        self.property
+      }
+
+      /// Use another graph property.  Used when the properties get new ids.
+      pub fn set_property(&mut self, property: Uuid) {
+        self.property = property;
+      }
+    }
+
+    impl NodeImpl for PropertyNode {
+      fn as_property(&self) -> Option<&PropertyNode> {
+        Some(self)
+      }
+
+      fn as_property_mut(&mut self) -> Option<&mut PropertyNode> {
+        Some(self)
+      }
+
+      fn eval(&self, graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Ok(graph.property(self.property)?.value().clone())
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let value = graph.property(self.property)?.value();
+        let dt = value.data_type();
+        self.out.compile(compile, id, "property_node", value.compile()?.to_string(), dt)
+      }
+    }
+  }
+}
@@ -32,6 +32,21 @@ impl_node! {
     }
 
     impl NodeImpl for SplitNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, id: NodeId) -> Result<Value> {
+        self.eval_output(graph, execution, OutputId::new(id, 0))
+      }
+
+      fn eval_output(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, id: OutputId) -> Result<Value> {
+        let input = self.eval_inputs(graph, execution)?;
+        let comps = match input.data_type() {
+          DataType::F32 | DataType::Vec2 | DataType::Vec3 | DataType::Vec4 => input.components()?,
+          _ => {
+            return Err(anyhow::anyhow!("Unsupported input data type: {input:?}"));
+          }
+        };
+        Ok(Value::F32(comps.get(id.idx as usize).copied().unwrap_or_default()))
+      }
+
       fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let input = self.resolve_inputs(graph, compile)?;
         let (r, g, b, a) = match input.dt {
@@ -86,6 +101,16 @@ impl_node! {
     }
 
     impl NodeImpl for SwizzleNode {
+      fn output_concrete_type(&self, _idx: u32, concrete_type: &NodeConcreteType, dt: DataType) -> Option<DataType> {
+        let scalar = concrete_type.scalar.unwrap_or(DataType::F32);
+        self.swizzle.output_type(scalar).or_else(|| concrete_type.resolve_output_type(dt))
+      }
+
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let input = self.eval_inputs(graph, execution)?;
+        self.swizzle.eval(input)
+      }
+
       fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let input = self.resolve_inputs(graph, compile)?;
         let out = self.swizzle.compile(input)?;
@@ -129,6 +154,21 @@ impl_node! {
     }
 
     impl NodeImpl for CombineNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, id: NodeId) -> Result<Value> {
+        self.eval_output(graph, execution, OutputId::new(id, 0))
+      }
+
+      fn eval_output(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, id: OutputId) -> Result<Value> {
+        let (r, g, b, a) = self.eval_inputs(graph, execution)?;
+        let (r, g, b, a) = (r.components()?[0], g.components()?[0], b.components()?[0], a.components()?[0]);
+        match id.idx {
+          0 => Ok(Value::Vec4(Vec4::new(r, g, b, a))),
+          1 => Ok(Value::Vec3(Vec3::new(r, g, b))),
+          2 => Ok(Value::Vec2(Vec2::new(r, g))),
+          idx => Err(anyhow::anyhow!("Invalid output: {idx}")),
+        }
+      }
+
       fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let (r, g, b, a) = self.resolve_inputs(graph, compile)?;
         self.rgba.compile(compile, id, "combine_node_rgba", format!("vec4<f32>({r}, {g}, {b}, {a})"), DataType::Vec4)?;
@@ -139,3 +179,145 @@ impl_node! {
     }
   }
 }
+
+impl_node! {
+  mod convert_node {
+    NodeInfo {
+      name: "Convert",
+      category: ["Channel"],
+    }
+
+    /// Target data type of the `Convert` node.
+    pub enum ConvertType {
+      #[label = "Float"]
+      F32,
+      #[label = "Vector 2"]
+      Vec2,
+      #[label = "Vector 3"]
+      Vec3,
+      #[label = "Vector 4"]
+      Vec4,
+      #[label = "Int"]
+      I32,
+      #[label = "Unsigned Int"]
+      U32,
+      #[label = "Half"]
+      F16,
+    }
+
+    /// Convert the input value to another data type.
+    #[derive(Default)]
+    pub struct ConvertNode {
+      /// Input value.
+      pub input: Input<Dynamic>,
+      /// Target data type.
+      pub target: Param<ConvertType>,
+      /// Output value.
+      pub out: Output<Dynamic>,
+    }
+
+    impl ConvertNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+
+      /// Create a node that converts to `dt`.  Returns `None` for unsupported data types.
+      pub fn with_target(dt: DataType) -> Option<Self> {
+        let target = match dt {
+          DataType::F32 => ConvertType::F32,
+          DataType::Vec2 => ConvertType::Vec2,
+          DataType::Vec3 => ConvertType::Vec3,
+          DataType::Vec4 => ConvertType::Vec4,
+          DataType::I32 => ConvertType::I32,
+          DataType::U32 => ConvertType::U32,
+          DataType::F16 => ConvertType::F16,
+          _ => return None,
+        };
+        Some(Self {
+          target,
+          ..Default::default()
+        })
+      }
+
+      pub fn target_type(&self) -> DataType {
+        match self.target {
+          ConvertType::F32 => DataType::F32,
+          ConvertType::Vec2 => DataType::Vec2,
+          ConvertType::Vec3 => DataType::Vec3,
+          ConvertType::Vec4 => DataType::Vec4,
+          ConvertType::I32 => DataType::I32,
+          ConvertType::U32 => DataType::U32,
+          ConvertType::F16 => DataType::F16,
+        }
+      }
+    }
+
+    impl NodeImpl for ConvertNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let input = self.eval_inputs(graph, execution)?;
+        input.convert(self.target_type())
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let mut input = self.resolve_inputs(graph, compile)?;
+        let dt = self.target_type();
+        input.convert(dt)?;
+        self.out.compile(compile, id, "convert_node", input.value, dt)
+      }
+    }
+  }
+}
+
+impl_node! {
+  mod array_index_node {
+    NodeInfo {
+      name: "Array Index",
+      category: ["Channel"],
+    }
+
+    /// Get a value from a float array.  The index is clamped to the array.
+    #[derive(Default)]
+    pub struct ArrayIndexNode {
+      /// Input array.
+      pub array: Input<F32Array>,
+      /// Index.
+      pub index: Input<i32>,
+      /// Output.
+      pub out: Output<f32>,
+    }
+
+    impl ArrayIndexNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for ArrayIndexNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let (array, index) = self.eval_inputs(graph, execution)?;
+        let index = index.components()?[0] as i64;
+        match array {
+          Value::F32Array(array) => Ok(Value::F32(array.get(index))),
+          _ => Err(anyhow::anyhow!("Expected a F32Array got: {array:?}")),
+        }
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let (array, index) = self.resolve_inputs(graph, compile)?;
+        // Clamp the index like `eval`.  Constant indices must be in bounds, they are a
+        // shader-creation error otherwise.
+        let index = match compile.const_value(&array) {
+          Some(Value::F32Array(values)) if values.0.is_empty() => {
+            return Err(anyhow::anyhow!("Empty arrays can't be indexed"));
+          }
+          Some(Value::F32Array(values)) => format!("clamp({index}, 0, {})", values.0.len() as i32 - 1),
+          // Unknown length, dynamic indices are clamped by the shader's bounds checks.
+          _ => index.to_string(),
+        };
+        // WGSL only allows dynamic indexing of arrays stored in a `var`.
+        let array = compile.add_var("array_index_node_array", array.value, DataType::F32Array)?;
+        self.out.compile(compile, id, "array_index_node", format!("{array}[{index}]"), DataType::F32)
+      }
+    }
+  }
+}
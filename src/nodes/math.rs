@@ -4,7 +4,7 @@ use crate::*;
 
 #[macro_export]
 macro_rules! impl_dyn_vec_trinary_node {
-  ( $mod_name:ident, $ty_name:ident, $docs:expr, $op:expr ) => {
+  ( $mod_name:ident, $ty_name:ident, $docs:expr, $op:expr, $eval:expr ) => {
     $crate::impl_dyn_vec_trinary_node!(
       $mod_name,
       $ty_name,
@@ -15,10 +15,11 @@ macro_rules! impl_dyn_vec_trinary_node {
       "Input `B`.",
       c,
       "Input `C`.",
-      $op
+      $op,
+      $eval
     );
   };
-  ( $mod_name:ident, $ty_name:ident, $docs:expr, $a:ident, $a_doc:expr, $b:ident, $b_doc:expr, $c:ident, $c_doc:expr, $op:expr ) => {
+  ( $mod_name:ident, $ty_name:ident, $docs:expr, $a:ident, $a_doc:expr, $b:ident, $b_doc:expr, $c:ident, $c_doc:expr, $op:expr, $eval:expr ) => {
     $crate::impl_node! {
       mod $mod_name {
         NodeInfo {
@@ -46,6 +47,16 @@ macro_rules! impl_dyn_vec_trinary_node {
         }
 
         impl NodeImpl for $ty_name {
+          fn eval(
+            &self,
+            graph: &NodeGraph,
+            execution: &mut NodeGraphExecution,
+            _id: NodeId,
+          ) -> Result<Value> {
+            let (a, b, c) = self.eval_inputs(graph, execution)?;
+            a.zip_map3(&b, &c, $eval)
+          }
+
           fn compile(
             &self,
             graph: &NodeGraph,
@@ -71,7 +82,8 @@ impl_dyn_vec_trinary_node!(
   "Input B",
   t,
   "Input T",
-  "mix({}, {}, {})"
+  "mix({}, {}, {})",
+  |a, b, t| a + (b - a) * t
 );
 impl_dyn_vec_trinary_node!(
   clamp_node,
@@ -83,12 +95,13 @@ impl_dyn_vec_trinary_node!(
   "Minimum value",
   max,
   "Maximum value",
-  "clamp({}, {}, {})"
+  "clamp({}, {}, {})",
+  |x, min, max| x.max(min).min(max)
 );
 
 #[macro_export]
 macro_rules! impl_dyn_vec_binary_node {
-  ( $mod_name:ident, $ty_name:ident, $name:expr, $docs:expr, $op:expr ) => {
+  ( $mod_name:ident, $ty_name:ident, $name:expr, $docs:expr, $op:expr, $eval:expr ) => {
     $crate::impl_node! {
       mod $mod_name {
         NodeInfo {
@@ -114,6 +127,16 @@ macro_rules! impl_dyn_vec_binary_node {
         }
 
         impl NodeImpl for $ty_name {
+          fn eval(
+            &self,
+            graph: &NodeGraph,
+            execution: &mut NodeGraphExecution,
+            _id: NodeId,
+          ) -> Result<Value> {
+            let (a, b) = self.eval_inputs(graph, execution)?;
+            a.zip_map(&b, $eval)
+          }
+
           fn compile(
             &self,
             graph: &NodeGraph,
@@ -130,46 +153,51 @@ macro_rules! impl_dyn_vec_binary_node {
   };
 }
 
-impl_dyn_vec_binary_node!(add_node, AddNode, "Add", "Add two vectors.", "({} + {})");
+impl_dyn_vec_binary_node!(add_node, AddNode, "Add", "Add two vectors.", "({} + {})", |a, b| a + b);
 impl_dyn_vec_binary_node!(
   subtract_node,
   SubtractNode,
   "Subtract",
   "Subtract two vectors.",
-  "({} - {})"
+  "({} - {})",
+  |a, b| a - b
 );
 impl_dyn_vec_binary_node!(
   divide_node,
   DivideNode,
   "Divide",
   "Divide two vectors.",
-  "({} / {})"
+  "({} / {})",
+  |a, b| a / b
 );
 impl_dyn_vec_binary_node!(
   power_node,
   PowerNode,
   "Power",
   "Output input `a` to the power of input `b`.",
-  "pow({}, {})"
+  "pow({}, {})",
+  f32::powf
 );
 impl_dyn_vec_binary_node!(
   min_node,
   MinNode,
   "Minimum",
   "Output the smallest of two inputs.",
-  "min({}, {})"
+  "min({}, {})",
+  f32::min
 );
 impl_dyn_vec_binary_node!(
   max_node,
   MaxNode,
   "Maximum",
   "Output the largest of two inputs.",
-  "max({}, {})"
+  "max({}, {})",
+  f32::max
 );
 
 #[macro_export]
 macro_rules! impl_dyn_vec_unary_node {
-  ( $mod_name:ident, $ty_name:ident, $name:expr, $desp:expr, $op:expr ) => {
+  ( $mod_name:ident, $ty_name:ident, $name:expr, $desp:expr, $op:expr, $eval:expr ) => {
     $crate::impl_node! {
       mod $mod_name {
         NodeInfo {
@@ -194,6 +222,16 @@ macro_rules! impl_dyn_vec_unary_node {
         }
 
         impl NodeImpl for $ty_name {
+          fn eval(
+            &self,
+            graph: &NodeGraph,
+            execution: &mut NodeGraphExecution,
+            _id: NodeId,
+          ) -> Result<Value> {
+            let a = self.eval_inputs(graph, execution)?;
+            a.map($eval)
+          }
+
           fn compile(
             &self,
             graph: &NodeGraph,
@@ -214,49 +252,56 @@ impl_dyn_vec_unary_node!(
   SquareRootNode,
   "Square Root",
   "Output the square root of input `a`.",
-  "sqrt({})"
+  "sqrt({})",
+  f32::sqrt
 );
 impl_dyn_vec_unary_node!(
   round_node,
   RoundNode,
   "Round",
   "Round input `a` to the nearest integer.",
-  "round({})"
+  "round({})",
+  f32::round_ties_even
 );
 impl_dyn_vec_unary_node!(
   floor_node,
   FloorNode,
   "Floor",
   "Floor input `a`.",
-  "floor({})"
+  "floor({})",
+  f32::floor
 );
 impl_dyn_vec_unary_node!(
   fract_node,
   FractionNode,
   "Fraction",
   "Fraction input `a`.",
-  "fract({})"
+  "fract({})",
+  |a| a - a.floor()
 );
 impl_dyn_vec_unary_node!(
   ceiling_node,
   CeilingNode,
   "Ceiling",
   "Ceiling input `a`.",
-  "ceil({})"
+  "ceil({})",
+  f32::ceil
 );
 impl_dyn_vec_unary_node!(
   truncate_node,
   TruncateNode,
   "Truncate",
   "Truncate input `a`.",
-  "trunc({})"
+  "trunc({})",
+  f32::trunc
 );
 impl_dyn_vec_unary_node!(
   absolute_node,
   AbsoluteNode,
   "Absolute",
   "Absolute input `a`.",
-  "abs({})"
+  "abs({})",
+  f32::abs
 );
 
 impl_node! {
@@ -284,6 +329,15 @@ impl_node! {
     }
 
     impl NodeImpl for MultiplyNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let (a, b) = self.eval_inputs(graph, execution)?;
+        match (a.data_type().class(), b.data_type().class()) {
+          // Re-order so the vector is first.
+          (DataTypeClass::Matrix, DataTypeClass::Vector) => b.mul(&a),
+          _ => a.mul(&b),
+        }
+      }
+
       fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
         let (a, b) = self.resolve_inputs(graph, compile)?;
         let (code, out_dt) = match (a.dt.class(), b.dt.class()) {
@@ -298,3 +352,232 @@ impl_node! {
     }
   }
 }
+
+impl_node! {
+  mod curve_node {
+    NodeInfo {
+      name: "Curve",
+      category: ["Math", "Interpolation"],
+      auto_size: false,
+    }
+
+    /// Remap the input through a response curve.
+    #[derive(Default)]
+    pub struct CurveNode {
+      /// Input.
+      pub input: Input<f32>,
+      /// Response curve.
+      pub curve: Param<Curve>,
+      /// Output.
+      pub out: Output<f32>,
+    }
+
+    impl CurveNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for CurveNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let input = self.eval_inputs(graph, execution)?;
+        Ok(Value::F32(self.curve.eval(input.components()?[0])))
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let input = self.resolve_inputs(graph, compile)?;
+        let out = self.curve.compile(input)?;
+        self.out.compile(compile, id, "curve_node", out.value, out.dt)
+      }
+    }
+  }
+}
+
+impl_node! {
+  mod sum_node {
+    NodeInfo {
+      name: "Sum",
+      category: ["Math", "Basic"],
+    }
+
+    /// Add any number of values.
+    #[derive(Default)]
+    pub struct SumNode {
+      /// Input values.
+      pub values: Inputs<f32>,
+      /// Output.
+      pub out: Output<f32>,
+    }
+
+    impl SumNode {
+      pub fn new() -> Self {
+        Self {
+          values: InputsTyped::new(2),
+          ..Default::default()
+        }
+      }
+    }
+
+    impl NodeImpl for SumNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let mut concrete_type = NodeConcreteType::default();
+        let mut sum = 0.0;
+        for value in self.values.eval_values(&mut concrete_type, graph, execution)? {
+          sum += value.components()?[0];
+        }
+        Ok(Value::F32(sum))
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let values = self.values.compile(graph, compile)?;
+        let code = if values.is_empty() {
+          "0.".to_string()
+        } else {
+          let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+          format!("({})", values.join(" + "))
+        };
+        self.out.compile(compile, id, "sum_node", code, DataType::F32)
+      }
+    }
+  }
+}
+
+impl_node! {
+  mod branch_node {
+    NodeInfo {
+      name: "Branch",
+      category: ["Math", "Basic"],
+    }
+
+    /// Select between two values.
+    ///
+    /// When the predicate is a `Static Boolean` only the selected value is compiled.
+    #[derive(Default)]
+    pub struct BranchNode {
+      /// The predicate, non-zero is true.
+      pub predicate: Input<f32>,
+      /// Value used when the predicate is true.
+      pub on_true: Input<DynamicVector>,
+      /// Value used when the predicate is false.
+      pub on_false: Input<DynamicVector>,
+      /// Output.
+      pub out: Output<DynamicVector>,
+    }
+
+    impl BranchNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for BranchNode {
+      fn eval(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        let (predicate, on_true, on_false) = self.eval_inputs(graph, execution)?;
+        Ok(if predicate.components()?[0] != 0.0 { on_true } else { on_false })
+      }
+
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+        let predicate = self.predicate.compile(graph, compile)?;
+        if let Some(value) = compile.const_value(&predicate) {
+          // Specialize on the constant, the other value isn't compiled.
+          let mut concrete_type = NodeConcreteType::default();
+          let value = if value.components()?[0] != 0.0 {
+            self.on_true.resolve(&mut concrete_type, graph, compile)?
+          } else {
+            self.on_false.resolve(&mut concrete_type, graph, compile)?
+          };
+          return self.out.compile(compile, id, "branch_node", value.value, value.dt);
+        }
+        let (predicate, on_true, on_false) = self.resolve_inputs(graph, compile)?;
+        let code = format!("select({on_false}, {on_true}, {predicate} != 0.0)");
+        self.out.compile(compile, id, "branch_node", code, on_true.dt)
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glam::{Mat2, Vec2, Vec3};
+
+  use super::*;
+
+  fn eval_node(name: &str, inputs: &[(&str, Value)]) -> Result<Value> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let id = graph.add(reg.new_by_name(name)?);
+    for (input, value) in inputs {
+      graph.set_node_input(id, *input, Input::Value(value.clone()))?;
+    }
+    graph.set_output(Some(id));
+    graph.evaluate()
+  }
+
+  #[test]
+  fn eval_math_nodes() -> Result<()> {
+    let v = Value::Vec3(Vec3::new(1., -2., 3.));
+    // Scalar inputs are applied to each component.
+    assert_eq!(
+      eval_node("Add", &[("A", v.clone()), ("B", Value::F32(1.0))])?,
+      Value::Vec3(Vec3::new(2., -1., 4.))
+    );
+    assert_eq!(
+      eval_node("Power", &[("A", Value::F32(2.0)), ("B", Value::F32(3.0))])?,
+      Value::F32(8.0)
+    );
+    assert_eq!(
+      eval_node("Absolute", &[("A", v.clone())])?,
+      Value::Vec3(Vec3::new(1., 2., 3.))
+    );
+    assert_eq!(
+      eval_node(
+        "Clamp",
+        &[
+          ("Input", v.clone()),
+          ("Min", Value::F32(0.0)),
+          ("Max", Value::F32(2.0))
+        ]
+      )?,
+      Value::Vec3(Vec3::new(1., 0., 2.))
+    );
+    assert_eq!(
+      eval_node(
+        "Lerp",
+        &[
+          ("A", Value::F32(1.0)),
+          ("B", Value::F32(3.0)),
+          ("T", Value::F32(0.25))
+        ]
+      )?,
+      Value::F32(1.5)
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn eval_matrix_multiply() -> Result<()> {
+    // Input values can't be matrixes, use properties.
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add_property = |graph: &mut NodeGraph, value: Value| {
+      let property = NodeGraphProperty::new("Value", value);
+      let node = crate::nodes::basic::PropertyNode::with_property(&property);
+      graph.add_property(property);
+      graph.add(Node::from_impl(Box::new(node)))
+    };
+    let m = Mat2::from_cols(Vec2::new(1., 2.), Vec2::new(3., 4.));
+    let mat = add_property(&mut graph, Value::Mat2(m));
+    let identity = add_property(&mut graph, Value::Mat2(Mat2::IDENTITY));
+    let mul = graph.add(reg.new_by_name("Multiply")?);
+    graph.set_output(Some(mul));
+
+    // The vector is moved first, like the shader code.
+    graph.set_node_input(mul, "A", mat.into())?;
+    graph.set_node_input(mul, "B", Vec2::ONE.into())?;
+    assert_eq!(graph.evaluate()?, Value::Vec2(Vec2::new(3., 7.)));
+    graph.set_node_input(mul, "B", identity.into())?;
+    assert_eq!(graph.evaluate()?, Value::Mat2(m));
+    Ok(())
+  }
+}
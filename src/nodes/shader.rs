@@ -131,12 +131,11 @@ impl_node! {
     }
 
     /// Tiling and Offset Node.
-    #[derive(Default)]
     pub struct TilingOffsetNode {
       /// Input UV.
       pub uv: Input<UV>,
       /// The tiling to apply.
-      pub tiling: Input<Vec2>,
+      pub tiling: Input<Vec2> = Vec2::ONE,
       /// The offset to apply.
       pub offset: Input<Vec2>,
       /// Output UV value.
@@ -158,6 +157,24 @@ impl_node! {
   }
 }
 
+/// Append the template imports and bindings, only once even if there are multiple output nodes.
+fn append_shader_header(compile: &mut NodeGraphCompile) -> Result<()> {
+  let template = compile.template();
+  let imports = template.imports.clone();
+  let bindings = template.bindings.clone();
+  let layout = template.binding_layout.clone();
+  if !imports.is_empty() {
+    compile.append_code_once("imports", imports)?;
+  }
+  if !bindings.is_empty() {
+    compile.append_code_once("bindings", bindings)?;
+    for binding in layout {
+      compile.add_binding(binding);
+    }
+  }
+  Ok(())
+}
+
 impl_node! {
   mod fragment_output_node {
     NodeInfo {
@@ -188,97 +205,60 @@ impl_node! {
         self.color.eval(graph, execution).map(|v| v.to_value())
       }
 
-      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, _id: NodeId) -> Result<()> {
-        compile.append_code(
-          "imports",
-          r#"
-#import bevy_pbr::{
-	pbr_fragment::pbr_input_from_standard_material,
-	pbr_functions::alpha_discard,
-	pbr_bindings,
-	mesh_view_bindings::view,
-	mesh_functions,
-	skinning,
-	view_transformations::position_world_to_clip,
-}
-#import bevy_render::instance_index::get_instance_index
-
-#ifdef PREPASS_PIPELINE
-#import bevy_pbr::{
-	prepass_io::{Vertex, VertexOutput, FragmentOutput},
-	pbr_deferred_functions::deferred_output,
-}
-#else
-#import bevy_pbr::{
-	forward_io::{Vertex, VertexOutput, FragmentOutput},
-	pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
-	pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
-}
-#endif
-"#
-          .to_string(),
-        )?;
-        compile.append_code(
-          "bindings",
-          r#"
-struct ShaderGraphMaterialUniform {
-  prop_vec4: vec4<f32>,
-};
+      fn preview(&self, graph: &NodeGraph, execution: &mut NodeGraphExecution) -> Option<Value> {
+        self.color.eval(graph, execution).map(|v| v.to_value()).ok()
+      }
 
-@group(2) @binding(100) var<uniform> material: ShaderGraphMaterialUniform;
-"#
-          .to_string(),
-        )?;
-        let frag_block = compile.push_new_block("fragment");
-        {
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, _id: NodeId) -> Result<()> {
+        append_shader_header(compile)?;
+        let template = compile.template().clone();
+        compile.add_entry_point(&template.fragment_entry, ShaderStage::Fragment);
+        compile.with_block("fragment", |compile| {
+          compile.current_block()?.append(template.fragment_begin.clone());
+          let color = self.resolve_inputs(graph, compile)?;
           let block = compile.current_block()?;
-          block.append(
-            r##"
-@fragment
-fn fragment(
-  v_in: VertexOutput,
-  @builtin(front_facing) is_front: bool,
-) -> FragmentOutput {
-	var in = v_in;
-
-	// get PbrInput from StandardMaterial bindings.
-	var pbr_input = pbr_input_from_standard_material(in, is_front);
-"##
-              .to_string(),
-          );
-        }
-        let color = self.resolve_inputs(graph, compile)?;
-        let block = compile.current_block()?;
-        block.append(format!(r#"
-  // Color from graph input `color`.
-  pbr_input.material.base_color = {color};
-"#));
+          block.append(template.fragment_color(&color.to_string()));
+          block.append(template.fragment_end.clone());
+          Ok(())
+        })
+      }
+    }
+  }
+}
 
-        block.append(r#"
-	// alpha discard
-  pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+impl_node! {
+  mod vertex_output_node {
+    NodeInfo {
+      name: "Vertex",
+      category: ["Output"],
+    }
 
-#ifdef PREPASS_PIPELINE
-	// No lighting in deferred mode.
-	let out = deferred_output(in, pbr_input);
-#else
-	var out: FragmentOutput;
-  if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
-		out.color = apply_pbr_lighting(pbr_input);
-	} else {
-		out.color = pbr_input.material.base_color;
-  }
+    /// The vertex shader node.
+    #[derive(Default)]
+    pub struct VertexOutputNode {
+      /// Object space offset added to the vertex position.
+      pub position_offset: Input<Vec3>,
+    }
 
-	// Apply PBR post processing.
-	out.color = main_pass_post_lighting_processing(pbr_input, out.color);
-#endif
+    impl VertexOutputNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
 
-  return out;
-}
-"#.to_string()
-        );
-        compile.pop(Some(frag_block))?;
-        Ok(())
+    impl NodeImpl for VertexOutputNode {
+      fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, _id: NodeId) -> Result<()> {
+        append_shader_header(compile)?;
+        let template = compile.template().clone();
+        compile.add_entry_point(&template.vertex_entry, ShaderStage::Vertex);
+        compile.with_block("vertex", |compile| {
+          compile.current_block()?.append(template.vertex_begin.clone());
+          let offset = self.resolve_inputs(graph, compile)?;
+          let block = compile.current_block()?;
+          block.append(template.vertex_offset(&offset.to_string()));
+          block.append(template.vertex_end.clone());
+          Ok(())
+        })
       }
     }
   }
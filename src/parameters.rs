@@ -1,5 +1,9 @@
+use glam::{Vec2, Vec3, Vec4};
+
 use anyhow::Result;
 
+#[cfg(feature = "egui")]
+use crate::ui::*;
 use crate::*;
 
 impl_enum_parameter_type!(
@@ -15,9 +19,13 @@ impl_enum_parameter_type!(
 impl_enum_parameter_type!(
   #[derive(PartialEq, Eq)]
   pub enum UvChannel {
+    #[label = "UV 0"]
     UV0,
+    #[label = "UV 1"]
     UV1,
+    #[label = "UV 2"]
     UV2,
+    #[label = "UV 3"]
     UV3,
   }
 );
@@ -44,14 +52,15 @@ pub fn is_swizzle_limit(len: usize, ch: char) -> bool {
 }
 
 impl SwizzleMask {
-  pub fn compile(&self, input: CompiledValue) -> Result<CompiledValue> {
-    let len = match input.dt {
+  // Validate the mask against the input type.  Returns the input length and output type.
+  fn validate(&self, dt: DataType) -> Result<(usize, DataType)> {
+    let len = match dt {
       DataType::I32 | DataType::U32 | DataType::F32 => 1,
       DataType::Vec2 => 2,
       DataType::Vec3 => 3,
       DataType::Vec4 => 4,
       _ => {
-        return Err(anyhow::anyhow!("Unsupport input for Swizzle: {input:?}"));
+        return Err(anyhow::anyhow!("Unsupport input for Swizzle: {dt:?}"));
       }
     };
     // Validate mask.
@@ -62,12 +71,24 @@ impl SwizzleMask {
         self.0
       ));
     }
-    let out_dt = match self.0.len() {
-      4 => DataType::Vec4,
-      3 => DataType::Vec3,
-      2 => DataType::Vec2,
-      _ => input.dt,
-    };
+    let scalar = if len == 1 { dt } else { DataType::F32 };
+    Ok((len, self.output_type(scalar).unwrap_or(dt)))
+  }
+
+  /// The output type, based on the length of the mask.  Single component masks keep
+  /// the `scalar` type of the input.
+  pub fn output_type(&self, scalar: DataType) -> Option<DataType> {
+    match self.0.len() {
+      4 => Some(DataType::Vec4),
+      3 => Some(DataType::Vec3),
+      2 => Some(DataType::Vec2),
+      1 => Some(scalar),
+      _ => None,
+    }
+  }
+
+  pub fn compile(&self, input: CompiledValue) -> Result<CompiledValue> {
+    let (len, out_dt) = self.validate(input.dt)?;
     let out = if len == 1 {
       // Special case for scalar inputs.
       match out_dt {
@@ -85,6 +106,38 @@ impl SwizzleMask {
     })
   }
 
+  /// Apply the swizzle mask to an evaluated value.  The CPU version of `compile`.
+  pub fn eval(&self, input: Value) -> Result<Value> {
+    let (len, _) = self.validate(input.data_type())?;
+    if len == 1 || self.0.is_empty() {
+      // Scalar inputs are splatted by `convert`.
+      let out_dt = match self.0.len() {
+        4 => DataType::Vec4,
+        3 => DataType::Vec3,
+        2 => DataType::Vec2,
+        _ => return Ok(input),
+      };
+      return input.convert(out_dt);
+    }
+    let comps = input.components()?;
+    let out: Vec<f32> = self
+      .0
+      .chars()
+      .map(|ch| match ch {
+        'y' | 'g' => comps[1],
+        'z' | 'b' => comps[2],
+        'w' | 'a' => comps[3],
+        _ => comps[0],
+      })
+      .collect();
+    Ok(match out.len() {
+      4 => Value::Vec4(Vec4::from_slice(&out)),
+      3 => Value::Vec3(Vec3::from_slice(&out)),
+      2 => Value::Vec2(Vec2::from_slice(&out)),
+      _ => Value::F32(out[0]),
+    })
+  }
+
   pub fn filter(&mut self) {
     // Remove any non-swizzle digit.
     let mut mask = self.0.replace(|ch| !is_swizzle(ch), "");
@@ -118,6 +171,13 @@ impl ParameterType for SwizzleMask {
     ParameterDataType::Text("xyz".to_string())
   }
 
+  fn validate_concrete_type(&self, concrete_type: &NodeConcreteType) -> Result<()> {
+    match concrete_type.data_type() {
+      Some(dt) => self.validate(dt).map(|_| ()),
+      None => Ok(()),
+    }
+  }
+
   #[cfg(feature = "egui")]
   fn parameter_ui(
     &mut self,
@@ -139,3 +199,214 @@ impl ParameterType for SwizzleMask {
     .inner
   }
 }
+
+/// A response curve, the control points are interpolated with `smoothstep`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Curve {
+  pub points: Vec<Vec2>,
+}
+
+impl Default for Curve {
+  fn default() -> Self {
+    Self::new(vec![Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)])
+  }
+}
+
+impl Curve {
+  pub fn new(mut points: Vec<Vec2>) -> Self {
+    points.sort_by(|a, b| a.x.total_cmp(&b.x));
+    Self { points }
+  }
+
+  /// Evaluate the curve at `x`.
+  pub fn eval(&self, x: f32) -> f32 {
+    let mut points = self.points.iter();
+    let Some(first) = points.next() else {
+      return x;
+    };
+    let mut prev = first;
+    let mut y = first.y;
+    for p in points {
+      if p.x > prev.x {
+        let t = ((x - prev.x) / (p.x - prev.x)).clamp(0.0, 1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        y += (p.y - y) * t;
+      } else if x >= p.x {
+        y = p.y;
+      }
+      prev = p;
+    }
+    y
+  }
+
+  /// Compile the curve into piecewise `mix`/`smoothstep`.
+  pub fn compile(&self, input: CompiledValue) -> Result<CompiledValue> {
+    let mut points = self.points.iter();
+    let Some(first) = points.next() else {
+      return Ok(input);
+    };
+    let mut prev = first;
+    let mut code = format!("{:?}", first.y);
+    for p in points {
+      code = if p.x > prev.x {
+        format!(
+          "mix({code}, {:?}, smoothstep({:?}, {:?}, {input}))",
+          p.y, prev.x, p.x
+        )
+      } else {
+        format!("select({code}, {:?}, {input} >= {:?})", p.y, p.x)
+      };
+      prev = p;
+    }
+    Ok(CompiledValue {
+      value: code,
+      dt: DataType::F32,
+    })
+  }
+
+  /// Curve editor.  Drag points to move them, double-click to add a point and
+  /// right-click a point to remove it.
+  #[cfg(feature = "egui")]
+  pub fn ui(&mut self, ui: &mut egui::Ui, size: egui::Vec2) -> bool {
+    let (resp, painter) = ui.allocate_painter(size, egui::Sense::click());
+    let rect = resp.rect;
+    let to_screen = |p: Vec2| rect.lerp_inside([p.x, 1.0 - p.y].into());
+    let from_screen = |pos: egui::Pos2| {
+      let p = (pos - rect.min) / rect.size();
+      Vec2::new(p.x.clamp(0.0, 1.0), (1.0 - p.y).clamp(0.0, 1.0))
+    };
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+    let mut changed = false;
+    let mut remove = None;
+    let radius = 4.0;
+    for (idx, p) in self.points.iter_mut().enumerate() {
+      let point_rect = egui::Rect::from_center_size(to_screen(*p), egui::Vec2::splat(radius * 2.0));
+      let point_resp = ui.interact(point_rect, resp.id.with(idx), egui::Sense::click_and_drag());
+      if point_resp.dragged() {
+        let pos = to_screen(*p) + point_resp.drag_delta();
+        *p = from_screen(pos);
+        changed = true;
+      }
+      if point_resp.secondary_clicked() {
+        remove = Some(idx);
+      }
+    }
+    if let Some(idx) = remove {
+      // Keep at least two points.
+      if self.points.len() > 2 {
+        self.points.remove(idx);
+        changed = true;
+      }
+    }
+    if resp.double_clicked() {
+      if let Some(pos) = resp.interact_pointer_pos() {
+        self.points.push(from_screen(pos));
+        changed = true;
+      }
+    }
+    if changed {
+      self.points.sort_by(|a, b| a.x.total_cmp(&b.x));
+    }
+
+    // Draw the curve.
+    let stroke = visuals.widgets.active.fg_stroke;
+    let steps = 32;
+    let line = (0..=steps)
+      .map(|step| {
+        let x = step as f32 / steps as f32;
+        to_screen(Vec2::new(x, self.eval(x)))
+      })
+      .collect();
+    painter.add(egui::Shape::line(line, stroke));
+    for p in &self.points {
+      painter.circle_filled(to_screen(*p), radius, stroke.color);
+    }
+    changed
+  }
+}
+
+impl ParameterType for Curve {
+  fn get_param(&self) -> ParameterValue {
+    ParameterValue::Curve(self.points.clone())
+  }
+
+  fn set_param(&mut self, value: ParameterValue) -> Result<()> {
+    match value {
+      ParameterValue::Curve(points) => {
+        *self = Self::new(points);
+        Ok(())
+      }
+      _ => Err(anyhow::anyhow!(
+        "Unsupport ParameterValue -> Curve conversion."
+      )),
+    }
+  }
+
+  fn parameter_data_type() -> ParameterDataType {
+    ParameterDataType::Curve
+  }
+
+  #[cfg(feature = "egui")]
+  fn parameter_ui(
+    &mut self,
+    def: &ParameterDefinition,
+    ui: &mut egui::Ui,
+    _id: NodeId,
+    details: bool,
+  ) -> bool {
+    let zoom = NodeStyle::get(ui).zoom;
+    let size = if details { 150.0 } else { 100.0 * zoom };
+    ui.vertical(|ui| {
+      ui.label(&def.name);
+      self.ui(ui, egui::Vec2::splat(size))
+    })
+    .inner
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn curve_eval() {
+    let curve = Curve::new(vec![
+      Vec2::new(1.0, 0.0),
+      Vec2::new(0.0, 0.0),
+      Vec2::new(0.5, 1.0),
+    ]);
+    assert_eq!(curve.eval(-1.0), 0.0);
+    assert_eq!(curve.eval(0.25), 0.5);
+    assert_eq!(curve.eval(0.5), 1.0);
+    assert_eq!(curve.eval(2.0), 0.0);
+
+    let input = CompiledValue {
+      value: "x".into(),
+      dt: DataType::F32,
+    };
+    assert_eq!(
+      curve.compile(input).unwrap().value,
+      "mix(mix(0.0, 1.0, smoothstep(0.0, 0.5, x)), 0.0, smoothstep(0.5, 1.0, x))"
+    );
+  }
+
+  #[test]
+  fn swizzle_validate_concrete_type() {
+    let mut concrete_type = NodeConcreteType::default();
+    let mask = SwizzleMask("xw".into());
+    // Unconnected inputs can't be validated.
+    assert!(mask.validate_concrete_type(&concrete_type).is_ok());
+
+    concrete_type.add_input_type(DataType::Vec2);
+    assert!(mask.validate_concrete_type(&concrete_type).is_err());
+    assert!(SwizzleMask("yx".into())
+      .validate_concrete_type(&concrete_type)
+      .is_ok());
+
+    let mut concrete_type = NodeConcreteType::default();
+    concrete_type.add_input_type(DataType::Vec4);
+    assert!(mask.validate_concrete_type(&concrete_type).is_ok());
+  }
+}
@@ -0,0 +1,213 @@
+use std::fs::File;
+use std::path::Path;
+
+use indexmap::IndexMap;
+use uuid::Uuid;
+
+use serde::{Deserialize, Serialize};
+
+use anyhow::Result;
+
+use crate::*;
+
+/// A configured node saved for reuse.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NodePreset {
+  pub name: String,
+  pub node_type: Uuid,
+  pub node: serde_json::Value,
+}
+
+impl NodePreset {
+  /// Save the node's type and state as a preset.
+  ///
+  /// Connected inputs are reset to their default values, the connections refer to
+  /// the nodes of the node's graph.
+  pub fn from_node(name: &str, node: &Node) -> Result<Self> {
+    let mut node = node.clone();
+    let defaults = Node::new(node.def())?;
+    for idx in 0..node.input_count() as u32 {
+      if let Input::Connect(..) = node.get_input(idx)? {
+        let value = defaults.get_input(idx).unwrap_or(Input::Disconnect);
+        node.set_input(idx, value)?;
+      }
+    }
+    Ok(Self {
+      name: name.to_string(),
+      node_type: node.def().id,
+      node: node.node_state()?,
+    })
+  }
+
+  /// Create a new node from the preset.
+  pub fn instantiate(&self, reg: &NodeRegistry) -> Result<Node> {
+    let mut node = reg.load_node(LoadNodeState {
+      id: Uuid::new_v4(),
+      group_id: Uuid::nil(),
+      name: self.name.clone(),
+      node_type: self.node_type,
+      node: self.node.clone(),
+      area: emath::Rect::from_min_size([0., 0.].into(), [10., 10.].into()),
+      locked: false,
+    })?;
+    // Give the nodes contained in a subgraph new ids too.
+    node.new_id();
+    Ok(node)
+  }
+}
+
+/// A library of node presets, listed by the node finder.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PresetLibrary {
+  presets: IndexMap<String, NodePreset>,
+}
+
+impl PresetLibrary {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+    let file = File::open(path)?;
+    Ok(serde_json::from_reader(file)?)
+  }
+
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, self)?;
+    Ok(())
+  }
+
+  /// Add a preset.  Replaces and returns the preset with the same name.
+  pub fn add(&mut self, preset: NodePreset) -> Option<NodePreset> {
+    self.presets.insert(preset.name.clone(), preset)
+  }
+
+  pub fn remove(&mut self, name: &str) -> Option<NodePreset> {
+    self.presets.shift_remove(name)
+  }
+
+  pub fn get(&self, name: &str) -> Option<&NodePreset> {
+    self.presets.get(name)
+  }
+
+  pub fn len(&self) -> usize {
+    self.presets.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.presets.is_empty()
+  }
+
+  pub fn presets(&self) -> impl Iterator<Item = &NodePreset> {
+    self.presets.values()
+  }
+
+  pub fn matches<'a>(&'a self, filter: &'a NodeFilter) -> impl Iterator<Item = &'a NodePreset> {
+    self
+      .presets
+      .values()
+      .filter(|preset| filter.matches(&preset.name))
+  }
+
+  #[cfg(feature = "egui")]
+  pub fn ui(&self, ui: &mut egui::Ui, filter: &NodeFilter, reg: &NodeRegistry) -> Option<Node> {
+    // Hide the presets when none match.
+    self.matches(filter).next()?;
+    let mut selected = None;
+    ui.group(|ui| {
+      ui.collapsing("Presets", |ui| {
+        for preset in self.matches(filter) {
+          if ui.button(&preset.name).clicked() {
+            selected = Some(preset);
+          }
+        }
+      });
+    });
+    selected.and_then(|preset| match preset.instantiate(reg) {
+      Ok(node) => Some(node),
+      Err(err) => {
+        log::error!("Failed to create node from preset: {err:?}");
+        None
+      }
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn preset_round_trip() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut node = reg.new_by_name("Float")?;
+    node.set_param("Value", ParameterValue::Value(Value::F32(0.25)))?;
+
+    let mut library = PresetLibrary::new();
+    library.add(NodePreset::from_node("Quarter", &node)?);
+    let json = serde_json::to_string(&library)?;
+    let library: PresetLibrary = serde_json::from_str(&json)?;
+
+    let filter = NodeFilter {
+      name: "quar".to_string(),
+      ..Default::default()
+    };
+    let preset = library.matches(&filter).next().expect("matching preset");
+    let new_node = preset.instantiate(&reg)?;
+    assert_ne!(new_node.id, node.id);
+    assert_eq!(new_node.name, "Quarter");
+    assert_eq!(new_node.def().name, "Float");
+    assert!(matches!(
+      new_node.get_param("Value")?,
+      ParameterValue::Value(Value::F32(v)) if v == 0.25
+    ));
+    Ok(())
+  }
+
+  #[test]
+  fn preset_drops_connections() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+
+    let preset = NodePreset::from_node("Add 3", graph.get(add)?)?;
+    let node = preset.instantiate(&reg)?;
+    // The connected input has the default value of a new node.
+    assert_eq!(
+      format!("{:?}", node.get_input("A")?),
+      format!("{:?}", reg.new_by_name("Add")?.get_input("A")?)
+    );
+    assert!(matches!(node.get_input("B")?, Input::Value(Value::F32(v)) if v == 3.0));
+    Ok(())
+  }
+
+  #[test]
+  fn preset_subgraph_ids() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", float.into())?;
+    let out = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(out, "A", add.into())?;
+    let group = graph.add_group(NodeGroup::new());
+    for id in [float, add] {
+      graph.get_mut(id)?.group_id = group;
+    }
+    let sub = graph.collapse_group(group)?;
+
+    let preset = NodePreset::from_node("Sub", graph.get(sub)?)?;
+    let (a, b) = (preset.instantiate(&reg)?, preset.instantiate(&reg)?);
+    let (a, b) = (
+      a.as_subgraph().expect("subgraph"),
+      b.as_subgraph().expect("subgraph"),
+    );
+    assert_ne!(a.outputs()[0].output, b.outputs()[0].output);
+    assert!(!a.graph().contains(b.outputs()[0].output.node()));
+    Ok(())
+  }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Accumulated time spent in a node type.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NodeTiming {
+  /// Number of times nodes of this type were evaluated/compiled.
+  pub count: usize,
+  /// Time spent in the nodes, not including their inputs.
+  pub total: Duration,
+}
+
+/// Per-node-type timings recorded by `NodeGraphExecution` and `NodeGraphCompile`.
+#[derive(Clone, Debug, Default)]
+pub struct NodeProfile {
+  timings: HashMap<String, NodeTiming>,
+  /// Time spent in nested nodes, used to exclude the inputs from a node's time.
+  nested: Vec<Duration>,
+}
+
+impl NodeProfile {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn clear(&mut self) {
+    self.timings.clear();
+    self.nested.clear();
+  }
+
+  /// Start timing a node.  Must be followed by `finish`.
+  pub(crate) fn start(&mut self) -> Instant {
+    self.nested.push(Duration::ZERO);
+    Instant::now()
+  }
+
+  /// Record the time spent in a node of type `name` since `start`.
+  pub(crate) fn finish(&mut self, name: &str, start: Instant) {
+    let elapsed = start.elapsed();
+    let nested = self.nested.pop().unwrap_or_default();
+    if let Some(parent) = self.nested.last_mut() {
+      *parent += elapsed;
+    }
+    let timing = self.timings.entry(name.to_string()).or_default();
+    timing.count += 1;
+    timing.total += elapsed.saturating_sub(nested);
+  }
+
+  pub fn get(&self, name: &str) -> Option<&NodeTiming> {
+    self.timings.get(name)
+  }
+
+  /// The timings sorted by total time, most expensive first.
+  pub fn summary(&self) -> Vec<(&str, NodeTiming)> {
+    let mut summary: Vec<_> = self
+      .timings
+      .iter()
+      .map(|(name, timing)| (name.as_str(), *timing))
+      .collect();
+    summary.sort_by(|a, b| b.1.total.cmp(&a.1.total).then(a.0.cmp(b.0)));
+    summary
+  }
+}
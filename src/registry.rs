@@ -14,31 +14,96 @@ use serde::{Deserialize, Serialize};
 use crate::*;
 
 lazy_static::lazy_static! {
-  pub static ref NODE_REGISTRY: NodeRegistry = {
-    let registry = NodeRegistry::new();
-    for reg in inventory::iter::<RegisterNode> {
-      let def = (reg.get_def)();
-      if let Some(prev) = registry.register(&def) {
-        log::error!(
-          "Node {:?} re-defined at {}, prev definition at: {}",
-          def.name,
-          def.source_file,
-          prev.source_file
-        );
-      }
-    }
-    registry
-  };
+  pub static ref NODE_REGISTRY: NodeRegistry =
+    NodeRegistry::from_definitions(NodeRegistry::inventory_definitions());
 }
 
 #[derive(Clone, Default, Debug)]
 pub struct NodeFilter {
   pub name: String,
+  /// Only match nodes from this package.
+  pub package: Option<String>,
 }
 
+/// Base score of a substring match.  Higher than any subsequence match.
+const SUBSTRING_SCORE: u32 = 1000;
+
 impl NodeFilter {
   pub fn matches(&self, name: &str) -> bool {
-    name.to_lowercase().contains(&self.name.to_lowercase())
+    self.score(name).is_some()
+  }
+
+  /// Score how well `name` matches the filter, higher is better.  `None` if it doesn't match.
+  ///
+  /// Substring matches score highest (prefix and word starts first), then fuzzy
+  /// subsequence matches like "tao" for "Tiling and Offset".
+  pub fn score(&self, name: &str) -> Option<u32> {
+    let pattern = self.name.trim().to_lowercase();
+    if pattern.is_empty() {
+      return Some(0);
+    }
+    let name = name.to_lowercase();
+    let is_word_start = |pos: usize| {
+      name[..pos]
+        .chars()
+        .next_back()
+        .is_none_or(|ch| !ch.is_alphanumeric())
+    };
+    // Fast path: substring match.
+    if let Some(pos) = name.find(&pattern) {
+      let bonus = if pos == 0 {
+        200
+      } else if is_word_start(pos) {
+        100
+      } else {
+        0
+      };
+      // Prefer shorter names.
+      let len_penalty = name.len().min(99) as u32;
+      return Some(SUBSTRING_SCORE + bonus - len_penalty);
+    }
+    // Subsequence match.  Favour word starts and consecutive characters.
+    let mut score = 0;
+    let mut chars = pattern.chars().filter(|ch| !ch.is_whitespace()).peekable();
+    let mut last_end = None;
+    for (pos, ch) in name.char_indices() {
+      let Some(&next) = chars.peek() else {
+        break;
+      };
+      if ch != next {
+        continue;
+      }
+      chars.next();
+      score += 1;
+      if is_word_start(pos) {
+        score += 5;
+      }
+      if last_end == Some(pos) {
+        score += 3;
+      }
+      last_end = Some(pos + ch.len_utf8());
+    }
+    if chars.peek().is_some() {
+      return None;
+    }
+    Some(score.min(SUBSTRING_SCORE - 1))
+  }
+
+  /// Returns true if the filter has a search text.
+  pub fn is_searching(&self) -> bool {
+    !self.name.trim().is_empty()
+  }
+
+  pub fn matches_package(&self, package: &str) -> bool {
+    match &self.package {
+      Some(p) => p == package,
+      None => true,
+    }
+  }
+
+  /// Match the node definition's name and package.
+  pub fn matches_def(&self, def: &NodeDefinition) -> bool {
+    self.matches(&def.name) && self.matches_package(&def.package)
   }
 
   #[cfg(feature = "egui")]
@@ -50,6 +115,42 @@ impl NodeFilter {
   }
 }
 
+/// A category of the registry's node palette.  See `NodeRegistry::category_tree`.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CategoryNode {
+  /// The category name.  Empty for the root.
+  pub name: String,
+  pub categories: Vec<CategoryNode>,
+  /// The names and ids of the nodes in this category.
+  pub nodes: Vec<(String, Uuid)>,
+}
+
+impl CategoryNode {
+  /// Find a sub-category by its path of names.
+  pub fn get(&self, path: &[&str]) -> Option<&CategoryNode> {
+    match path.split_first() {
+      Some((name, remaining)) => self
+        .categories
+        .iter()
+        .find(|c| c.name == *name)
+        .and_then(|c| c.get(remaining)),
+      None => Some(self),
+    }
+  }
+
+  /// Iterate over the nodes in all sub-categories and this category, in the order
+  /// they are listed by `NodeRegistry::ui`.
+  pub fn all_nodes(&self) -> Box<dyn Iterator<Item = &(String, Uuid)> + '_> {
+    Box::new(
+      self
+        .categories
+        .iter()
+        .flat_map(|c| c.all_nodes())
+        .chain(self.nodes.iter()),
+    )
+  }
+}
+
 #[derive(Default, Debug, Serialize, Deserialize)]
 struct NodeCategory {
   categories: IndexMap<String, NodeCategory>,
@@ -57,41 +158,195 @@ struct NodeCategory {
 }
 
 impl NodeCategory {
-  pub fn matches(&self, filter: &NodeFilter) -> bool {
+  fn node_matches(
+    filter: &NodeFilter,
+    name: &str,
+    id: &Uuid,
+    defs: &HashMap<Uuid, NodeDefinition>,
+  ) -> bool {
+    filter.matches(name)
+      && defs
+        .get(id)
+        .is_some_and(|def| filter.matches_package(&def.package))
+  }
+
+  /// Does the category contain any nodes from the filter's package.
+  fn in_package(&self, filter: &NodeFilter, defs: &HashMap<Uuid, NodeDefinition>) -> bool {
+    filter.package.is_none()
+      || self.categories.values().any(|c| c.in_package(filter, defs))
+      || self.nodes.values().any(|id| {
+        defs
+          .get(id)
+          .is_some_and(|def| filter.matches_package(&def.package))
+      })
+  }
+
+  fn category_matches(
+    filter: &NodeFilter,
+    name: &str,
+    category: &NodeCategory,
+    defs: &HashMap<Uuid, NodeDefinition>,
+  ) -> bool {
+    (filter.matches(name) && category.in_package(filter, defs)) || category.matches(filter, defs)
+  }
+
+  pub fn matches(&self, filter: &NodeFilter, defs: &HashMap<Uuid, NodeDefinition>) -> bool {
     for (name, category) in &self.categories {
-      if filter.matches(name) || category.matches(filter) {
+      if Self::category_matches(filter, name, category, defs) {
         return true;
       }
     }
     // Render nodes.
-    for (name, _) in &self.nodes {
-      if filter.matches(name) {
+    for (name, id) in &self.nodes {
+      if Self::node_matches(filter, name, id, defs) {
         return true;
       }
     }
     false
   }
 
+  /// The best match score of the category's name, sub-categories and nodes.
+  fn score(
+    &self,
+    filter: &NodeFilter,
+    name: &str,
+    defs: &HashMap<Uuid, NodeDefinition>,
+  ) -> Option<u32> {
+    let name_score = filter.score(name).filter(|_| self.in_package(filter, defs));
+    let (categories, nodes) = self.sorted_matches(filter, defs);
+    let best_category = categories.first().map(|(_, _, score)| *score);
+    let best_node = nodes.first().map(|(_, _, score)| *score);
+    name_score.max(best_category).max(best_node)
+  }
+
+  /// The matching sub-categories and nodes, sorted by score (best first).
+  #[allow(clippy::type_complexity)]
+  fn sorted_matches(
+    &self,
+    filter: &NodeFilter,
+    defs: &HashMap<Uuid, NodeDefinition>,
+  ) -> (
+    Vec<(&String, &NodeCategory, u32)>,
+    Vec<(&String, &Uuid, u32)>,
+  ) {
+    let mut categories: Vec<_> = self
+      .categories
+      .iter()
+      .filter(|(name, category)| Self::category_matches(filter, name, category, defs))
+      .map(|(name, category)| {
+        let score = category.score(filter, name, defs).unwrap_or_default();
+        (name, category, score)
+      })
+      .collect();
+    let mut nodes: Vec<_> = self
+      .nodes
+      .iter()
+      .filter(|(name, id)| Self::node_matches(filter, name, id, defs))
+      .map(|(name, id)| (name, id, filter.score(name).unwrap_or_default()))
+      .collect();
+    // Stable sort, keeps the registration order for equal scores.
+    categories.sort_by_key(|(_, _, score)| std::cmp::Reverse(*score));
+    nodes.sort_by_key(|(_, _, score)| std::cmp::Reverse(*score));
+    (categories, nodes)
+  }
+
+  /// The nodes matching the filter, in the same order as they are listed in the ui.
+  fn matching_nodes(
+    &self,
+    filter: &NodeFilter,
+    defs: &HashMap<Uuid, NodeDefinition>,
+    found: &mut Vec<Uuid>,
+  ) {
+    let (categories, nodes) = self.sorted_matches(filter, defs);
+    for (_, category, _) in categories {
+      category.matching_nodes(filter, defs, found);
+    }
+    found.extend(nodes.into_iter().map(|(_, id, _)| *id));
+  }
+
+  /// The nodes matching the filter as a flat list with their category path, best
+  /// matches first.  Nodes with equal scores are ranked by how well their category
+  /// path matches the filter.
+  fn ranked_nodes(
+    &self,
+    filter: &NodeFilter,
+    defs: &HashMap<Uuid, NodeDefinition>,
+  ) -> Vec<(&String, Uuid, Vec<&String>)> {
+    let mut found = Vec::new();
+    self.collect_ranked(filter, defs, &mut Vec::new(), &mut found);
+    // Stable sort, keeps the tree order for equal scores.
+    found.sort_by_key(|(_, _, _, score)| std::cmp::Reverse(*score));
+    found
+      .into_iter()
+      .map(|(name, id, path, _)| (name, id, path))
+      .collect()
+  }
+
+  #[allow(clippy::type_complexity)]
+  fn collect_ranked<'a>(
+    &'a self,
+    filter: &NodeFilter,
+    defs: &HashMap<Uuid, NodeDefinition>,
+    path: &mut Vec<&'a String>,
+    found: &mut Vec<(&'a String, Uuid, Vec<&'a String>, (u32, u32))>,
+  ) {
+    for (name, category) in &self.categories {
+      path.push(name);
+      category.collect_ranked(filter, defs, path, found);
+      path.pop();
+    }
+    let category_score = path
+      .iter()
+      .filter_map(|name| filter.score(name))
+      .max()
+      .unwrap_or_default();
+    for (name, id) in &self.nodes {
+      if Self::node_matches(filter, name, id, defs) {
+        let score = filter.score(name).unwrap_or_default();
+        found.push((name, *id, path.clone(), (score, category_score)));
+      }
+    }
+  }
+
+  fn contains(&self, id: &Uuid) -> bool {
+    self.nodes.values().any(|n| n == id) || self.categories.values().any(|c| c.contains(id))
+  }
+
+  /// Render the category.  `highlight` is the node selected with the keyboard,
+  /// when `reveal` is true the categories are opened and scrolled to show it.
   #[cfg(feature = "egui")]
-  pub fn ui(&self, ui: &mut egui::Ui, filter: &NodeFilter) -> Option<Uuid> {
+  pub fn ui(
+    &self,
+    ui: &mut egui::Ui,
+    filter: &NodeFilter,
+    defs: &HashMap<Uuid, NodeDefinition>,
+    highlight: Option<Uuid>,
+    reveal: bool,
+  ) -> Option<Uuid> {
     let mut selected_node = None;
+    let (categories, nodes) = self.sorted_matches(filter, defs);
     // Render sub-categories.
-    for (name, category) in &self.categories {
-      if filter.matches(name) || category.matches(filter) {
-        ui.collapsing(name, |ui| {
-          let id = category.ui(ui, filter);
-          if id.is_some() {
-            selected_node = id;
-          }
-        });
+    for (name, category, _) in categories {
+      let mut header = egui::CollapsingHeader::new(name);
+      if reveal && highlight.is_some_and(|id| category.contains(&id)) {
+        header = header.open(Some(true));
       }
+      header.show(ui, |ui| {
+        let id = category.ui(ui, filter, defs, highlight, reveal);
+        if id.is_some() {
+          selected_node = id;
+        }
+      });
     }
     // Render nodes.
-    for (name, id) in &self.nodes {
-      if filter.matches(name) {
-        if ui.button(name).clicked() {
-          selected_node = Some(*id);
-        }
+    for (name, id, _) in nodes {
+      let highlighted = highlight == Some(*id);
+      let resp = ui.add(egui::Button::new(name).selected(highlighted));
+      if highlighted && reveal {
+        resp.scroll_to_me(None);
+      }
+      if resp.clicked() {
+        selected_node = Some(*id);
       }
     }
     selected_node
@@ -109,6 +364,22 @@ impl NodeCategory {
   fn add_node(&mut self, name: String, id: Uuid) {
     self.nodes.insert(name, id);
   }
+
+  fn to_tree(&self, name: &str) -> CategoryNode {
+    CategoryNode {
+      name: name.to_string(),
+      categories: self
+        .categories
+        .iter()
+        .map(|(name, category)| category.to_tree(name))
+        .collect(),
+      nodes: self
+        .nodes
+        .iter()
+        .map(|(name, id)| (name.clone(), *id))
+        .collect(),
+    }
+  }
 }
 
 #[derive(Default, Debug, Serialize, Deserialize)]
@@ -123,6 +394,22 @@ impl NodeRegistryInner {
     self.nodes.values().cloned().collect()
   }
 
+  pub fn nodes_in_package(&self, package: &str) -> Vec<NodeDefinition> {
+    self
+      .nodes
+      .values()
+      .filter(|def| def.package == package)
+      .cloned()
+      .collect()
+  }
+
+  pub fn packages(&self) -> Vec<String> {
+    let mut packages: Vec<_> = self.nodes.values().map(|def| def.package.clone()).collect();
+    packages.sort();
+    packages.dedup();
+    packages
+  }
+
   fn register(&mut self, def: &NodeDefinition) -> Option<NodeDefinition> {
     let category = self.categories.get_category_mut(def.category.as_slice());
     if !def.deprecated {
@@ -157,21 +444,75 @@ impl NodeRegistryInner {
     Node::new(def)
   }
 
+  fn matching_nodes(&self, filter: &NodeFilter) -> Vec<Uuid> {
+    if filter.is_searching() {
+      return self
+        .categories
+        .ranked_nodes(filter, &self.nodes)
+        .into_iter()
+        .map(|(_, id, _)| id)
+        .collect();
+    }
+    let mut found = Vec::new();
+    self
+      .categories
+      .matching_nodes(filter, &self.nodes, &mut found);
+    found
+  }
+
+  /// Render the matching nodes as a flat list with their category paths.
   #[cfg(feature = "egui")]
-  pub fn ui(&self, ui: &mut egui::Ui, filter: &NodeFilter) -> Option<Node> {
+  fn ranked_ui(
+    &self,
+    ui: &mut egui::Ui,
+    filter: &NodeFilter,
+    highlight: Option<Uuid>,
+    reveal: bool,
+  ) -> Option<Uuid> {
+    let mut selected_node = None;
+    for (name, id, path) in self.categories.ranked_nodes(filter, &self.nodes) {
+      ui.horizontal(|ui| {
+        let highlighted = highlight == Some(id);
+        let resp = ui.add(egui::Button::new(name).selected(highlighted));
+        if highlighted && reveal {
+          resp.scroll_to_me(None);
+        }
+        if resp.clicked() {
+          selected_node = Some(id);
+        }
+        let path: Vec<&str> = path.iter().map(|name| name.as_str()).collect();
+        ui.weak(path.join(" › "));
+      });
+    }
+    selected_node
+  }
+
+  #[cfg(feature = "egui")]
+  pub fn ui(
+    &self,
+    ui: &mut egui::Ui,
+    filter: &NodeFilter,
+    highlight: Option<Uuid>,
+    reveal: bool,
+  ) -> Option<Node> {
     let mut selected_node = None;
     ui.group(|ui| {
-      selected_node = self
-        .categories
-        .ui(ui, filter)
-        .and_then(|id| self.nodes.get(&id))
-        .and_then(|def| match Node::new(def) {
-          Ok(node) => Some(node),
-          Err(err) => {
+      // Search results are shown as a flat list, the tree is only used for browsing.
+      let id = if filter.is_searching() {
+        self.ranked_ui(ui, filter, highlight, reveal)
+      } else {
+        self
+          .categories
+          .ui(ui, filter, &self.nodes, highlight, reveal)
+      };
+      selected_node = id.and_then(|id| {
+        self
+          .new_by_id(id)
+          .map_err(|err| {
             log::error!("Failed to create node: {err:?}");
-            None
-          }
-        });
+          })
+          .ok()
+      });
     });
     selected_node
   }
@@ -186,6 +527,37 @@ impl NodeRegistry {
     Default::default()
   }
 
+  /// An empty node registry, without the built-in nodes.  Same as `new`.
+  pub fn empty() -> Self {
+    Self::new()
+  }
+
+  /// All node definitions submitted with `register_node!` (and `impl_node!`).
+  ///
+  /// Filter them and use `from_definitions` to build a registry with only some of the nodes.
+  pub fn inventory_definitions() -> Vec<NodeDefinition> {
+    inventory::iter::<RegisterNode>
+      .into_iter()
+      .map(|reg| (reg.get_def)())
+      .collect()
+  }
+
+  /// Build a node registry from `defs`.
+  pub fn from_definitions(defs: impl IntoIterator<Item = NodeDefinition>) -> Self {
+    let registry = Self::new();
+    for def in defs {
+      if let Some(prev) = registry.register(&def) {
+        log::error!(
+          "Node {:?} re-defined at {}, prev definition at: {}",
+          def.name,
+          def.source_file,
+          prev.source_file
+        );
+      }
+    }
+    registry
+  }
+
   /// Build node registry from all node definitions.
   pub fn build() -> Self {
     NODE_REGISTRY.clone()
@@ -196,6 +568,18 @@ impl NodeRegistry {
     inner.nodes()
   }
 
+  /// The node definitions from `package`.
+  pub fn nodes_in_package(&self, package: &str) -> Vec<NodeDefinition> {
+    let inner = self.0.read().unwrap();
+    inner.nodes_in_package(package)
+  }
+
+  /// The sorted names of all packages with registered nodes.
+  pub fn packages(&self) -> Vec<String> {
+    let inner = self.0.read().unwrap();
+    inner.packages()
+  }
+
   pub fn register(&self, def: &NodeDefinition) -> Option<NodeDefinition> {
     let mut inner = self.0.write().unwrap();
     inner.register(def)
@@ -216,10 +600,37 @@ impl NodeRegistry {
     inner.new_by_name(name)
   }
 
+  /// The ids of the nodes matching `filter`, in the order they are listed by `ui`.
+  pub fn matching_nodes(&self, filter: &NodeFilter) -> Vec<Uuid> {
+    let inner = self.0.read().unwrap();
+    inner.matching_nodes(filter)
+  }
+
+  /// The node categories as listed by `ui`, for building a custom node palette.
+  /// Deprecated nodes aren't included.
+  pub fn category_tree(&self) -> CategoryNode {
+    let inner = self.0.read().unwrap();
+    inner.categories.to_tree("")
+  }
+
   #[cfg(feature = "egui")]
   pub fn ui(&self, ui: &mut egui::Ui, filter: &NodeFilter) -> Option<Node> {
-    let inner = self.0.write().unwrap();
-    inner.ui(ui, filter)
+    self.ui_with_highlight(ui, filter, None, false)
+  }
+
+  /// Same as `ui`, but highlights the `highlight` node.  Used for keyboard navigation.
+  ///
+  /// When `reveal` is true the node's categories are opened and it is scrolled into view.
+  #[cfg(feature = "egui")]
+  pub fn ui_with_highlight(
+    &self,
+    ui: &mut egui::Ui,
+    filter: &NodeFilter,
+    highlight: Option<Uuid>,
+    reveal: bool,
+  ) -> Option<Node> {
+    let inner = self.0.read().unwrap();
+    inner.ui(ui, filter, highlight, reveal)
   }
 }
 
@@ -295,12 +706,34 @@ pub struct NodeDefinition {
   pub inputs: IndexMap<String, InputDefinition>,
   pub outputs: IndexMap<String, OutputDefinition>,
   pub custom: IndexMap<String, String>,
+  /// The last input is a variadic group (`Inputs<T>`).  Its definition is used for all the
+  /// inputs in the group.
+  #[serde(default)]
+  pub variadic_inputs: bool,
+  /// The node implements `eval` (CPU evaluation).
+  #[serde(default = "default_supports")]
+  pub supports_eval: bool,
+  /// The node implements `compile` (shader code generation).
+  #[serde(default = "default_supports")]
+  pub supports_compile: bool,
+  /// Resize the node to fit its contents.  Nodes with large editors (curves, gradients)
+  /// can use a fixed size instead, which the user can change.
+  #[serde(default = "default_auto_size")]
+  pub auto_size: bool,
   #[serde(skip)]
   pub source_file: String,
   #[serde(skip)]
   pub builder: Arc<Box<dyn NodeBuilder>>,
 }
 
+fn default_auto_size() -> bool {
+  true
+}
+
+fn default_supports() -> bool {
+  true
+}
+
 impl NodeDefinition {
   pub fn new(
     name: &str,
@@ -314,20 +747,26 @@ impl NodeDefinition {
       name: name.to_title_case(),
       package: package.map(|p| p.to_string()).unwrap_or_default(),
       builder: Arc::new(Box::new(NodeBuilderFn(create))),
+      supports_eval: true,
+      supports_compile: true,
+      auto_size: true,
       ..Default::default()
     }
   }
 
   pub fn matches(&self, filter: &NodeFilter) -> bool {
-    !self.deprecated
-      && self
-        .name
-        .to_lowercase()
-        .contains(&filter.name.to_lowercase())
+    !self.deprecated && filter.matches_def(self)
   }
 
   pub fn new_node(&self) -> Result<Box<dyn NodeImpl>> {
-    self.builder.new_node(self, None)
+    let mut node = self.builder.new_node(self, None)?;
+    // Apply the input defaults.
+    for (idx, input) in self.inputs.values().enumerate() {
+      if let Some(default) = &input.default {
+        node.set_node_input(&InputKey::Idx(idx as _), Input::Value(default.clone()))?;
+      }
+    }
+    Ok(node)
   }
 
   pub fn load_node(&self, data: serde_json::Value) -> Result<Box<dyn NodeImpl>> {
@@ -364,6 +803,10 @@ impl NodeDefinition {
     self.inputs[idx as usize].set_color(color);
   }
 
+  pub fn set_input_default(&mut self, idx: u32, default: Option<Value>) {
+    self.inputs[idx as usize].set_default(default);
+  }
+
   pub fn set_output_color(&mut self, idx: u32, color: Option<u32>) {
     self.outputs[idx as usize].set_color(color);
   }
@@ -372,13 +815,20 @@ impl NodeDefinition {
     self.outputs.iter()
   }
 
+  /// Find an input by its display name, falling back to its field name.
+  fn input_index_by_name(&self, name: &str) -> Option<usize> {
+    self
+      .inputs
+      .get_index_of(name)
+      .or_else(|| self.inputs.values().position(|def| def.field_name == name))
+  }
+
   pub fn get_input_idx(&self, idx: &InputKey) -> Result<u32> {
     match idx {
       InputKey::Idx(idx) => Ok(*idx),
       InputKey::Name(name) => {
         let idx = self
-          .inputs
-          .get_index_of(name)
+          .input_index_by_name(name)
           .ok_or_else(|| anyhow!("Invalid input: {name}"))?;
         Ok(idx as _)
       }
@@ -387,8 +837,14 @@ impl NodeDefinition {
 
   pub fn get_input(&self, idx: &InputKey) -> Option<&InputDefinition> {
     match idx {
+      InputKey::Idx(idx) if self.variadic_inputs && *idx as usize >= self.inputs.len() => {
+        self.inputs.last().map(|(_, v)| v)
+      }
       InputKey::Idx(idx) => self.inputs.get_index(*idx as _).map(|(_, v)| v),
-      InputKey::Name(name) => self.inputs.get(name),
+      InputKey::Name(name) => self
+        .input_index_by_name(name)
+        .and_then(|idx| self.inputs.get_index(idx))
+        .map(|(_, v)| v),
     }
   }
 
@@ -399,4 +855,192 @@ impl NodeDefinition {
   pub fn get_output(&self, name: &str) -> Option<&OutputDefinition> {
     self.outputs.get(name)
   }
+
+  pub fn custom(&self, key: &str) -> Option<&str> {
+    self.custom.get(key).map(|v| v.as_str())
+  }
+
+  /// Parse a custom field.  Returns `None` if the field is missing or fails to parse.
+  pub fn custom_get<T: core::str::FromStr>(&self, key: &str) -> Option<T> {
+    self.custom.get(key).and_then(|v| v.parse().ok())
+  }
+
+  pub fn custom_bool(&self, key: &str) -> Option<bool> {
+    self.custom_get(key)
+  }
+
+  pub fn custom_i64(&self, key: &str) -> Option<i64> {
+    self.custom_get(key)
+  }
+
+  pub fn custom_f64(&self, key: &str) -> Option<f64> {
+    self.custom_get(key)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn nodes_in_package() {
+    let reg = NodeRegistry::build();
+    assert!(reg.packages().contains(&"node_engine".to_string()));
+    let nodes = reg.nodes_in_package("node_engine");
+    assert!(nodes.iter().any(|def| def.name == "Add"));
+    assert!(nodes.iter().all(|def| def.package == "node_engine"));
+    assert!(reg.nodes_in_package("missing").is_empty());
+
+    let add = nodes.iter().find(|def| def.name == "Add").unwrap();
+    let mut filter = NodeFilter {
+      name: "add".to_string(),
+      package: Some("node_engine".to_string()),
+    };
+    assert!(add.matches(&filter));
+    filter.package = Some("my_game".to_string());
+    assert!(!add.matches(&filter));
+  }
+
+  #[test]
+  fn input_key_names() -> anyhow::Result<()> {
+    let reg = NodeRegistry::build();
+    let add = reg.new_by_name("Add")?;
+    let def = add.def();
+    // Display name and field name.
+    assert_eq!(def.get_input_idx(&"B".into())?, 1);
+    assert_eq!(def.get_input_idx(&"b".into())?, 1);
+    assert_eq!(
+      def.get_input(&"b".into()).map(|i| i.name.as_str()),
+      Some("B")
+    );
+    assert!(def.get_input_idx(&"c".into()).is_err());
+
+    let mut graph = NodeGraph::new();
+    let add = graph.add(add);
+    graph.set_node_input(add, "a", 2.0.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    assert_eq!(graph.evaluate_node(add)?, Value::F32(5.0));
+    Ok(())
+  }
+
+  #[test]
+  fn category_tree() {
+    let reg = NodeRegistry::build();
+    let tree = reg.category_tree();
+    assert!(tree.name.is_empty());
+    let math = tree.get(&["Math", "Basic"]).expect("Math/Basic category");
+    let (_, add_id) = math
+      .nodes
+      .iter()
+      .find(|(name, _)| name == "Add")
+      .expect("Add node");
+    assert_eq!(reg.new_by_id(*add_id).unwrap().def().name, "Add");
+    assert!(tree.get(&["Math", "Missing"]).is_none());
+    // Every registered non-deprecated node is in the tree once.
+    let count = reg.nodes().iter().filter(|def| !def.deprecated).count();
+    assert_eq!(tree.all_nodes().count(), count);
+  }
+
+  #[test]
+  fn ranked_search() {
+    let reg = NodeRegistry::build();
+    let filter = NodeFilter {
+      name: "o".to_string(),
+      ..Default::default()
+    };
+    // Search results are ranked across categories.
+    let names: Vec<_> = reg
+      .matching_nodes(&filter)
+      .iter()
+      .map(|id| reg.new_by_id(*id).unwrap().def().name.clone())
+      .collect();
+    assert!(names.len() > 1);
+    let scores: Vec<_> = names
+      .iter()
+      .map(|name| filter.score(name).unwrap())
+      .collect();
+    assert!(scores.windows(2).all(|w| w[0] >= w[1]), "{names:?}");
+    // Without a search text the tree order is used.
+    let all = reg.matching_nodes(&NodeFilter::default());
+    let tree: Vec<Uuid> = reg.category_tree().all_nodes().map(|(_, id)| *id).collect();
+    assert_eq!(all, tree);
+  }
+
+  #[test]
+  fn fuzzy_filter() {
+    let filter = |name: &str| NodeFilter {
+      name: name.to_string(),
+      ..Default::default()
+    };
+    assert!(filter("tao").matches("Tiling and Offset"));
+    assert!(filter("tiloff").matches("Tiling and Offset"));
+    assert!(filter("TILING").matches("Tiling and Offset"));
+    assert!(!filter("tlx").matches("Tiling and Offset"));
+    // Substring beats subsequence, prefix beats other substrings.
+    let f = filter("add");
+    assert!(f.score("Add") > f.score("Texture Add"));
+    assert!(f.score("Texture Add") > f.score("A Dot D"));
+    assert!(filter("").score("Anything") == Some(0));
+
+    // Results are sorted by score.
+    let reg = NodeRegistry::build();
+    let ids = reg.matching_nodes(&filter("vec3"));
+    let first = reg.new_by_id(ids[0]).unwrap();
+    assert_eq!(first.def().name, "Vector 3");
+    let ids = reg.matching_nodes(&filter("tao"));
+    let names: Vec<_> = ids
+      .iter()
+      .map(|id| reg.new_by_id(*id).unwrap().def().name.clone())
+      .collect();
+    assert_eq!(names[0], "Tiling And Offset");
+    assert!(reg.matching_nodes(&filter("qqzx")).is_empty());
+  }
+
+  #[test]
+  fn nodes_round_trip() -> anyhow::Result<()> {
+    let reg = NodeRegistry::build();
+    let nodes = reg.nodes();
+    assert!(!nodes.is_empty());
+    for def in nodes {
+      let node = reg.new_by_id(def.id)?;
+      let blob = serde_json::to_value(&node)?;
+      let loaded: Node = serde_json::from_value(blob.clone())
+        .map_err(|err| anyhow!("Failed to load node {:?}: {err}", def.name))?;
+      assert_eq!(loaded.id(), node.id(), "{}", def.name);
+      assert_eq!(serde_json::to_value(&loaded)?, blob, "{}", def.name);
+    }
+    Ok(())
+  }
+
+  #[test]
+  fn definition_defaults() -> anyhow::Result<()> {
+    // Definitions saved before the `supports_*` fields support both.
+    let def = NodeRegistry::build().new_by_name("Add")?.def().clone();
+    let mut blob = serde_json::to_value(&def)?;
+    let fields = blob.as_object_mut().expect("object");
+    fields.remove("supports_eval");
+    fields.remove("supports_compile");
+    let loaded: NodeDefinition = serde_json::from_value(blob)?;
+    assert!(loaded.supports_eval);
+    assert!(loaded.supports_compile);
+    Ok(())
+  }
+
+  #[test]
+  fn curated_registry() -> anyhow::Result<()> {
+    let defs = NodeRegistry::inventory_definitions();
+    assert_eq!(defs.len(), NodeRegistry::build().nodes().len());
+
+    let reg = NodeRegistry::from_definitions(
+      defs
+        .into_iter()
+        .filter(|def| ["Add", "Subtract"].contains(&def.name.as_str())),
+    );
+    assert_eq!(reg.nodes().len(), 2);
+    assert_eq!(reg.new_by_name("Add")?.def().name, "Add");
+    assert!(reg.new_by_name("Multiply").is_err());
+    assert_eq!(reg.category_tree().all_nodes().count(), 2);
+    assert!(NodeRegistry::empty().nodes().is_empty());
+    Ok(())
+  }
 }
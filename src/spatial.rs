@@ -0,0 +1,120 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::NodeId;
+
+/// Size of a grid cell in graph-space.
+pub const SPATIAL_CELL_SIZE: f32 = 512.0;
+
+type Cell = (i32, i32);
+
+/// A uniform grid that buckets nodes by their `area`.
+///
+/// Used by the editor to only render the nodes near the visible viewport.
+#[derive(Clone, Debug, Default)]
+pub struct SpatialIndex {
+  cells: HashMap<Cell, Vec<NodeId>>,
+  indexed: HashSet<NodeId>,
+  /// Nodes added since the last rebuild.  They need to be rendered once to get their size.
+  pending: HashSet<NodeId>,
+  valid: bool,
+}
+
+impl SpatialIndex {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Mark the index as stale.  It will be rebuilt on the next `update`.
+  pub fn invalidate(&mut self) {
+    self.valid = false;
+  }
+
+  pub fn is_valid(&self) -> bool {
+    self.valid
+  }
+
+  fn cell_range(area: emath::Rect) -> (Cell, Cell) {
+    let min = (area.min / SPATIAL_CELL_SIZE).floor();
+    let max = (area.max / SPATIAL_CELL_SIZE).floor();
+    ((min.x as i32, min.y as i32), (max.x as i32, max.y as i32))
+  }
+
+  /// Rebuild the index if it is stale.
+  pub fn update(&mut self, nodes: impl Iterator<Item = (NodeId, emath::Rect)>) {
+    if self.valid {
+      return;
+    }
+    self.cells.clear();
+    let mut indexed = HashSet::new();
+    for (id, area) in nodes {
+      if !self.indexed.contains(&id) {
+        self.pending.insert(id);
+      }
+      indexed.insert(id);
+      if !area.is_finite() {
+        // Nodes without a valid area are always treated as pending.
+        self.pending.insert(id);
+        continue;
+      }
+      let ((x0, y0), (x1, y1)) = Self::cell_range(area);
+      for y in y0..=y1 {
+        for x in x0..=x1 {
+          self.cells.entry((x, y)).or_default().push(id);
+        }
+      }
+    }
+    self.pending.retain(|id| indexed.contains(id));
+    self.indexed = indexed;
+    self.valid = true;
+  }
+
+  /// Take the nodes that haven't been rendered since they were indexed.
+  pub fn take_pending(&mut self) -> HashSet<NodeId> {
+    std::mem::take(&mut self.pending)
+  }
+
+  /// Get the nodes in the cells that intersect `area`.
+  pub fn query(&self, area: emath::Rect) -> HashSet<NodeId> {
+    let mut found = HashSet::new();
+    if !area.is_finite() {
+      return found;
+    }
+    let ((x0, y0), (x1, y1)) = Self::cell_range(area);
+    for y in y0..=y1 {
+      for x in x0..=x1 {
+        if let Some(ids) = self.cells.get(&(x, y)) {
+          found.extend(ids.iter().copied());
+        }
+      }
+    }
+    found
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use uuid::Uuid;
+
+  #[test]
+  fn query_viewport() {
+    let near = Uuid::new_v4();
+    let far = Uuid::new_v4();
+    let rect =
+      |x: f32, y: f32| emath::Rect::from_min_size(emath::pos2(x, y), emath::vec2(100., 50.));
+    let mut index = SpatialIndex::new();
+    index.update([(near, rect(10., 10.)), (far, rect(5000., 5000.))].into_iter());
+    assert_eq!(index.take_pending().len(), 2);
+
+    let view = emath::Rect::from_min_size(emath::pos2(0., 0.), emath::vec2(800., 600.));
+    let found = index.query(view);
+    assert!(found.contains(&near));
+    assert!(!found.contains(&far));
+
+    // Move the far node into view.
+    index.invalidate();
+    index.update([(near, rect(10., 10.)), (far, rect(300., 300.))].into_iter());
+    assert!(index.take_pending().is_empty());
+    assert!(index.query(view).contains(&far));
+  }
+}
@@ -0,0 +1,640 @@
+use serde::{Deserialize, Serialize};
+
+use anyhow::{anyhow, Result};
+
+#[cfg(feature = "egui")]
+use crate::ui::*;
+use crate::*;
+
+impl_node! {
+  mod subgraph_input_node {
+    NodeInfo {
+      name: "Subgraph Input",
+      category: ["Subgraph"],
+      // Only used inside subgraphs, hide it from the node finder.
+      deprecated: true,
+    }
+
+    /// An input port of a subgraph.  The value is provided by the parent `SubGraphNode`.
+    #[derive(Default)]
+    pub struct SubGraphInputNode {
+      /// Output.
+      pub out: Output<Dynamic>,
+    }
+
+    impl SubGraphInputNode {
+      pub fn new() -> Self {
+        Default::default()
+      }
+    }
+
+    impl NodeImpl for SubGraphInputNode {
+      fn cache_output(&self) -> bool {
+        // The parent subgraph node caches the port value in the execution state.
+        true
+      }
+
+      fn eval(&self, _graph: &NodeGraph, _execution: &mut NodeGraphExecution, _id: NodeId) -> Result<Value> {
+        Err(anyhow!("Subgraph input evaluated outside of a subgraph"))
+      }
+
+      fn compile(&self, _graph: &NodeGraph, _compile: &mut NodeGraphCompile, _id: NodeId) -> Result<()> {
+        // The output is defined by the parent subgraph node.
+        Ok(())
+      }
+    }
+  }
+}
+
+/// An input port of a `SubGraphNode`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubGraphInput {
+  pub name: String,
+  pub value_type: DataType,
+  /// The `SubGraphInputNode` inside the subgraph.
+  pub port: NodeId,
+  value: Value,
+  connected: Option<(OutputId, Option<DataType>)>,
+}
+
+impl SubGraphInput {
+  pub fn new(name: &str, value_type: DataType, port: NodeId) -> Self {
+    Self {
+      name: name.to_string(),
+      value_type,
+      port,
+      value: value_type.default_value(),
+      connected: None,
+    }
+  }
+
+  pub fn as_input(&self) -> Input {
+    match &self.connected {
+      Some((id, dt)) => Input::Connect(*id, *dt),
+      None => Input::Value(self.value.clone()),
+    }
+  }
+
+  pub fn set_input(&mut self, input: Input) -> Result<Option<OutputId>> {
+    let old = self.connected.take().map(|(id, _)| id);
+    match input {
+      Input::Disconnect => (),
+      Input::Value(val) => {
+        self.value = val.convert(self.value_type)?;
+      }
+      Input::Connect(id, dt) => {
+        if let Some(output_dt) = dt {
+          if !self.value_type.is_compatible(&output_dt) {
+            return Err(anyhow!("Incompatible output"));
+          }
+        }
+        self.connected = Some((id, dt));
+      }
+    }
+    Ok(old)
+  }
+}
+
+/// An output port of a `SubGraphNode`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubGraphOutput {
+  pub name: String,
+  pub value_type: DataType,
+  /// The output inside the subgraph.
+  pub output: OutputId,
+}
+
+lazy_static::lazy_static! {
+  pub static ref SUBGRAPH_DEFINITION: NodeDefinition = {
+    let mut def = NodeDefinition::new("SubGraphNode", module_path!(), |_, data| {
+      Ok(Box::new(match data {
+        Some(data) => {
+          let mut node = SubGraphNode::deserialize(data)?;
+          node.update_def();
+          node
+        }
+        None => SubGraphNode::new(),
+      }))
+    });
+    def.set_node_type_name("Subgraph");
+    def.set_docs("A collapsed group of nodes.");
+    def.category = vec!["Subgraph".to_string()];
+    // Subgraphs are created by collapsing a group.
+    def.deprecated = true;
+    def.source_file = file!().to_string();
+    def
+  };
+}
+
+register_node! {
+  SUBGRAPH_DEFINITION.clone()
+}
+
+/// A collapsed group of nodes.
+///
+/// The connections that crossed the group's boundary become the node's inputs and outputs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SubGraphNode {
+  graph: NodeGraph,
+  inputs: Vec<SubGraphInput>,
+  outputs: Vec<SubGraphOutput>,
+  #[serde(skip, default = "SubGraphNode::default_def")]
+  def: NodeDefinition,
+}
+
+impl Default for SubGraphNode {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl SubGraphNode {
+  pub fn new() -> Self {
+    Self::from_parts(NodeGraph::new(), Vec::new(), Vec::new())
+  }
+
+  pub fn from_parts(
+    graph: NodeGraph,
+    inputs: Vec<SubGraphInput>,
+    outputs: Vec<SubGraphOutput>,
+  ) -> Self {
+    let mut node = Self {
+      graph,
+      inputs,
+      outputs,
+      def: Self::default_def(),
+    };
+    node.update_def();
+    node
+  }
+
+  fn default_def() -> NodeDefinition {
+    SUBGRAPH_DEFINITION.clone()
+  }
+
+  /// Rebuild the node definition from the ports.
+  fn update_def(&mut self) {
+    self.def.inputs = self
+      .inputs
+      .iter()
+      .map(|port| InputDefinition::new(&port.name, port.value_type))
+      .collect();
+    self.def.outputs = self
+      .outputs
+      .iter()
+      .map(|port| OutputDefinition::new(&port.name, port.value_type))
+      .collect();
+  }
+
+  /// The contained nodes.
+  pub fn graph(&self) -> &NodeGraph {
+    &self.graph
+  }
+
+  pub fn inputs(&self) -> &[SubGraphInput] {
+    &self.inputs
+  }
+
+  pub fn outputs(&self) -> &[SubGraphOutput] {
+    &self.outputs
+  }
+
+  fn get_port(&self, key: &InputKey) -> Result<&SubGraphInput> {
+    let idx = self.get_input_idx(key)?;
+    self
+      .inputs
+      .get(idx as usize)
+      .ok_or_else(|| anyhow!("Invalid input key: {key:?}"))
+  }
+
+  fn get_output(&self, idx: u32) -> Result<&SubGraphOutput> {
+    self
+      .outputs
+      .get(idx as usize)
+      .ok_or_else(|| anyhow!("Invalid output: {idx}"))
+  }
+}
+
+impl NodeImpl for SubGraphNode {
+  fn clone_node(&self) -> Box<dyn NodeImpl> {
+    Box::new(self.clone())
+  }
+
+  fn def(&self) -> &NodeDefinition {
+    &self.def
+  }
+
+  fn as_subgraph(&self) -> Option<&SubGraphNode> {
+    Some(self)
+  }
+
+  fn renew_ids(&mut self) {
+    let (graph, ids) = match self.graph.clone_with_new_ids() {
+      Ok(res) => res,
+      Err(err) => {
+        log::error!("Failed to renew the subgraph's node ids: {err:?}");
+        return;
+      }
+    };
+    for port in &mut self.inputs {
+      if let Some(id) = ids.get(&port.port) {
+        port.port = *id;
+      }
+    }
+    for port in &mut self.outputs {
+      if let Some(id) = ids.get(&port.output.node) {
+        port.output = OutputId::new(*id, port.output.idx);
+      }
+    }
+    self.graph = graph;
+  }
+
+  fn get_node_input(&self, key: &InputKey) -> Result<Input> {
+    Ok(self.get_port(key)?.as_input())
+  }
+
+  fn set_node_input(&mut self, key: &InputKey, value: Input) -> Result<Option<OutputId>> {
+    let idx = self.get_input_idx(key)?;
+    let value = match self.def.get_input(&InputKey::Idx(idx)) {
+      Some(def) => def.check(value)?,
+      None => value,
+    };
+    self
+      .inputs
+      .get_mut(idx as usize)
+      .ok_or_else(|| anyhow!("Invalid input key: {key:?}"))?
+      .set_input(value)
+  }
+
+  fn eval(
+    &self,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+    id: NodeId,
+  ) -> Result<Value> {
+    self.eval_output(graph, execution, OutputId::new(id, 0))
+  }
+
+  fn eval_output(
+    &self,
+    graph: &NodeGraph,
+    execution: &mut NodeGraphExecution,
+    id: OutputId,
+  ) -> Result<Value> {
+    let output = self.get_output(id.idx)?.output;
+    // Provide the port values to the contained nodes.
+    for port in &self.inputs {
+      let value = match &port.connected {
+        Some((id, _)) => execution.eval_output(graph, *id)?,
+        None => port.value.clone(),
+      };
+      // The contained nodes cached the old port values.
+      execution.invalidate(&self.graph, port.port);
+      execution.set_output(OutputId::new(port.port, 0), value.convert(port.value_type)?);
+    }
+    execution.eval_output(&self.graph, output)
+  }
+
+  fn compile(&self, graph: &NodeGraph, compile: &mut NodeGraphCompile, id: NodeId) -> Result<()> {
+    // The contained nodes are compiled against the subgraph, so this node can't be
+    // replayed from the compile cache.
+    compile.mark_impure();
+    for port in &self.inputs {
+      let mut value = match &port.connected {
+        Some((id, _)) => compile.resolve_output(graph, *id)?,
+        None => compile.literal(port.value.compile()?),
+      };
+      value.convert(port.value_type)?;
+      compile.add_output(
+        OutputId::new(port.port, 0),
+        "subgraph_input",
+        value.value,
+        value.dt,
+      )?;
+    }
+    // Inline the contained nodes into the current block.
+    for (idx, output) in self.outputs.iter().enumerate() {
+      let value = compile.resolve_output(&self.graph, output.output)?;
+      compile.add_output(
+        OutputId::new(id, idx as u32),
+        "subgraph_output",
+        value.value,
+        value.dt,
+      )?;
+    }
+    Ok(())
+  }
+
+  #[cfg(feature = "egui")]
+  fn inputs_ui(
+    &mut self,
+    _concrete_type: &mut NodeConcreteType,
+    ui: &mut egui::Ui,
+    id: NodeId,
+    details: bool,
+  ) -> bool {
+    let mut changed = false;
+    for (idx, (def, port)) in self
+      .def
+      .inputs
+      .values()
+      .zip(self.inputs.iter_mut())
+      .enumerate()
+    {
+      ui.horizontal(|ui| {
+        if details {
+          ui.collapsing(&def.name, |ui| {
+            changed |= port.value.ui(ui);
+          });
+        } else if port.connected.is_some() {
+          ui.add(NodeSocket::input(id, idx as u32, true, def));
+          ui.label(&def.name);
+        } else {
+          ui.add(NodeSocket::input(id, idx as u32, false, def));
+          ui.collapsing(&def.name, |ui| {
+            changed |= port.value.ui(ui);
+          });
+        }
+      });
+    }
+    changed
+  }
+
+  #[cfg(feature = "egui")]
+  fn outputs_ui(
+    &mut self,
+    _concrete_type: &mut NodeConcreteType,
+    ui: &mut egui::Ui,
+    id: NodeId,
+    details: bool,
+  ) -> bool {
+    for (idx, def) in self.def.outputs.values().enumerate() {
+      ui.horizontal(|ui| {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+          if !details {
+            ui.add(NodeSocket::output(id, idx as u32, def, None));
+          }
+          ui.label(&def.name);
+        });
+      });
+    }
+    false
+  }
+
+  #[cfg(feature = "egui")]
+  fn parameters_ui(
+    &mut self,
+    _concrete_type: &mut NodeConcreteType,
+    _ui: &mut egui::Ui,
+    _id: NodeId,
+    _details: bool,
+  ) -> bool {
+    false
+  }
+}
+
+/// Make a port name unique by appending a number.
+pub(crate) fn subgraph_port_name<'a>(
+  names: impl Iterator<Item = &'a String> + Clone,
+  name: &str,
+) -> String {
+  let mut port_name = name.to_string();
+  let mut n = 1;
+  while names.clone().any(|other| *other == port_name) {
+    n += 1;
+    port_name = format!("{name} {n}");
+  }
+  port_name
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// `vec4 -> (add(vec4, float) * vec4) -> fragment` with `float`, `add` and `mul` grouped.
+  fn grouped_graph() -> Result<(NodeGraph, NodeGroupId, [NodeId; 5])> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let vec4 = graph.add(reg.new_by_name("Vector 4")?);
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let mul = graph.add(reg.new_by_name("Multiply")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_param(
+      vec4,
+      "Value",
+      ParameterValue::Value(Value::Vec4([1., 2., 3., 1.].into())),
+    )?;
+    graph.set_node_param(float, "Value", ParameterValue::Value(Value::F32(2.)))?;
+    graph.set_node_input(add, "A", vec4.into())?;
+    graph.set_node_input(add, "B", float.into())?;
+    graph.set_node_input(mul, "A", add.into())?;
+    graph.set_node_input(mul, "B", vec4.into())?;
+    graph.set_node_input(frag, "Color", mul.into())?;
+    graph.set_output(Some(frag));
+
+    let group = graph.add_group(NodeGroup::new());
+    for id in [float, add, mul] {
+      graph.get_mut(id)?.group_id = group;
+    }
+    Ok((graph, group, [vec4, float, add, mul, frag]))
+  }
+
+  #[test]
+  fn collapse_group_with_property() -> Result<()> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let add = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(add, "A", 2.0.into())?;
+    graph.set_node_input(add, "B", 3.0.into())?;
+    let (_, prop_node) = graph.promote_to_property(InputId::new(add, 0))?;
+    let out = graph.add(reg.new_by_name("Add")?);
+    graph.set_node_input(out, "A", add.into())?;
+    graph.set_output(Some(out));
+    let expected = graph.evaluate()?;
+
+    let group = graph.add_group(NodeGroup::new());
+    for id in [add, prop_node] {
+      graph.get_mut(id)?.group_id = group;
+    }
+    let sub = graph.collapse_group(group)?;
+    assert_eq!(graph.evaluate()?, expected);
+
+    graph.expand_subgraph(sub)?;
+    assert_eq!(graph.properties().count(), 1);
+    assert_eq!(graph.evaluate()?, expected);
+    Ok(())
+  }
+
+  #[test]
+  fn collapse_group() -> Result<()> {
+    let (mut graph, group, [vec4, _, add, _, frag]) = grouped_graph()?;
+    let expected = NodeGraphExecution::new().eval_graph(&graph)?;
+
+    let sub = graph.collapse_group(group)?;
+    assert!(!graph.contains(add));
+    let def = graph.get(sub)?.def();
+    // Both uses of `vec4` share one port.
+    assert_eq!(def.inputs.len(), 1);
+    assert_eq!(def.outputs.len(), 1);
+    assert_eq!(
+      graph.output_consumers(vec4.into()),
+      vec![InputId::new(sub, 0)]
+    );
+    assert_eq!(
+      graph.output_consumers(sub.into()),
+      vec![InputId::new(frag, 0)]
+    );
+
+    assert_eq!(NodeGraphExecution::new().eval_graph(&graph)?, expected);
+    let mut compile = NodeGraphCompile::new();
+    compile.push_new_block("main");
+    compile.resolve_output(&graph, OutputId::new(sub, 0))?;
+    assert!(compile.dump().contains("subgraph_input"));
+
+    // The subgraph survives a save/load round-trip.
+    let json = serde_json::to_string(&graph)?;
+    let loaded: NodeGraph = serde_json::from_str(&json)?;
+    assert_eq!(loaded.get(sub)?.def().inputs.len(), 1);
+    assert_eq!(NodeGraphExecution::new().eval_graph(&loaded)?, expected);
+    Ok(())
+  }
+
+  /// A scalar node feeding the color of the fragment output, in a group.
+  fn scalar_color_graph() -> Result<(NodeGraph, NodeGroupId, [NodeId; 3])> {
+    let reg = NodeRegistry::build();
+    let mut graph = NodeGraph::new();
+    let float = graph.add(reg.new_by_name("Float")?);
+    let add = graph.add(reg.new_by_name("Add")?);
+    let frag = graph.add(reg.new_by_name("Fragment")?);
+    graph.set_node_input(add, "A", float.into())?;
+    graph.set_node_input(frag, "Color", add.into())?;
+    graph.set_output(Some(frag));
+    let group = graph.add_group(NodeGroup::new());
+    graph.get_mut(add)?.group_id = group;
+    Ok((graph, group, [float, add, frag]))
+  }
+
+  #[test]
+  fn collapse_group_failure() -> Result<()> {
+    let (mut graph, group, [_, add, frag]) = scalar_color_graph()?;
+    let connections = graph.stats().connections;
+
+    // The scalar output of the subgraph can't be connected to the color under the
+    // strict policy, the graph must be left unchanged.
+    graph.set_connection_policy(ConnectionPolicy::Strict);
+    assert!(graph.collapse_group(group).is_err());
+    assert!(graph.contains(add));
+    assert!(graph.get_group(group).is_ok());
+    assert_eq!(graph.stats().connections, connections);
+    assert_eq!(
+      graph.output_consumers(add.into()),
+      vec![InputId::new(frag, 0)]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn expand_subgraph_failure() -> Result<()> {
+    let (mut graph, group, [_, _, frag]) = scalar_color_graph()?;
+    let sub = graph.collapse_group(group)?;
+    let connections = graph.stats().connections;
+
+    // Rewiring the color to the contained node fails under the strict policy, the
+    // subgraph node must be kept.
+    graph.set_connection_policy(ConnectionPolicy::Strict);
+    assert!(graph.expand_subgraph(sub).is_err());
+    assert!(graph.contains(sub));
+    assert_eq!(graph.stats().connections, connections);
+    assert_eq!(
+      graph.output_consumers(OutputId::new(sub, 0)),
+      vec![InputId::new(frag, 0)]
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn subgraph_instances() -> Result<()> {
+    let (mut graph, group, _) = grouped_graph()?;
+    let sub = graph.collapse_group(group)?;
+    let copy = graph.add(graph.get(sub)?.duplicate());
+    let value = Value::Vec4([4., 3., 2., 1.].into());
+    graph.set_node_input(copy, 0, value.into())?;
+    // The contained nodes have new ids.
+    let (a, b) = (graph.get(sub)?, graph.get(copy)?);
+    let (a, b) = (
+      a.as_subgraph().expect("subgraph"),
+      b.as_subgraph().expect("subgraph"),
+    );
+    assert_ne!(a.inputs()[0].port, b.inputs()[0].port);
+    assert_ne!(a.outputs()[0].output, b.outputs()[0].output);
+    assert!(!a.graph().contains(b.outputs()[0].output.node()));
+
+    // Each instance uses its own port values.
+    let mut execution = NodeGraphExecution::new();
+    let a = execution.eval_output(&graph, OutputId::new(sub, 0))?;
+    let b = execution.eval_output(&graph, OutputId::new(copy, 0))?;
+    assert_ne!(a, b);
+    assert_eq!(
+      NodeGraphExecution::new().eval_output(&graph, OutputId::new(copy, 0))?,
+      b
+    );
+
+    let mut compile = NodeGraphCompile::new();
+    compile.push_new_block("main");
+    let a = compile.resolve_output(&graph, OutputId::new(sub, 0))?;
+    let b = compile.resolve_output(&graph, OutputId::new(copy, 0))?;
+    assert_ne!(a.value, b.value);
+    assert!(compile.dump().contains("vec4<f32>(4.0, 3.0, 2.0, 1.0)"));
+    Ok(())
+  }
+
+  #[test]
+  fn expand_subgraph() -> Result<()> {
+    let (mut graph, group, [vec4, float, add, mul, frag]) = grouped_graph()?;
+    let expected = NodeGraphExecution::new().eval_graph(&graph)?;
+    let sub = graph.collapse_group(group)?;
+
+    let ids = graph.expand_subgraph(sub)?;
+    assert!(!graph.contains(sub));
+    assert_eq!(ids.len(), 3);
+    for id in [float, add, mul] {
+      assert!(!ids.contains(&id));
+    }
+    // The port's consumers are connected directly to `vec4` again.
+    let consumers = graph.output_consumers(vec4.into());
+    assert_eq!(consumers.len(), 2);
+    assert!(consumers.iter().all(|input| ids.contains(&input.node())));
+    // The consumer of the subgraph's output is connected to the new `mul` node.
+    let output = graph.get_node_input(frag, "Color")?;
+    let Input::Connect(output, _) = output else {
+      panic!("Fragment should be connected: {output:?}");
+    };
+    assert!(ids.contains(&output.node()));
+    assert_eq!(graph.get(output.node())?.def().name, "Multiply");
+
+    assert_eq!(NodeGraphExecution::new().eval_graph(&graph)?, expected);
+    Ok(())
+  }
+
+  #[test]
+  fn expand_subgraph_port_value() -> Result<()> {
+    let (mut graph, group, [vec4, ..]) = grouped_graph()?;
+    let sub = graph.collapse_group(group)?;
+    // Replace the port's connection with a value.
+    let value = Value::Vec4([4., 3., 2., 1.].into());
+    graph.set_node_input(sub, 0, value.clone().into())?;
+    let expected = NodeGraphExecution::new().eval_graph(&graph)?;
+
+    let ids = graph.expand_subgraph(sub)?;
+    assert!(graph.output_consumers(vec4.into()).is_empty());
+    for id in ids {
+      let node = graph.get(id)?;
+      if node.def().name == "Add" {
+        assert!(matches!(node.get_input("A")?, Input::Value(v) if v == value));
+      }
+    }
+    assert_eq!(NodeGraphExecution::new().eval_graph(&graph)?, expected);
+    Ok(())
+  }
+}
@@ -0,0 +1,321 @@
+//! Shader boilerplate used by the output nodes.
+//!
+//! The output nodes (`Fragment`, `Vertex`) take the imports, bindings and entry-point
+//! wrappers from the `ShaderTemplate` of the `NodeGraphCompile`.  This allows the same
+//! graph to target Bevy's PBR pipeline (the default) or a plain WGSL shader.
+
+/// Placeholder for the fragment color in `ShaderTemplate::fragment_color`.
+pub const TEMPLATE_COLOR: &str = "{color}";
+/// Placeholder for the position offset in `ShaderTemplate::vertex_offset`.
+pub const TEMPLATE_OFFSET: &str = "{offset}";
+
+const BEVY_PBR_IMPORTS: &str = r#"
+#import bevy_pbr::{
+	pbr_fragment::pbr_input_from_standard_material,
+	pbr_functions::alpha_discard,
+	pbr_bindings,
+	mesh_view_bindings::view,
+	mesh_functions,
+	skinning,
+	view_transformations::position_world_to_clip,
+}
+#import bevy_render::instance_index::get_instance_index
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+	prepass_io::{Vertex, VertexOutput, FragmentOutput},
+	pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+	forward_io::{Vertex, VertexOutput, FragmentOutput},
+	pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+	pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
+}
+#endif
+"#;
+
+const BEVY_PBR_BINDINGS: &str = r#"
+struct ShaderGraphMaterialUniform {
+  prop_vec4: vec4<f32>,
+};
+
+@group(2) @binding(100) var<uniform> material: ShaderGraphMaterialUniform;
+"#;
+
+const BEVY_PBR_FRAGMENT_BEGIN: &str = r#"
+@fragment
+fn fragment(
+  v_in: VertexOutput,
+  @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+	var in = v_in;
+
+	// get PbrInput from StandardMaterial bindings.
+	var pbr_input = pbr_input_from_standard_material(in, is_front);
+"#;
+
+const BEVY_PBR_FRAGMENT_COLOR: &str = r#"
+  // Color from graph input `color`.
+  pbr_input.material.base_color = {color};
+"#;
+
+const BEVY_PBR_FRAGMENT_END: &str = r#"
+	// alpha discard
+  pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+	// No lighting in deferred mode.
+	let out = deferred_output(in, pbr_input);
+#else
+	var out: FragmentOutput;
+  if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+		out.color = apply_pbr_lighting(pbr_input);
+	} else {
+		out.color = pbr_input.material.base_color;
+  }
+
+	// Apply PBR post processing.
+	out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+  return out;
+}
+"#;
+
+const BEVY_PBR_VERTEX_BEGIN: &str = r#"
+@vertex
+fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
+  var out: VertexOutput;
+  var vertex = vertex_no_morph;
+"#;
+
+const BEVY_PBR_VERTEX_OFFSET: &str = r#"
+  // Position offset from graph input `position_offset`.
+  vertex.position = vertex.position + {offset};
+"#;
+
+const BEVY_PBR_VERTEX_END: &str = r#"
+#ifdef SKINNED
+  var world_from_local = skinning::skin_model(vertex.joint_indices, vertex.joint_weights);
+#else
+  var world_from_local = mesh_functions::get_world_from_local(vertex.instance_index);
+#endif
+
+#ifdef VERTEX_NORMALS
+#ifdef SKINNED
+  out.world_normal = skinning::skin_normals(world_from_local, vertex.normal);
+#else
+  out.world_normal = mesh_functions::mesh_normal_local_to_world(vertex.normal, get_instance_index(vertex.instance_index));
+#endif
+#endif
+
+#ifdef VERTEX_POSITIONS
+  out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
+  out.position = position_world_to_clip(out.world_position.xyz);
+#endif
+
+#ifdef VERTEX_UVS_A
+  out.uv = vertex.uv;
+#endif
+#ifdef VERTEX_UVS_B
+  out.uv_b = vertex.uv_b;
+#endif
+
+#ifdef VERTEX_TANGENTS
+  out.world_tangent = mesh_functions::mesh_tangent_local_to_world(world_from_local, vertex.tangent, get_instance_index(vertex.instance_index));
+#endif
+
+#ifdef VERTEX_COLORS
+  out.color = vertex.color;
+#endif
+
+#ifdef VERTEX_OUTPUT_INSTANCE_INDEX
+  out.instance_index = get_instance_index(vertex.instance_index);
+#endif
+
+  return out;
+}
+"#;
+
+const BARE_BINDINGS: &str = r#"
+struct ShaderGraphMaterialUniform {
+  prop_vec4: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> material: ShaderGraphMaterialUniform;
+"#;
+
+const BARE_FRAGMENT_BEGIN: &str = r#"
+@fragment
+fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+"#;
+
+const BARE_FRAGMENT_COLOR: &str = r#"
+  // Color from graph input `color`.
+  return {color};
+"#;
+
+const BARE_FRAGMENT_END: &str = r#"}
+"#;
+
+const BARE_VERTEX_BEGIN: &str = r#"
+@vertex
+fn vertex(@location(0) vertex_position: vec3<f32>) -> @builtin(position) vec4<f32> {
+  var position = vertex_position;
+"#;
+
+const BARE_VERTEX_OFFSET: &str = r#"
+  // Position offset from graph input `position_offset`.
+  position = position + {offset};
+"#;
+
+const BARE_VERTEX_END: &str = r#"
+  return vec4<f32>(position, 1.0);
+}
+"#;
+
+/// The pipeline stage of a shader entry-point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+  Vertex,
+  Fragment,
+}
+
+/// A resource binding declared in `ShaderTemplate::bindings`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderBinding {
+  pub group: u32,
+  pub binding: u32,
+  /// The WGSL variable name.
+  pub name: String,
+}
+
+impl ShaderBinding {
+  pub fn new(group: u32, binding: u32, name: &str) -> Self {
+    Self {
+      group,
+      binding,
+      name: name.to_string(),
+    }
+  }
+}
+
+/// The shader boilerplate around the code generated from the graph.
+#[derive(Clone, Debug)]
+pub struct ShaderTemplate {
+  /// Appended once to the `imports` block.  Skipped when empty.
+  pub imports: String,
+  /// Appended once to the `bindings` block.  Skipped when empty.
+  pub bindings: String,
+  /// The resource bindings declared by `bindings`.
+  pub binding_layout: Vec<ShaderBinding>,
+  /// Name of the fragment entry-point function declared by `fragment_begin`.
+  pub fragment_entry: String,
+  /// Start of the fragment entry-point.
+  pub fragment_begin: String,
+  /// Use the fragment color, `{color}` is replaced with the compiled color.
+  pub fragment_color: String,
+  /// End of the fragment entry-point.
+  pub fragment_end: String,
+  /// Name of the vertex entry-point function declared by `vertex_begin`.
+  pub vertex_entry: String,
+  /// Start of the vertex entry-point.
+  pub vertex_begin: String,
+  /// Apply the position offset, `{offset}` is replaced with the compiled offset.
+  pub vertex_offset: String,
+  /// End of the vertex entry-point.
+  pub vertex_end: String,
+}
+
+impl Default for ShaderTemplate {
+  fn default() -> Self {
+    Self::bevy_pbr()
+  }
+}
+
+impl ShaderTemplate {
+  /// Bevy PBR material shader.
+  pub fn bevy_pbr() -> Self {
+    Self {
+      imports: BEVY_PBR_IMPORTS.to_string(),
+      bindings: BEVY_PBR_BINDINGS.to_string(),
+      binding_layout: vec![ShaderBinding::new(2, 100, "material")],
+      fragment_entry: "fragment".to_string(),
+      fragment_begin: BEVY_PBR_FRAGMENT_BEGIN.to_string(),
+      fragment_color: BEVY_PBR_FRAGMENT_COLOR.to_string(),
+      fragment_end: BEVY_PBR_FRAGMENT_END.to_string(),
+      vertex_entry: "vertex".to_string(),
+      vertex_begin: BEVY_PBR_VERTEX_BEGIN.to_string(),
+      vertex_offset: BEVY_PBR_VERTEX_OFFSET.to_string(),
+      vertex_end: BEVY_PBR_VERTEX_END.to_string(),
+    }
+  }
+
+  /// Plain WGSL shader without any imports.
+  ///
+  /// Nodes that use Bevy's vertex outputs (`Uv`, `View Direction`, ...) will not work with it.
+  pub fn bare() -> Self {
+    Self {
+      imports: String::new(),
+      bindings: BARE_BINDINGS.to_string(),
+      binding_layout: vec![ShaderBinding::new(0, 0, "material")],
+      fragment_entry: "fragment".to_string(),
+      fragment_begin: BARE_FRAGMENT_BEGIN.to_string(),
+      fragment_color: BARE_FRAGMENT_COLOR.to_string(),
+      fragment_end: BARE_FRAGMENT_END.to_string(),
+      vertex_entry: "vertex".to_string(),
+      vertex_begin: BARE_VERTEX_BEGIN.to_string(),
+      vertex_offset: BARE_VERTEX_OFFSET.to_string(),
+      vertex_end: BARE_VERTEX_END.to_string(),
+    }
+  }
+
+  /// The fragment color code for `color`.
+  pub fn fragment_color(&self, color: &str) -> String {
+    self.fragment_color.replace(TEMPLATE_COLOR, color)
+  }
+
+  /// The vertex position offset code for `offset`.
+  pub fn vertex_offset(&self, offset: &str) -> String {
+    self.vertex_offset.replace(TEMPLATE_OFFSET, offset)
+  }
+}
+
+/// The texture format of the graph's fragment output.
+///
+/// Only metadata for the host, it's used to configure the render target.
+#[derive(
+  Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum OutputFormat {
+  #[default]
+  Rgba8UnormSrgb,
+  Rgba8Unorm,
+  Rgba16Float,
+  Rgba32Float,
+}
+
+impl OutputFormat {
+  pub const ALL: [Self; 4] = [
+    Self::Rgba8UnormSrgb,
+    Self::Rgba8Unorm,
+    Self::Rgba16Float,
+    Self::Rgba32Float,
+  ];
+
+  /// The WGSL texel format name.  `None` for formats that can't be used for storage textures.
+  pub fn wgsl_name(&self) -> Option<&'static str> {
+    match self {
+      Self::Rgba8UnormSrgb => None,
+      Self::Rgba8Unorm => Some("rgba8unorm"),
+      Self::Rgba16Float => Some("rgba16float"),
+      Self::Rgba32Float => Some("rgba32float"),
+    }
+  }
+
+  /// Returns true for floating-point formats that can store values outside `0..1`.
+  pub fn is_hdr(&self) -> bool {
+    matches!(self, Self::Rgba16Float | Self::Rgba32Float)
+  }
+}
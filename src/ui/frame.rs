@@ -6,6 +6,9 @@ use crate::GetId;
 pub struct NodeFrameStyle {
   pub fill: egui::Color32,
   pub selected: egui::Color32,
+  pub highlight: egui::Color32,
+  /// Border and badge of the graph's output node.
+  pub output: egui::Color32,
 }
 
 impl Default for NodeFrameStyle {
@@ -13,6 +16,8 @@ impl Default for NodeFrameStyle {
     Self {
       fill: egui::Color32::from_gray(50),
       selected: egui::Color32::WHITE,
+      highlight: egui::Color32::from_rgb(255, 200, 0),
+      output: egui::Color32::from_rgb(80, 200, 120),
     }
   }
 }
@@ -29,12 +34,26 @@ pub enum NodeAction {
   LeaveGroup(Uuid),
   /// Add selected nodes to the group.
   JoinGroup(Uuid),
+  /// Collapse the group into a subgraph node.
+  Collapse,
+  /// Expand a subgraph node into its nodes.
+  Expand,
+  /// Make the node the graph's output.
+  SetOutput,
 }
 
 #[derive(Clone, Debug)]
 pub struct NodeFrameState {
   pub updated: bool,
   pub selected: bool,
+  /// Highlight the frame (for example when it matches a search).
+  pub highlight: bool,
+  /// Locked frames can't be moved or resized (for example nodes in a locked group).
+  pub locked: bool,
+  /// The frame is one of the graph's output nodes.
+  pub output: bool,
+  /// The frame is the graph's fragment output node, see `NodeGraph::output`.
+  pub fragment_output: bool,
   pub edit_title: bool,
   pub drag: Option<NodeFrameDragState>,
 }
@@ -44,6 +63,10 @@ impl Default for NodeFrameState {
     Self {
       updated: true,
       selected: false,
+      highlight: false,
+      locked: false,
+      output: false,
+      fragment_output: false,
       edit_title: false,
       drag: None,
     }
@@ -62,19 +85,40 @@ impl NodeFrameState {
     self.drag == Some(NodeFrameDragState::Drag)
   }
 
-  pub fn node_selected(&mut self, rect: emath::Rect, selecting: &NodeSelectingState) -> bool {
+  /// Update the selected state from the selection box.  With `contained` the box must
+  /// contain the whole `rect`, otherwise intersecting is enough.
+  pub fn node_selected(
+    &mut self,
+    rect: emath::Rect,
+    selecting: &NodeSelectingState,
+    contained: bool,
+  ) -> bool {
+    let hit = |area: &emath::Rect| {
+      if contained {
+        area.contains_rect(rect)
+      } else {
+        area.intersects(rect)
+      }
+    };
     match selecting {
       NodeSelectingState::Selecting {
-        area, clear_old, ..
+        area,
+        clear_old,
+        remove,
+        ..
       } => {
         if self.selected && *clear_old {
           self.selected = false;
         }
-        self.selected | area.intersects(rect)
+        if *remove {
+          self.selected && !hit(area)
+        } else {
+          self.selected | hit(area)
+        }
       }
-      NodeSelectingState::Select { area } => {
-        if area.intersects(rect) {
-          self.selected = true;
+      NodeSelectingState::Select { area, remove } => {
+        if hit(area) {
+          self.selected = !remove;
         }
         self.selected
       }
@@ -120,7 +164,15 @@ impl ResizeState {
     }
   }
 
-  pub fn resize_rect(&self, mut rect: emath::Rect, delta: emath::Vec2) -> emath::Rect {
+  /// Move the dragged edges by `delta`.  The size is clamped to `min_size` and `max_size`,
+  /// the opposite edges don't move.
+  pub fn resize_rect(
+    &self,
+    mut rect: emath::Rect,
+    delta: emath::Vec2,
+    min_size: emath::Vec2,
+    max_size: emath::Vec2,
+  ) -> emath::Rect {
     if self.top {
       rect.set_top(rect.top() + delta.y);
     }
@@ -133,6 +185,17 @@ impl ResizeState {
     if self.left {
       rect.set_left(rect.left() + delta.x);
     }
+    let size = rect.size().max(min_size).min(max_size.max(min_size));
+    if self.left {
+      rect.set_left(rect.right() - size.x);
+    } else {
+      rect.set_right(rect.left() + size.x);
+    }
+    if self.top {
+      rect.set_top(rect.bottom() - size.y);
+    } else {
+      rect.set_bottom(rect.top() + size.y);
+    }
     rect
   }
 }
@@ -158,6 +221,16 @@ pub trait NodeFrame: GetId {
     NodeFrameStyle::default()
   }
 
+  /// Frame style when a `GraphTheme` is used.  `theme` is the theme's default style.
+  fn themed_frame_style(&self, theme: &NodeFrameStyle) -> NodeFrameStyle {
+    theme.clone()
+  }
+
+  /// Only select the frame when the selection box contains all of it.
+  fn select_contained(&self) -> bool {
+    false
+  }
+
   /// Automatically fit the frame's contents.
   fn auto_size(&self) -> bool {
     false
@@ -168,11 +241,27 @@ pub trait NodeFrame: GetId {
     true
   }
 
+  /// The smallest size the frame can be resized to, in graph-space.
+  /// Defaults to `NodeStyle::node_min_size`.
+  fn min_size(&self, node_style: &NodeStyle) -> emath::Vec2 {
+    node_style.node_min_size / node_style.zoom
+  }
+
+  /// The largest size the frame can be resized to, in graph-space.
+  fn max_size(&self, _node_style: &NodeStyle) -> emath::Vec2 {
+    emath::Vec2::INFINITY
+  }
+
   /// Allow moving.
   fn movable(&self) -> bool {
     true
   }
 
+  /// Show a lock icon in the title bar.
+  fn is_locked(&self) -> bool {
+    false
+  }
+
   /// Handle moving - either the frame is being dragged or it's parent group is moving.
   fn handle_move(&mut self, delta: emath::Vec2) {
     if self.movable() {
@@ -216,6 +305,7 @@ pub trait NodeFrame: GetId {
   ) -> Option<NodeAction> {
     let node_style = NodeStyle::get(ui);
     let zoom = node_style.zoom;
+    let (min_size, max_size) = (self.min_size(&node_style), self.max_size(&node_style));
     // Zoom and translate frame to Screen space.
     let mut rect = self.rect();
     let updated = self.take_updated(state);
@@ -231,6 +321,7 @@ pub trait NodeFrame: GetId {
 
     // Allocate a response for the whole frame area.
     let resp = ui.interact(rect, ui.id(), egui::Sense::click_and_drag());
+    resp.widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Other, true, self.title()));
 
     // Only render this frame if it is visible or the frame was updated.
     if !updated && !ui.is_rect_visible(rect) {
@@ -240,7 +331,8 @@ pub trait NodeFrame: GetId {
     }
 
     // Is the frame currently selected?
-    let selected = graph.selecting(|selecting| state.node_selected(rect, selecting));
+    let contained = self.select_contained();
+    let selected = graph.selecting(|selecting| state.node_selected(rect, selecting, contained));
 
     // Render frame UI.
     self.frame_ui(ui, selected, state, node_style);
@@ -248,7 +340,7 @@ pub trait NodeFrame: GetId {
     // Handle events.
     if resp.clicked() {
       state.selected = !state.selected;
-    } else if resp.dragged() {
+    } else if resp.dragged() && !state.locked {
       let delta = resp.drag_delta() / zoom;
       match state.drag.clone() {
         Some(NodeFrameDragState::Drag) => {
@@ -256,7 +348,7 @@ pub trait NodeFrame: GetId {
           resp.scroll_to_me(None);
         }
         Some(NodeFrameDragState::Resize(state)) => {
-          self.set_rect(state.resize_rect(self.rect(), delta));
+          self.set_rect(state.resize_rect(self.rect(), delta, min_size, max_size));
           resp.scroll_to_me(None);
           state.set_cursor(ui);
         }
@@ -276,7 +368,9 @@ pub trait NodeFrame: GetId {
           rect
         };
         // Drag the frame if inside the margin area.
-        if inside.contains(pointer) {
+        if state.locked {
+          state.drag = None;
+        } else if inside.contains(pointer) {
           state.drag = Some(NodeFrameDragState::Drag);
           self.handle_move(resp.drag_delta() / zoom);
         } else if self.resizable() && rect.contains(pointer) {
@@ -342,11 +436,20 @@ pub trait NodeFrame: GetId {
   ) {
     // Window-style frame.
     let style = ui.style();
-    let frame_style = self.frame_style();
+    let frame_style = match GraphTheme::get(ui) {
+      Some(theme) => self.themed_frame_style(&theme.frame_style),
+      None => self.frame_style(),
+    };
     let mut frame = egui::Frame::window(style);
     frame.shadow = Default::default();
     if selected {
       frame.stroke.color = frame_style.selected;
+    } else if state.highlight {
+      frame.stroke.color = frame_style.highlight;
+      frame.stroke.width *= 2.0;
+    } else if state.output {
+      frame.stroke.color = frame_style.output;
+      frame.stroke.width *= 2.0;
     }
 
     frame.fill(frame_style.fill).show(ui, |ui| {
@@ -365,7 +468,14 @@ pub trait NodeFrame: GetId {
             resp.request_focus();
           } else {
             let rect = ui.available_rect_before_wrap();
+            if self.is_locked() {
+              ui.label("🔒");
+            }
             ui.label(self.title());
+            if state.output {
+              ui.colored_label(frame_style.output, "⏵ Output")
+                .on_hover_text("Graph output");
+            }
             // Manually detect click.  To fix issue with context menu.
             if ui.rect_contains_pointer(rect) {
               if ui.input(|i| {
@@ -387,3 +497,77 @@ pub trait NodeFrame: GetId {
     ui.set_min_size(ui.available_size());
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn box_deselect() {
+    let rect = |x: f32| emath::Rect::from_min_size(emath::pos2(x, 0.), emath::vec2(10., 10.));
+    let (inside, outside) = (rect(0.), rect(100.));
+    let mut a = NodeFrameState {
+      selected: true,
+      ..Default::default()
+    };
+    let mut b = a.clone();
+
+    let mut selecting = NodeSelectingState::default();
+    selecting.drag_started(emath::pos2(-5., -5.), true, true);
+    selecting.update(emath::pos2(20., 20.));
+    // Removing from the selection never clears the old selection.
+    assert!(!a.node_selected(inside, &selecting, false));
+    assert!(b.node_selected(outside, &selecting, false));
+    selecting.drag_released();
+    assert!(!a.node_selected(inside, &selecting, false));
+    assert!(b.node_selected(outside, &selecting, false));
+    assert!(!a.selected && b.selected);
+  }
+
+  #[test]
+  fn resize_constraints() {
+    let rect = emath::Rect::from_min_size(emath::pos2(0., 0.), emath::vec2(100., 100.));
+    let (min, max) = (emath::vec2(50., 20.), emath::vec2(300., 200.));
+    let bottom_right = ResizeState {
+      top: false,
+      right: true,
+      bottom: true,
+      left: false,
+    };
+    let resized = bottom_right.resize_rect(rect, emath::vec2(-80., -90.), min, max);
+    assert_eq!(resized, emath::Rect::from_min_size(rect.min, min));
+    let resized = bottom_right.resize_rect(rect, emath::vec2(500., 500.), min, max);
+    assert_eq!(resized, emath::Rect::from_min_size(rect.min, max));
+    // Dragging the top left corner keeps the bottom right corner.
+    let top_left = ResizeState {
+      top: true,
+      right: false,
+      bottom: false,
+      left: true,
+    };
+    let resized = top_left.resize_rect(rect, emath::vec2(90., 90.), min, max);
+    assert_eq!(resized, emath::Rect::from_min_max(rect.max - min, rect.max));
+    let resized = top_left.resize_rect(rect, emath::vec2(10., -10.), min, max);
+    assert_eq!(
+      resized,
+      emath::Rect::from_min_max(emath::pos2(10., -10.), rect.max)
+    );
+  }
+
+  #[test]
+  fn box_select_contained() {
+    let group = emath::Rect::from_min_size(emath::pos2(0., 0.), emath::vec2(100., 100.));
+    let mut frame = NodeFrameState::default();
+    let mut selecting = NodeSelectingState::default();
+    // A box inside the group doesn't select it.
+    selecting.drag_started(emath::pos2(10., 10.), true, false);
+    selecting.update(emath::pos2(20., 20.));
+    assert!(frame.node_selected(group, &selecting, false));
+    assert!(!frame.node_selected(group, &selecting, true));
+    // A box around the group does.
+    selecting.drag_started(emath::pos2(-10., -10.), true, false);
+    selecting.update(emath::pos2(110., 110.));
+    selecting.drag_released();
+    assert!(frame.node_selected(group, &selecting, true));
+  }
+}
@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
@@ -7,17 +8,38 @@ use indexmap::IndexMap;
 use uuid::Uuid;
 
 use crate::node::{InputId, NodeId, OutputId};
-use crate::values::DataType;
+use crate::values::{ConnectionPolicy, DataType, Value};
 use crate::{GetId, InputDefinition, OutputDefinition};
 
 mod frame;
+mod theme;
 mod zoom;
 pub use frame::*;
+pub use theme::*;
 pub use zoom::*;
 
 const NODE_STYLE: &'static str = "NodeStyle";
 const NODE_GRAPH_META: &'static str = "NodeGraphMeta";
 
+/// How connections between sockets are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConnectionRouting {
+  /// Cubic bezier curve.
+  #[default]
+  Bezier,
+  /// Straight line between the sockets.
+  Straight,
+  /// Right-angle polyline between the sockets.
+  Orthogonal,
+}
+
+/// Offset from the top of a node's frame to the center of its first socket row.
+///
+/// Matches the default egui style.  Used to compute socket positions without rendering.
+pub const NODE_SOCKET_FIRST_ROW: f32 = 36.0;
+/// Height of a socket row, including the item spacing.
+pub const NODE_SOCKET_ROW_HEIGHT: f32 = 21.0;
+
 #[derive(Clone, Debug)]
 pub struct NodeStyle {
   pub node_min_size: emath::Vec2,
@@ -25,6 +47,12 @@ pub struct NodeStyle {
   pub input_to_edge: f32,
   pub output_to_edge: f32,
   pub curve_offset: f32,
+  pub routing: ConnectionRouting,
+  /// Animate dots along the connections in the direction of the data flow.
+  pub flow_animation: bool,
+  /// When nodes are selected, fade the connections that don't touch a selected node.
+  pub dim_unselected: bool,
+  pub connection_policy: ConnectionPolicy,
   pub zoom: f32,
 }
 
@@ -36,6 +64,10 @@ impl Default for NodeStyle {
       input_to_edge: -13.0,
       output_to_edge: 17.0,
       curve_offset: 10.0,
+      routing: ConnectionRouting::default(),
+      flow_animation: false,
+      dim_unselected: false,
+      connection_policy: ConnectionPolicy::default(),
       zoom: 1.0,
     }
   }
@@ -84,6 +116,9 @@ pub struct NodeConnection {
   pub zoom: f32,
   pub line_stroke: egui::Stroke,
   pub curve_offset: f32,
+  pub routing: ConnectionRouting,
+  pub flow_animation: bool,
+  pub dim_unselected: bool,
 }
 
 impl NodeConnection {
@@ -93,6 +128,9 @@ impl NodeConnection {
       zoom: style.zoom,
       line_stroke: style.line_stroke,
       curve_offset: style.curve_offset,
+      routing: style.routing,
+      flow_animation: style.flow_animation,
+      dim_unselected: style.dim_unselected,
     }
   }
 
@@ -101,6 +139,71 @@ impl NodeConnection {
     (pos * self.zoom).to_pos2() + self.ui_min
   }
 
+  /// Route a right-angle polyline from the input socket (`start`) to the output socket (`end`).
+  fn orthogonal_route(&self, start: emath::Pos2, end: emath::Pos2) -> Vec<emath::Pos2> {
+    let offset = self.curve_offset;
+    if end.x + offset <= start.x - offset {
+      // Z-shape: the output is to the left of the input.
+      let mid_x = (start.x + end.x) / 2.0;
+      if start.y == end.y {
+        vec![start, end]
+      } else {
+        vec![
+          start,
+          emath::pos2(mid_x, start.y),
+          emath::pos2(mid_x, end.y),
+          end,
+        ]
+      }
+    } else {
+      // The output is to the right of the input, route around between the sockets.
+      let mid_y = (start.y + end.y) / 2.0;
+      let in_x = start.x - offset;
+      let out_x = end.x + offset;
+      vec![
+        start,
+        emath::pos2(in_x, start.y),
+        emath::pos2(in_x, mid_y),
+        emath::pos2(out_x, mid_y),
+        emath::pos2(out_x, end.y),
+        end,
+      ]
+    }
+  }
+
+  /// Draw dots moving along the connection from the output (last point) to the input (first point).
+  fn draw_flow(
+    &self,
+    ui: &egui::Ui,
+    painter: &egui::Painter,
+    points: &[emath::Pos2],
+    stroke: egui::Stroke,
+  ) {
+    let spacing = 30.0 * self.zoom;
+    let speed = 40.0 * self.zoom;
+    let time = ui.input(|i| i.time) as f32;
+    let phase = (time * speed) % spacing;
+    let radius = stroke.width * 1.5;
+    // Walk the segments backwards, placing a dot every `spacing` starting at `phase`.
+    let mut next = phase;
+    let mut walked = 0.0;
+    for segment in points.windows(2).rev() {
+      let (a, b) = (segment[1], segment[0]);
+      let len = a.distance(b);
+      while next <= walked + len {
+        let t = if len > 0.0 {
+          (next - walked) / len
+        } else {
+          0.0
+        };
+        painter.circle_filled(a.lerp(b, t), radius, stroke.color);
+        next += spacing;
+      }
+      walked += len;
+    }
+    ui.ctx().request_repaint();
+  }
+
   pub fn draw(
     &self,
     ui: &mut egui::Ui,
@@ -109,20 +212,31 @@ impl NodeConnection {
     color: Option<ecolor::Color32>,
     highlight: bool,
   ) -> Option<emath::Rect> {
-    let mut offset = (start - end) * 0.2;
-    offset.x = self.curve_offset + offset.y.abs() + offset.x.abs();
-    let start2 = start - offset;
-    let end2 = end + offset;
-    let mut shape = egui::epaint::CubicBezierShape {
-      points: [start, start2, end2, end],
-      closed: false,
-      fill: ecolor::Color32::TRANSPARENT,
-      stroke: self.line_stroke.into(),
-    };
-    let rect = shape.visual_bounding_rect();
+    let mut stroke = self.line_stroke;
     if let Some(color) = color {
-      shape.stroke.color = egui::epaint::ColorMode::Solid(color);
+      stroke.color = color;
     }
+    let (bezier, path) = match self.routing {
+      ConnectionRouting::Bezier => {
+        let mut offset = (start - end) * 0.2;
+        offset.x = self.curve_offset + offset.y.abs() + offset.x.abs();
+        let start2 = start - offset;
+        let end2 = end + offset;
+        let shape = egui::epaint::CubicBezierShape {
+          points: [start, start2, end2, end],
+          closed: false,
+          fill: ecolor::Color32::TRANSPARENT,
+          stroke: stroke.into(),
+        };
+        (Some(shape), Vec::new())
+      }
+      ConnectionRouting::Straight => (None, vec![start, end]),
+      ConnectionRouting::Orthogonal => (None, self.orthogonal_route(start, end)),
+    };
+    let rect = match &bezier {
+      Some(shape) => shape.visual_bounding_rect(),
+      None => emath::Rect::from_points(&path).expand(stroke.width / 2.0),
+    };
     // Check if the mouse pointer is close to the connection.
     let mut hover = false;
     let resp_rect = if highlight {
@@ -130,19 +244,24 @@ impl NodeConnection {
       let rect = rect.expand(margin);
       match ui.ctx().pointer_latest_pos() {
         Some(pointer) if rect.contains(pointer) => {
-          let tolerance = (start.x - end.x).abs() * 0.001;
           let mut last = start;
-          shape.for_each_flattened_with_t(tolerance, &mut |pos, _t| {
-            // Make bounding box for each line segment.
-            let rect = emath::Rect::from_two_pos(last, pos).expand(margin);
-            if rect.contains(pointer) {
+          let mut check_segment = |pos: emath::Pos2| {
+            // Check the distance to each line segment.
+            if segment_distance(last, pos, pointer) <= margin {
               hover = true;
             }
             last = pos;
-          });
+          };
+          match &bezier {
+            Some(shape) => {
+              let tolerance = (start.x - end.x).abs() * 0.001;
+              shape.for_each_flattened_with_t(tolerance, &mut |pos, _t| check_segment(pos));
+            }
+            None => path.iter().for_each(|pos| check_segment(*pos)),
+          }
 
           if hover {
-            shape.stroke.width *= 1.8;
+            stroke.width *= 1.8;
             Some(rect)
           } else {
             None
@@ -163,12 +282,43 @@ impl NodeConnection {
           id,
         ));
       }
-      painter.add(shape);
+      let flow_points = self.flow_animation.then(|| match &bezier {
+        Some(shape) => {
+          let tolerance = ((start.x - end.x).abs() * 0.001).max(0.1);
+          let mut points = Vec::new();
+          shape.for_each_flattened_with_t(tolerance, &mut |pos, _t| points.push(pos));
+          points
+        }
+        None => path.clone(),
+      });
+      match bezier {
+        Some(mut shape) => {
+          shape.stroke = stroke.into();
+          painter.add(shape);
+        }
+        None => {
+          painter.add(egui::Shape::line(path, stroke));
+        }
+      }
+      if let Some(points) = flow_points {
+        self.draw_flow(ui, &painter, &points, stroke);
+      }
     }
     resp_rect
   }
 }
 
+/// Distance from `pos` to the line segment `a` to `b`.
+fn segment_distance(a: emath::Pos2, b: emath::Pos2, pos: emath::Pos2) -> f32 {
+  let ab = b - a;
+  let len_sq = ab.length_sq();
+  if len_sq == 0.0 {
+    return pos.distance(a);
+  }
+  let t = ((pos - a).dot(ab) / len_sq).clamp(0.0, 1.0);
+  pos.distance(a + ab * t)
+}
+
 #[derive(Clone, Debug, Default)]
 pub enum NodeSelectingState {
   #[default]
@@ -176,10 +326,13 @@ pub enum NodeSelectingState {
   Selecting {
     start: emath::Pos2,
     clear_old: bool,
+    /// Remove the nodes in the area from the selection.
+    remove: bool,
     area: emath::Rect,
   },
   Select {
     area: emath::Rect,
+    remove: bool,
   },
 }
 
@@ -198,24 +351,30 @@ impl NodeSelectingState {
     }
   }
 
-  pub fn drag_started(&mut self, start: emath::Pos2, clear_old: bool) {
+  /// Start a box selection.  With `remove` the nodes in the box are deselected instead.
+  pub fn drag_started(&mut self, start: emath::Pos2, clear_old: bool, remove: bool) {
     *self = Self::Selecting {
       start,
-      clear_old,
+      clear_old: clear_old && !remove,
+      remove,
       area: emath::Rect::from_points(&[start]),
     }
   }
 
   pub fn drag_released(&mut self) {
-    if let Self::Selecting { area, .. } = *self {
-      *self = Self::Select { area };
+    if let Self::Selecting { area, remove, .. } = *self {
+      *self = Self::Select { area, remove };
     }
   }
 
   pub fn ui(&self, ui: &egui::Ui) {
-    if let Self::Selecting { area, .. } = self {
-      ui.painter()
-        .rect_stroke(*area, 0.0, (0.5, egui::Color32::LIGHT_GRAY));
+    if let Self::Selecting { area, remove, .. } = self {
+      let color = if *remove {
+        ui.visuals().error_fg_color
+      } else {
+        egui::Color32::LIGHT_GRAY
+      };
+      ui.painter().rect_stroke(*area, 0.0, (0.5, color));
     }
   }
 }
@@ -247,9 +406,19 @@ struct NodeGraphMetaInner {
   origin: emath::Vec2,
   sockets: HashMap<NodeSocketId, NodeSocket>,
   outputs_changed: HashSet<OutputId>,
+  inferred_counter: Option<usize>,
+  inferred_types: HashMap<OutputId, DataType>,
+  previews: HashMap<NodeId, Value>,
   frames: IndexMap<Uuid, NodeFrameState>,
   drag_state: NodeSocketDragState,
   selecting_state: NodeSelectingState,
+  /// Sockets to disconnect, requested with the keyboard.
+  disconnect: Vec<NodeSocketId>,
+  /// Inputs to promote to graph properties.
+  promote: Vec<InputId>,
+  /// The socket hovered in the last frame and the connections shown in its tooltip.
+  hover_socket: Option<NodeSocketId>,
+  hover_links: Option<(NodeSocketId, Vec<String>)>,
 }
 
 impl NodeGraphMetaInner {
@@ -279,6 +448,14 @@ impl NodeGraphMetaInner {
       .collect()
   }
 
+  pub fn selected(&self) -> Vec<Uuid> {
+    self
+      .frames
+      .iter()
+      .filter_map(|(id, frame)| frame.selected.then_some(*id))
+      .collect()
+  }
+
   pub fn has_selected(&self) -> bool {
     for frame in self.frames.values() {
       if frame.selected {
@@ -292,6 +469,10 @@ impl NodeGraphMetaInner {
     self.frames.get(&id).cloned().unwrap_or_default()
   }
 
+  pub fn is_selected(&self, id: Uuid) -> bool {
+    self.frames.get(&id).is_some_and(|frame| frame.selected)
+  }
+
   pub fn frame_state_mut<R>(
     &mut self,
     id: Uuid,
@@ -323,8 +504,63 @@ impl NodeGraphMetaInner {
     self.outputs_changed.drain().collect()
   }
 
+  pub fn request_disconnect(&mut self, id: NodeSocketId) {
+    self.disconnect.push(id);
+  }
+
+  pub fn take_disconnect(&mut self) -> Vec<NodeSocketId> {
+    std::mem::take(&mut self.disconnect)
+  }
+
+  pub fn request_promote(&mut self, id: InputId) {
+    self.promote.push(id);
+  }
+
+  pub fn take_promote(&mut self) -> Vec<InputId> {
+    std::mem::take(&mut self.promote)
+  }
+
+  pub fn set_hover_socket(&mut self, id: NodeSocketId) {
+    self.hover_socket = Some(id);
+  }
+
+  pub fn take_hover_socket(&mut self) -> Option<NodeSocketId> {
+    self.hover_socket.take()
+  }
+
+  pub fn set_hover_links(&mut self, id: NodeSocketId, links: Vec<String>) {
+    self.hover_links = Some((id, links));
+  }
+
+  pub fn hover_links(&self, id: NodeSocketId) -> Option<Vec<String>> {
+    self
+      .hover_links
+      .as_ref()
+      .filter(|(socket, _)| *socket == id)
+      .map(|(_, links)| links.clone())
+  }
+
+  pub fn set_inferred_types(&mut self, counter: usize, types: HashMap<OutputId, DataType>) {
+    self.inferred_counter = Some(counter);
+    self.inferred_types = types;
+  }
+
+  pub fn inferred_type(&self, output: &OutputId) -> Option<DataType> {
+    self.inferred_types.get(output).copied()
+  }
+
+  pub fn set_previews(&mut self, previews: HashMap<NodeId, Value>) {
+    self.previews = previews;
+  }
+
+  pub fn preview(&self, node: NodeId) -> Option<Value> {
+    self.previews.get(&node).cloned()
+  }
+
   pub fn resolve_output(&self, output: &OutputId) -> Option<DataType> {
-    self.sockets.get(&output.into()).map(|meta| meta.dt)
+    self
+      .inferred_type(output)
+      .or_else(|| self.sockets.get(&output.into()).map(|meta| meta.dt))
   }
 
   pub fn get_connection_meta(
@@ -341,6 +577,26 @@ impl NodeGraphMetaInner {
   }
 }
 
+/// Socket updates buffered while rendering a node.  See `NodeGraphMeta::batch_sockets`.
+struct SocketBatch {
+  graph: NodeGraphMeta,
+  /// The graph's `ui_min` and `zoom` when the batch started.
+  ui_min: emath::Vec2,
+  zoom: f32,
+  sockets: Vec<NodeSocket>,
+}
+
+impl SocketBatch {
+  /// Same as `NodeGraphMetaInner::ui_to_graph`, without locking the graph state.
+  fn ui_to_graph(&self, pos: egui::Pos2) -> emath::Vec2 {
+    (pos.to_vec2() - self.ui_min) / self.zoom
+  }
+}
+
+thread_local! {
+  static SOCKET_BATCH: RefCell<Option<SocketBatch>> = const { RefCell::new(None) };
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct NodeGraphMeta(Arc<RwLock<NodeGraphMetaInner>>);
 
@@ -350,13 +606,18 @@ impl NodeGraphMeta {
   }
 
   pub fn load(&self, ui: &mut egui::Ui, origin: emath::Vec2, ui_min: emath::Vec2, zoom: f32) {
-    let mut inner = self.0.write().unwrap();
-    inner.update(origin, ui_min, zoom);
+    self.update(origin, ui_min, zoom);
     ui.data_mut(|d| {
       d.insert_temp(egui::Id::new(NODE_GRAPH_META), self.clone());
     });
   }
 
+  /// Update the graph-space to UI-space transform.
+  pub fn update(&self, origin: emath::Vec2, ui_min: emath::Vec2, zoom: f32) {
+    let mut inner = self.0.write().unwrap();
+    inner.update(origin, ui_min, zoom);
+  }
+
   pub fn unload(&self, ui: &mut egui::Ui) {
     ui.data_mut(|d| {
       d.remove::<NodeGraphMeta>(egui::Id::new(NODE_GRAPH_META));
@@ -378,6 +639,11 @@ impl NodeGraphMeta {
     inner.take_selected()
   }
 
+  pub fn selected(&self) -> Vec<Uuid> {
+    let inner = self.0.read().unwrap();
+    inner.selected()
+  }
+
   pub fn clear_selected(&self) {
     self.take_selected();
   }
@@ -392,6 +658,11 @@ impl NodeGraphMeta {
     inner.frame_state(id)
   }
 
+  pub fn is_selected(&self, id: Uuid) -> bool {
+    let inner = self.0.read().unwrap();
+    inner.is_selected(id)
+  }
+
   pub fn set_frame_state(&self, id: Uuid, state: NodeFrameState) {
     let mut inner = self.0.write().unwrap();
     inner.set_frame_state(id, state);
@@ -436,8 +707,59 @@ impl NodeGraphMeta {
   }
 
   pub fn update_node_socket(&self, socket: &mut NodeSocket, pos: egui::Pos2) {
-    let mut inner = self.0.write().unwrap();
-    inner.update_node_socket(socket, pos)
+    let batched = SOCKET_BATCH.with(|batch| match batch.borrow_mut().as_mut() {
+      Some(batch) if Arc::ptr_eq(&batch.graph.0, &self.0) => {
+        socket.center = batch.ui_to_graph(pos);
+        batch.sockets.push(socket.clone());
+        true
+      }
+      _ => false,
+    });
+    if !batched {
+      let mut inner = self.0.write().unwrap();
+      inner.update_node_socket(socket, pos)
+    }
+  }
+
+  /// Buffer the socket updates from `f` and apply them with a single write lock.
+  ///
+  /// Used when rendering a node to avoid a write lock on the graph state for each socket.
+  pub fn batch_sockets<R>(&self, f: impl FnOnce() -> R) -> R {
+    let started = SOCKET_BATCH.with(|batch| {
+      let mut batch = batch.borrow_mut();
+      if batch.is_some() {
+        // Already batching, the outer batch will apply the updates.
+        return false;
+      }
+      let (ui_min, zoom) = {
+        let inner = self.0.read().unwrap();
+        (inner.ui_min, inner.zoom)
+      };
+      *batch = Some(SocketBatch {
+        graph: self.clone(),
+        ui_min,
+        zoom,
+        sockets: Vec::new(),
+      });
+      true
+    });
+    let res = f();
+    if started {
+      if let Some(batch) = SOCKET_BATCH.with(|batch| batch.borrow_mut().take()) {
+        if !batch.sockets.is_empty() {
+          let mut inner = self.0.write().unwrap();
+          for socket in batch.sockets {
+            inner.sockets.insert(socket.id, socket);
+          }
+        }
+      }
+    }
+    res
+  }
+
+  pub fn get_socket(&self, id: &NodeSocketId) -> Option<NodeSocket> {
+    let inner = self.0.read().unwrap();
+    inner.sockets.get(id).cloned()
   }
 
   pub fn update_output(&self, output_id: OutputId) {
@@ -450,6 +772,79 @@ impl NodeGraphMeta {
     inner.take_updated_outputs()
   }
 
+  /// Request to disconnect all connections of a socket.
+  pub fn request_disconnect(&self, id: NodeSocketId) {
+    let mut inner = self.0.write().unwrap();
+    inner.request_disconnect(id)
+  }
+
+  pub fn take_disconnect(&self) -> Vec<NodeSocketId> {
+    let mut inner = self.0.write().unwrap();
+    inner.take_disconnect()
+  }
+
+  /// Request to promote an input's value to a graph property.
+  pub fn request_promote(&self, id: InputId) {
+    let mut inner = self.0.write().unwrap();
+    inner.request_promote(id)
+  }
+
+  pub fn take_promote(&self) -> Vec<InputId> {
+    let mut inner = self.0.write().unwrap();
+    inner.take_promote()
+  }
+
+  /// Mark the socket as hovered.  Its connections are provided with `set_hover_links`
+  /// in the next frame.
+  pub fn set_hover_socket(&self, id: NodeSocketId) {
+    let mut inner = self.0.write().unwrap();
+    inner.set_hover_socket(id)
+  }
+
+  pub fn take_hover_socket(&self) -> Option<NodeSocketId> {
+    let mut inner = self.0.write().unwrap();
+    inner.take_hover_socket()
+  }
+
+  pub fn set_hover_links(&self, id: NodeSocketId, links: Vec<String>) {
+    let mut inner = self.0.write().unwrap();
+    inner.set_hover_links(id, links)
+  }
+
+  /// The connections of the hovered socket `id`, if known.
+  pub fn hover_links(&self, id: NodeSocketId) -> Option<Vec<String>> {
+    let inner = self.0.read().unwrap();
+    inner.hover_links(id)
+  }
+
+  /// The graph `changed` counter used for the last type inference.
+  pub fn inferred_counter(&self) -> Option<usize> {
+    let inner = self.0.read().unwrap();
+    inner.inferred_counter
+  }
+
+  /// Update the inferred output types.  See `NodeGraph::infer_types`.
+  pub fn set_inferred_types(&self, counter: usize, types: HashMap<OutputId, DataType>) {
+    let mut inner = self.0.write().unwrap();
+    inner.set_inferred_types(counter, types)
+  }
+
+  pub fn inferred_type(&self, output: &OutputId) -> Option<DataType> {
+    let inner = self.0.read().unwrap();
+    inner.inferred_type(output)
+  }
+
+  /// Update the node previews.  See `NodeGraph::previews`.
+  pub fn set_previews(&self, previews: HashMap<NodeId, Value>) {
+    let mut inner = self.0.write().unwrap();
+    inner.set_previews(previews)
+  }
+
+  pub fn preview(&self, node: NodeId) -> Option<Value> {
+    let inner = self.0.read().unwrap();
+    inner.preview(node)
+  }
+
   pub fn resolve_output(&self, output: &OutputId) -> Option<DataType> {
     let inner = self.0.read().unwrap();
     inner.resolve_output(output)
@@ -472,7 +867,7 @@ impl NodeGraphMeta {
     let id = node.id();
     let mut frame = self.frame_state(id);
 
-    let action = frame.render(ui, self, node);
+    let action = self.batch_sockets(|| frame.render(ui, self, node));
     self.set_frame_state(id, frame);
     action
   }
@@ -485,12 +880,18 @@ pub struct NodeSocket {
   pub center: emath::Vec2,
   pub color: egui::Color32,
   pub dt: DataType,
+  /// Accessible name of the socket.
+  pub name: String,
+  /// Use the theme's color for the data type.  False for sockets with a custom color.
+  themed: bool,
 }
 
 impl NodeSocket {
   pub fn input(node: NodeId, idx: u32, connected: bool, def: &InputDefinition) -> Self {
     let id = NodeSocketId::input(node, idx);
-    Self::new(id, connected, def.value_type, def.color)
+    let mut socket = Self::new(id, connected, def.value_type, def.color);
+    socket.name = def.name.clone();
+    socket
   }
 
   pub fn output(
@@ -501,7 +902,9 @@ impl NodeSocket {
   ) -> Self {
     let dt = concrete_type.unwrap_or_else(|| def.value_type);
     let id = NodeSocketId::output(node, idx);
-    Self::new(id, false, dt, def.color)
+    let mut socket = Self::new(id, false, dt, def.color);
+    socket.name = def.name.clone();
+    socket
   }
 
   pub fn new(
@@ -516,16 +919,19 @@ impl NodeSocket {
       center: Default::default(),
       color: color.unwrap_or_else(|| dt.color()),
       dt,
+      name: String::new(),
+      themed: color.is_none(),
     }
   }
 
   pub fn set_data_type(&mut self, dt: DataType) {
     self.dt = dt;
     self.color = dt.color();
+    self.themed = true;
   }
 
-  pub fn is_compatible(&self, dst: &NodeSocket) -> bool {
-    self.id.is_compatible(dst.id) && self.dt.is_compatible(&dst.dt)
+  pub fn is_compatible(&self, dst: &NodeSocket, policy: ConnectionPolicy) -> bool {
+    self.id.is_compatible(dst.id) && policy.is_compatible(&self.dt, &dst.dt)
   }
 
   pub fn input_id_first(
@@ -553,6 +959,30 @@ impl NodeSocket {
   }
 }
 
+impl NodeSocket {
+  fn tooltip_ui(&self, ui: &mut egui::Ui, links: Option<Vec<String>>) {
+    ui.strong(&self.name);
+    ui.label(format!("Type: {:?}", self.dt));
+    match links {
+      Some(links) if links.is_empty() => {
+        ui.weak("Not connected");
+      }
+      Some(links) => {
+        let label = if self.id.is_input() {
+          "Connected to:"
+        } else {
+          "Used by:"
+        };
+        ui.label(label);
+        for link in links {
+          ui.label(format!("• {link}"));
+        }
+      }
+      None => (),
+    }
+  }
+}
+
 impl egui::Widget for NodeSocket {
   fn ui(mut self, ui: &mut egui::Ui) -> egui::Response {
     let node_style = NodeStyle::get(ui);
@@ -583,6 +1013,12 @@ impl egui::Widget for NodeSocket {
     };
     let end = center + emath::Vec2::from((to_edge, 0.));
 
+    if self.themed {
+      if let Some(theme) = GraphTheme::get(ui) {
+        self.color = theme.socket_color(self.dt);
+      }
+    }
+
     // Update socket metadata.
     graph.update_node_socket(&mut self, end);
 
@@ -598,7 +1034,7 @@ impl egui::Widget for NodeSocket {
     if hovered {
       if let Some(src) = &drag_state.src {
         // Check if src socket is compatible.
-        if self.is_compatible(src) {
+        if self.is_compatible(src, node_style.connection_policy) {
           drag_state.dst = Some(self.clone());
         } else {
           hovered = false;
@@ -613,9 +1049,30 @@ impl egui::Widget for NodeSocket {
     }
     graph.set_drag_state(drag_state);
     let selected = hovered || self.connected;
+    let response = if response.hovered() {
+      graph.set_hover_socket(self.id);
+      let links = graph.hover_links(self.id);
+      response.on_hover_ui(|ui| self.tooltip_ui(ui, links))
+    } else {
+      response
+    };
 
     // Attach some meta-data to the response which can be used by screen readers:
-    response.widget_info(|| egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, selected, ""));
+    response.widget_info(|| {
+      let kind = if self.id.is_input() {
+        "input"
+      } else {
+        "output"
+      };
+      let label = format!("{} {kind} ({:?})", self.name, self.dt);
+      egui::WidgetInfo::selected(egui::WidgetType::Checkbox, true, self.connected, label)
+    });
+    // Delete the socket's connections with the keyboard.
+    if response.has_focus()
+      && ui.input(|i| i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace))
+    {
+      graph.request_disconnect(self.id);
+    }
 
     // 4. Paint!
     // Make sure we need to paint:
@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::ui::*;
+use crate::values::DataType;
+
+const GRAPH_THEME: &str = "GraphTheme";
+
+/// The colors and styles of the graph editor.
+#[derive(Clone, Debug)]
+pub struct GraphTheme {
+  pub name: String,
+  pub visuals: egui::Visuals,
+  /// Node layout and connection style.
+  pub node_style: NodeStyle,
+  /// Default frame colors of nodes and groups.
+  pub frame_style: NodeFrameStyle,
+  /// Socket (and connection) colors by data type.  Missing types use `DataType::color`.
+  pub socket_colors: HashMap<DataType, egui::Color32>,
+}
+
+impl Default for GraphTheme {
+  fn default() -> Self {
+    Self::dark()
+  }
+}
+
+impl GraphTheme {
+  /// The default theme.
+  pub fn dark() -> Self {
+    Self {
+      name: "Dark".to_string(),
+      visuals: egui::Visuals::dark(),
+      node_style: NodeStyle::default(),
+      frame_style: NodeFrameStyle::default(),
+      socket_colors: HashMap::new(),
+    }
+  }
+
+  pub fn light() -> Self {
+    let scalar = egui::Color32::from_rgb(30, 110, 200);
+    let matrix = egui::Color32::from_rgb(40, 40, 180);
+    let texture = egui::Color32::from_rgb(190, 30, 30);
+    let mut socket_colors = HashMap::new();
+    for dt in [
+      DataType::I32,
+      DataType::U32,
+      DataType::F32,
+      DataType::F16,
+      DataType::DynamicVector,
+    ] {
+      socket_colors.insert(dt, scalar);
+    }
+    for dt in [
+      DataType::Mat2,
+      DataType::Mat3,
+      DataType::Mat4,
+      DataType::Dynamic,
+      DataType::DynamicMatrix,
+    ] {
+      socket_colors.insert(dt, matrix);
+    }
+    for dt in [
+      DataType::Texture2D,
+      DataType::Texture2DArray,
+      DataType::Texture3D,
+      DataType::Cubemap,
+    ] {
+      socket_colors.insert(dt, texture);
+    }
+    socket_colors.insert(DataType::Vec2, egui::Color32::from_rgb(0, 140, 0));
+    socket_colors.insert(DataType::Vec3, egui::Color32::from_rgb(170, 140, 0));
+    socket_colors.insert(DataType::Vec4, egui::Color32::from_rgb(210, 70, 70));
+    socket_colors.insert(DataType::F32Array, egui::Color32::from_rgb(60, 160, 60));
+    Self {
+      name: "Light".to_string(),
+      visuals: egui::Visuals::light(),
+      node_style: NodeStyle {
+        line_stroke: (2.0, egui::Color32::from_gray(60)).into(),
+        ..Default::default()
+      },
+      frame_style: NodeFrameStyle {
+        fill: egui::Color32::from_gray(235),
+        selected: egui::Color32::BLACK,
+        highlight: egui::Color32::from_rgb(230, 120, 0),
+        output: egui::Color32::from_rgb(0, 140, 60),
+      },
+      socket_colors,
+    }
+  }
+
+  pub fn high_contrast() -> Self {
+    let mut visuals = egui::Visuals::dark();
+    visuals.override_text_color = Some(egui::Color32::WHITE);
+    let mut socket_colors = HashMap::new();
+    for dt in [DataType::I32, DataType::U32, DataType::F32, DataType::F16] {
+      socket_colors.insert(dt, egui::Color32::from_rgb(0, 200, 255));
+    }
+    socket_colors.insert(DataType::Vec2, egui::Color32::from_rgb(0, 255, 0));
+    socket_colors.insert(DataType::Vec3, egui::Color32::from_rgb(255, 255, 0));
+    socket_colors.insert(DataType::Vec4, egui::Color32::from_rgb(255, 0, 255));
+    Self {
+      name: "High contrast".to_string(),
+      visuals,
+      node_style: NodeStyle {
+        line_stroke: (3.0, egui::Color32::WHITE).into(),
+        ..Default::default()
+      },
+      frame_style: NodeFrameStyle {
+        fill: egui::Color32::BLACK,
+        selected: egui::Color32::YELLOW,
+        highlight: egui::Color32::from_rgb(0, 255, 255),
+        output: egui::Color32::from_rgb(0, 255, 0),
+      },
+      socket_colors,
+    }
+  }
+
+  /// The built-in themes.
+  pub fn presets() -> Vec<Self> {
+    vec![Self::dark(), Self::light(), Self::high_contrast()]
+  }
+
+  pub fn socket_color(&self, dt: DataType) -> egui::Color32 {
+    self
+      .socket_colors
+      .get(&dt)
+      .copied()
+      .unwrap_or_else(|| dt.color())
+  }
+
+  /// Get the theme applied to `ui`.  Returns `None` if no theme was applied.
+  pub fn get(ui: &egui::Ui) -> Option<Arc<Self>> {
+    ui.data(|d| d.get_temp::<Arc<Self>>(egui::Id::new(GRAPH_THEME)))
+  }
+
+  /// Use this theme for the graph rendered in `ui`.
+  pub fn apply(self: &Arc<Self>, ui: &mut egui::Ui) {
+    *ui.visuals_mut() = self.visuals.clone();
+    self.node_style.set(ui);
+    ui.data_mut(|d| d.insert_temp(egui::Id::new(GRAPH_THEME), self.clone()));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn socket_colors() {
+    let dark = GraphTheme::dark();
+    assert_eq!(dark.socket_color(DataType::Vec3), DataType::Vec3.color());
+    let light = GraphTheme::light();
+    assert_ne!(light.socket_color(DataType::Vec3), DataType::Vec3.color());
+    // Every data type gets a themed color.
+    for dt in DataType::ALL {
+      assert!(light.socket_colors.contains_key(&dt), "{dt:?}");
+    }
+    let names: Vec<String> = GraphTheme::presets().into_iter().map(|t| t.name).collect();
+    assert_eq!(names, ["Dark", "Light", "High contrast"]);
+  }
+
+  #[test]
+  fn editor_theme() {
+    // The editor keeps the app's visuals until a theme is set.
+    let mut editor = crate::NodeGraphEditor::new();
+    assert!(editor.theme().is_none());
+    editor.set_theme(GraphTheme::light());
+    assert_eq!(editor.theme().map(|t| t.name.as_str()), Some("Light"));
+  }
+}
@@ -25,6 +25,9 @@ pub use matrix::*;
 
 pub mod texture;
 
+pub mod array;
+pub use array::*;
+
 pub mod bindings;
 pub use bindings::*;
 
@@ -51,6 +54,8 @@ pub enum Value {
   I32(i32),
   U32(u32),
   F32(f32),
+  /// Half-precision float, stored as `f32`.
+  F16(f32),
   Vec2(Vec2),
   Vec3(Vec3),
   Vec4(Vec4),
@@ -61,6 +66,7 @@ pub enum Value {
   Texture2DArray(Texture2DArrayHandle),
   Texture3D(Texture3DHandle),
   Cubemap(CubemapHandle),
+  F32Array(F32Array),
 }
 
 impl Default for Value {
@@ -75,6 +81,7 @@ impl Value {
       Self::I32(v) => v,
       Self::U32(v) => v,
       Self::F32(v) => v,
+      Self::F16(v) => v,
       Self::Vec2(v) => v,
       Self::Vec3(v) => v,
       Self::Vec4(v) => v,
@@ -85,6 +92,7 @@ impl Value {
       Self::Texture2DArray(v) => v,
       Self::Texture3D(v) => v,
       Self::Cubemap(v) => v,
+      Self::F32Array(v) => v,
     }
   }
 
@@ -93,6 +101,7 @@ impl Value {
       Self::I32(_) => DataType::I32,
       Self::U32(_) => DataType::U32,
       Self::F32(_) => DataType::F32,
+      Self::F16(_) => DataType::F16,
       Self::Vec2(_) => DataType::Vec2,
       Self::Vec3(_) => DataType::Vec3,
       Self::Vec4(_) => DataType::Vec4,
@@ -103,6 +112,7 @@ impl Value {
       Self::Texture2DArray(_) => DataType::Texture2DArray,
       Self::Texture3D(_) => DataType::Texture3D,
       Self::Cubemap(_) => DataType::Cubemap,
+      Self::F32Array(_) => DataType::F32Array,
     }
   }
 
@@ -117,6 +127,9 @@ impl Value {
       Value::F32(val) => {
         format!("{val:?}")
       }
+      Value::F16(val) => {
+        format!("{val:?}h")
+      }
       Value::Vec2(v) => {
         format!("vec2<f32>({:?}, {:?})", v.x, v.y)
       }
@@ -160,6 +173,7 @@ impl Value {
         // TODO: Convert to wgsl syntax.
         format!("vec4<f32>(0.5, 0.5, 0., 1.)")
       }
+      Value::F32Array(v) => v.compile()?,
     };
     Ok(CompiledValue {
       value,
@@ -167,12 +181,198 @@ impl Value {
     })
   }
 
+  /// Convert the value to another data type.  Uses the same rules as `CompiledValue::convert`.
+  pub fn convert(self, to_dt: DataType) -> Result<Value> {
+    let from_dt = self.data_type();
+    match (from_dt.class(), to_dt) {
+      // Same data type, no conversion.
+      _ if from_dt == to_dt => return Ok(self),
+      (
+        DataTypeClass::Scalar | DataTypeClass::Vector | DataTypeClass::Matrix,
+        DataType::Dynamic,
+      )
+      | (DataTypeClass::Scalar | DataTypeClass::Vector, DataType::DynamicVector)
+      | (DataTypeClass::Matrix, DataType::DynamicMatrix) => return Ok(self),
+      _ => (),
+    }
+    let value = match (self, to_dt) {
+      // Between integers.
+      (Self::I32(v), DataType::U32) => Self::U32(v as u32),
+      (Self::U32(v), DataType::I32) => Self::I32(v as i32),
+      // Promote scalars to vectors.
+      (Self::I32(v), dt) if dt.class() == DataTypeClass::Vector => Self::F32(v as f32).splat(dt)?,
+      (Self::U32(v), dt) if dt.class() == DataTypeClass::Vector => Self::F32(v as f32).splat(dt)?,
+      (Self::F32(v) | Self::F16(v), dt) if dt.class() == DataTypeClass::Vector => {
+        Self::F32(v).splat(dt)?
+      }
+      // Vectors.
+      (Self::Vec2(v), DataType::Vec3) => Self::Vec3(v.extend(0.)),
+      (Self::Vec2(v), DataType::Vec4) => Self::Vec4(v.extend(0.).extend(1.)),
+      (Self::Vec3(v), DataType::Vec2) => Self::Vec2(v.truncate()),
+      (Self::Vec3(v), DataType::Vec4) => Self::Vec4(v.extend(1.)),
+      (Self::Vec4(v), DataType::Vec2) => Self::Vec2(v.truncate().truncate()),
+      (Self::Vec4(v), DataType::Vec3) => Self::Vec3(v.truncate()),
+      // Matrixes.
+      (Self::Mat3(m), DataType::Mat2) => Self::Mat2(Mat2::from_mat3(m)),
+      (Self::Mat4(m), DataType::Mat2) => Self::Mat2(Mat2::from_mat3(Mat3::from_mat4(m))),
+      (Self::Mat4(m), DataType::Mat3) => Self::Mat3(Mat3::from_mat4(m)),
+      // Demote scalars/vectors to scalars, using the first component.
+      (value, DataType::I32 | DataType::U32 | DataType::F32 | DataType::F16)
+        if matches!(
+          value.data_type().class(),
+          DataTypeClass::Scalar | DataTypeClass::Vector
+        ) =>
+      {
+        let x = value.components()?[0];
+        match to_dt {
+          DataType::I32 => Self::I32(x as i32),
+          DataType::U32 => Self::U32(x as u32),
+          DataType::F16 => Self::F16(x),
+          _ => Self::F32(x),
+        }
+      }
+      (_, to_dt) => {
+        return Err(anyhow!(
+          "Conversion from {from_dt:?} to {to_dt:?} not supported."
+        ));
+      }
+    };
+    Ok(value)
+  }
+
+  // Fill a vector with a scalar value.
+  fn splat(&self, dt: DataType) -> Result<Value> {
+    let x = self.components()?[0];
+    match dt {
+      DataType::Vec2 => Ok(Self::Vec2(Vec2::splat(x))),
+      DataType::Vec3 => Ok(Self::Vec3(Vec3::splat(x))),
+      DataType::Vec4 => Ok(Self::Vec4(Vec4::splat(x))),
+      _ => Err(anyhow!("Can't splat a scalar to {dt:?}")),
+    }
+  }
+
+  /// The components of a scalar, vector or matrix (column-major) value.
+  pub fn components(&self) -> Result<Vec<f32>> {
+    Ok(match self {
+      Self::I32(v) => vec![*v as f32],
+      Self::U32(v) => vec![*v as f32],
+      Self::F32(v) | Self::F16(v) => vec![*v],
+      Self::Vec2(v) => v.to_array().to_vec(),
+      Self::Vec3(v) => v.to_array().to_vec(),
+      Self::Vec4(v) => v.to_array().to_vec(),
+      Self::Mat2(m) => m.to_cols_array().to_vec(),
+      Self::Mat3(m) => m.to_cols_array().to_vec(),
+      Self::Mat4(m) => m.to_cols_array().to_vec(),
+      _ => {
+        return Err(anyhow!("Expected a scalar, vector or matrix got: {self:?}"));
+      }
+    })
+  }
+
+  // Build a value of the same type from it's components.
+  fn with_components(&self, c: &[f32]) -> Result<Value> {
+    Ok(match self {
+      Self::I32(_) => Self::I32(c[0] as i32),
+      Self::U32(_) => Self::U32(c[0] as u32),
+      Self::F32(_) => Self::F32(c[0]),
+      Self::F16(_) => Self::F16(c[0]),
+      Self::Vec2(_) => Self::Vec2(Vec2::from_slice(c)),
+      Self::Vec3(_) => Self::Vec3(Vec3::from_slice(c)),
+      Self::Vec4(_) => Self::Vec4(Vec4::from_slice(c)),
+      Self::Mat2(_) => Self::Mat2(Mat2::from_cols_slice(c)),
+      Self::Mat3(_) => Self::Mat3(Mat3::from_cols_slice(c)),
+      Self::Mat4(_) => Self::Mat4(Mat4::from_cols_slice(c)),
+      _ => {
+        return Err(anyhow!("Expected a scalar, vector or matrix got: {self:?}"));
+      }
+    })
+  }
+
+  /// Apply `f` to each component of the value.
+  pub fn map(&self, f: impl Fn(f32) -> f32) -> Result<Value> {
+    let c: Vec<f32> = self.components()?.into_iter().map(f).collect();
+    self.with_components(&c)
+  }
+
+  /// Apply `f` component-wise to two values.  Scalars are applied to each component of the other value.
+  pub fn zip_map(&self, other: &Value, f: impl Fn(f32, f32) -> f32) -> Result<Value> {
+    self.zip_map3(other, &Value::F32(0.), |a, b, _| f(a, b))
+  }
+
+  /// Apply `f` component-wise to three values.  Scalars are applied to each component of the other values.
+  pub fn zip_map3(&self, b: &Value, c: &Value, f: impl Fn(f32, f32, f32) -> f32) -> Result<Value> {
+    let values = [self.components()?, b.components()?, c.components()?];
+    // The largest value decides the output type.
+    let (shape, len) = [self, b, c]
+      .into_iter()
+      .zip(&values)
+      .map(|(v, c)| (v, c.len()))
+      .fold((self, 1), |acc, v| if v.1 > acc.1 { v } else { acc });
+    for (value, comps) in [self, b, c].into_iter().zip(&values) {
+      if comps.len() != 1 && comps.len() != len {
+        return Err(anyhow!(
+          "Mismatched value types: {:?} and {:?}",
+          shape.data_type(),
+          value.data_type()
+        ));
+      }
+    }
+    let get = |c: &Vec<f32>, idx: usize| if c.len() == 1 { c[0] } else { c[idx] };
+    let out: Vec<f32> = (0..len)
+      .map(|idx| {
+        f(
+          get(&values[0], idx),
+          get(&values[1], idx),
+          get(&values[2], idx),
+        )
+      })
+      .collect();
+    shape.with_components(&out)
+  }
+
+  /// Multiply two values, matrixes use matrix multiplication like WGSL.
+  pub fn mul(&self, rhs: &Value) -> Result<Value> {
+    Ok(match (self, rhs) {
+      (Self::Mat2(a), Self::Mat2(b)) => Self::Mat2(*a * *b),
+      (Self::Mat3(a), Self::Mat3(b)) => Self::Mat3(*a * *b),
+      (Self::Mat4(a), Self::Mat4(b)) => Self::Mat4(*a * *b),
+      (Self::Mat2(m), Self::Vec2(v)) => Self::Vec2(*m * *v),
+      (Self::Mat3(m), Self::Vec3(v)) => Self::Vec3(*m * *v),
+      (Self::Mat4(m), Self::Vec4(v)) => Self::Vec4(*m * *v),
+      // Row vector times matrix.
+      (Self::Vec2(v), Self::Mat2(m)) => Self::Vec2(m.transpose() * *v),
+      (Self::Vec3(v), Self::Mat3(m)) => Self::Vec3(m.transpose() * *v),
+      (Self::Vec4(v), Self::Mat4(m)) => Self::Vec4(m.transpose() * *v),
+      (a, b) => a.zip_map(b, |a, b| a * b)?,
+    })
+  }
+
+  /// Show the value as a color swatch.  Non-color values are shown as text.
+  #[cfg(feature = "egui")]
+  pub fn swatch_ui(&self, ui: &mut egui::Ui, size: f32) {
+    let rgba = match self {
+      Self::F32(v) | Self::F16(v) => [*v, *v, *v, 1.0],
+      Self::Vec2(v) => [v.x, v.y, 0.0, 1.0],
+      Self::Vec3(v) => [v.x, v.y, v.z, 1.0],
+      Self::Vec4(v) => v.to_array(),
+      value => {
+        ui.label(format!("{value:?}"));
+        return;
+      }
+    };
+    let [r, g, b, a] = rgba.map(|c| c.clamp(0.0, 1.0));
+    let color = egui::Rgba::from_rgba_unmultiplied(r, g, b, a);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+    ui.painter().rect_filled(rect, 2.0, color);
+  }
+
   #[cfg(feature = "egui")]
   pub fn ui(&mut self, ui: &mut egui::Ui) -> bool {
     match self {
       Self::I32(v) => v.ui(ui),
       Self::U32(v) => v.ui(ui),
       Self::F32(v) => v.ui(ui),
+      Self::F16(v) => v.ui(ui),
       Self::Vec2(v) => v.ui(ui),
       Self::Vec3(v) => v.ui(ui),
       Self::Vec4(v) => v.ui(ui),
@@ -183,6 +383,7 @@ impl Value {
       Self::Texture2DArray(v) => v.ui(ui),
       Self::Texture3D(v) => v.ui(ui),
       Self::Cubemap(v) => v.ui(ui),
+      Self::F32Array(v) => v.ui(ui),
     }
   }
 }
@@ -247,6 +448,9 @@ pub struct InputDefinition {
   pub field_name: String,
   pub value_type: DataType,
   pub color: Option<ecolor::Color32>,
+  /// Default value for new nodes.  `None` uses the data type's default.
+  #[serde(default)]
+  pub default: Option<Value>,
 }
 
 impl InputDefinition {
@@ -264,6 +468,7 @@ impl InputDefinition {
         field_name: field_name.to_string(),
         value_type,
         color: None,
+        default: None,
       },
     )
   }
@@ -272,27 +477,64 @@ impl InputDefinition {
     self.color = color.map(u32_to_color);
   }
 
+  pub fn set_default(&mut self, default: Option<Value>) {
+    self.default = default;
+  }
+
   pub fn default_value(&self) -> Value {
-    self.value_type.default_value()
+    self
+      .default
+      .clone()
+      .unwrap_or_else(|| self.value_type.default_value())
   }
 
+  /// Check that `input` can be used for this input.
+  ///
+  /// Values are accepted if they can be converted to the input's data type without
+  /// losing data.  Connections need a compatible data type.
   pub fn validate(&self, input: &Input) -> Result<()> {
     match input {
       Input::Disconnect => (),
       Input::Value(val) => {
         let in_type = val.data_type();
-        if self.value_type != in_type {
-          return Err(anyhow::anyhow!(
-            "Wrong input data type: expected {:?} got {:?}",
-            self.value_type,
-            in_type
-          ));
+        match in_type.conversion(&self.value_type) {
+          Conversion::Identity | Conversion::Lossless => (),
+          _ => {
+            return Err(anyhow!(
+              "Wrong data type for input `{}`: expected {:?} got {:?}",
+              self.name,
+              self.value_type,
+              in_type
+            ));
+          }
         }
       }
+      Input::Connect(_, Some(dt)) if !self.value_type.is_compatible(dt) => {
+        return Err(anyhow!(
+          "Incompatible connection to input `{}`: expected {:?} got {:?}",
+          self.name,
+          self.value_type,
+          dt
+        ));
+      }
       Input::Connect(_, _) => (),
     }
     Ok(())
   }
+
+  /// Validate `input` and convert values to the input's data type.
+  ///
+  /// Returns the input to assign, so literal values are converted the same way as
+  /// connected outputs (for example scalars are splatted to vectors).
+  pub fn check(&self, input: Input) -> Result<Input> {
+    self.validate(&input)?;
+    match input {
+      Input::Value(val) if val.data_type().conversion(&self.value_type) == Conversion::Lossless => {
+        Ok(Input::Value(val.convert(self.value_type)?))
+      }
+      input => Ok(input),
+    }
+  }
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
@@ -331,26 +573,107 @@ impl OutputDefinition {
   }
 }
 
+/// Numeric range of a value parameter.  Applied to each component of vector values.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ParameterRange {
+  pub min: f32,
+  pub max: f32,
+  /// Clamp out of range values instead of rejecting them.
+  pub clamp: bool,
+}
+
+impl ParameterRange {
+  /// A range that rejects out of range values.
+  pub fn new(min: f32, max: f32) -> Self {
+    Self {
+      min,
+      max,
+      clamp: false,
+    }
+  }
+
+  /// A range that clamps out of range values.
+  pub fn clamped(min: f32, max: f32) -> Self {
+    Self {
+      min,
+      max,
+      clamp: true,
+    }
+  }
+
+  pub fn contains(&self, v: f32) -> bool {
+    v >= self.min && v <= self.max
+  }
+
+  /// Clamp `value` to the range.
+  pub fn clamp_value(&self, value: &Value) -> Result<Value> {
+    value.map(|v| v.clamp(self.min, self.max))
+  }
+
+  /// Check that `value` is in range.  Out of range values are clamped or rejected.
+  pub fn apply(&self, name: &str, value: Value) -> Result<Value> {
+    if value.components()?.iter().all(|v| self.contains(*v)) {
+      Ok(value)
+    } else if self.clamp {
+      self.clamp_value(&value)
+    } else {
+      Err(anyhow!(
+        "Parameter {name:?} value {value:?} is out of range: {}..={}",
+        self.min,
+        self.max
+      ))
+    }
+  }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum ParameterDataType {
   Value(DataType),
   Text(String),
-  Select(IndexSet<String>),
+  /// Text edited with a multi-line editor.  Uses `ParameterValue::Text` values.
+  MultilineText(String),
+  /// Select one of the values.  The optional labels (one per value) are shown in the ui.
+  Select(IndexSet<String>, Option<Vec<String>>),
+  Curve,
 }
 
 impl ParameterDataType {
   pub fn select(values: &[&str]) -> Self {
-    Self::Select(values.iter().map(|s| s.to_string()).collect())
+    Self::Select(values.iter().map(|s| s.to_string()).collect(), None)
+  }
+
+  /// Select with display labels.  `labels` must be the same length as `values`.
+  pub fn select_with_labels(values: &[&str], labels: &[&str]) -> Self {
+    debug_assert_eq!(values.len(), labels.len());
+    let labels = if values == labels {
+      None
+    } else {
+      Some(labels.iter().map(|s| s.to_string()).collect())
+    };
+    Self::Select(values.iter().map(|s| s.to_string()).collect(), labels)
+  }
+
+  /// The display label of a select value.  Falls back to the value.
+  pub fn select_label<'a>(&'a self, value: &'a str) -> &'a str {
+    match self {
+      Self::Select(values, Some(labels)) => values
+        .get_index_of(value)
+        .and_then(|idx| labels.get(idx))
+        .map(|label| label.as_str())
+        .unwrap_or(value),
+      _ => value,
+    }
   }
 
   pub fn default_value(&self) -> ParameterValue {
     match self {
       Self::Value(dt) => ParameterValue::Value(dt.default_value()),
-      Self::Text(val) => ParameterValue::Text(val.clone()),
-      Self::Select(values) => {
+      Self::Text(val) | Self::MultilineText(val) => ParameterValue::Text(val.clone()),
+      Self::Select(values, _) => {
         let val = values.first().cloned().unwrap_or_default();
         ParameterValue::Selected(val)
       }
+      Self::Curve => ParameterValue::Curve(Curve::default().points),
     }
   }
 }
@@ -360,6 +683,7 @@ pub enum ParameterValue {
   Value(Value),
   Text(String),
   Selected(String),
+  Curve(Vec<Vec2>),
 }
 
 impl ParameterValue {
@@ -367,7 +691,8 @@ impl ParameterValue {
     match self {
       Self::Value(val) => ParameterDataType::Value(val.data_type()),
       Self::Text(val) => ParameterDataType::Text(val.clone()),
-      Self::Selected(val) => ParameterDataType::Select([val].into_iter().cloned().collect()),
+      Self::Selected(val) => ParameterDataType::Select([val].into_iter().cloned().collect(), None),
+      Self::Curve(_) => ParameterDataType::Curve,
     }
   }
 }
@@ -391,6 +716,16 @@ pub trait ParameterType {
 
   fn parameter_data_type() -> ParameterDataType;
 
+  /// Old values still accepted by `set_param`, e.g. the names of renamed enum variants.
+  fn parameter_aliases() -> Vec<String> {
+    Vec::new()
+  }
+
+  /// Validate the parameter against the resolved type of the node's dynamic inputs.
+  fn validate_concrete_type(&self, _concrete_type: &NodeConcreteType) -> Result<()> {
+    Ok(())
+  }
+
   #[cfg(feature = "egui")]
   fn parameter_ui(
     &mut self,
@@ -448,7 +783,16 @@ where
   ) -> bool {
     ui.horizontal(|ui| {
       ui.label(&def.name);
-      self.ui(ui)
+      let changed = self.ui(ui);
+      if changed {
+        if let Some(range) = def.range() {
+          // The value widgets don't know about the range, always clamp ui edits.
+          if let Ok(val) = range.clamp_value(&self.to_value()) {
+            let _ = self.set_value(val);
+          }
+        }
+      }
+      changed
     })
     .inner
   }
@@ -459,11 +803,19 @@ pub struct ParameterDefinition {
   pub name: String,
   pub field_name: String,
   pub param_type: ParameterDataType,
+  /// Numeric range of value parameters.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub range: Option<ParameterRange>,
+  /// Old values still accepted by the parameter, they aren't shown in the ui.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub aliases: Vec<String>,
 }
 
 impl ParameterDefinition {
   pub fn typed<T: ParameterType>(field_name: &str) -> (String, Self) {
-    Self::new(field_name, T::parameter_data_type())
+    let (name, mut def) = Self::new(field_name, T::parameter_data_type());
+    def.aliases = T::parameter_aliases();
+    (name, def)
   }
 
   pub fn new(field_name: &str, param_type: ParameterDataType) -> (String, Self) {
@@ -474,6 +826,8 @@ impl ParameterDefinition {
         name,
         field_name: field_name.to_string(),
         param_type,
+        range: None,
+        aliases: Vec::new(),
       },
     )
   }
@@ -482,10 +836,43 @@ impl ParameterDefinition {
     Self::new(name, ParameterDataType::Value(data_type))
   }
 
+  /// The numeric range of a value parameter.
+  pub fn range(&self) -> Option<ParameterRange> {
+    self.range
+  }
+
+  /// Set the numeric range of a value parameter.  Ignored for other parameter types.
+  pub fn set_range(&mut self, range: Option<ParameterRange>) {
+    if let ParameterDataType::Value(_) = self.param_type {
+      self.range = range;
+    }
+  }
+
+  /// Validate `value` and apply the parameter's range.
+  ///
+  /// Returns the value to assign, which might have been clamped.
+  pub fn check(&self, value: ParameterValue) -> Result<ParameterValue> {
+    match &value {
+      // Aliases are mapped to the current value by the parameter's `set_param`.
+      ParameterValue::Selected(val) if self.aliases.contains(val) => (),
+      _ => self.validate(&value)?,
+    }
+    match (self.range(), value) {
+      (Some(range), ParameterValue::Value(val)) => {
+        Ok(ParameterValue::Value(range.apply(&self.name, val)?))
+      }
+      (_, value) => Ok(value),
+    }
+  }
+
   pub fn select(name: &str, values: &[&str]) -> (String, Self) {
     Self::new(name, ParameterDataType::select(values))
   }
 
+  pub fn multiline_text(name: &str, default: &str) -> (String, Self) {
+    Self::new(name, ParameterDataType::MultilineText(default.to_string()))
+  }
+
   pub fn default_value(&self) -> ParameterValue {
     self.param_type.default_value()
   }
@@ -504,7 +891,7 @@ impl ParameterDefinition {
           Ok(())
         }
       }
-      (ParameterDataType::Select(values), ParameterValue::Selected(val)) => {
+      (ParameterDataType::Select(values, _), ParameterValue::Selected(val)) => {
         if values.contains(val) {
           Ok(())
         } else {
@@ -514,6 +901,11 @@ impl ParameterDefinition {
           ))
         }
       }
+      (
+        ParameterDataType::Text(_) | ParameterDataType::MultilineText(_),
+        ParameterValue::Text(_),
+      ) => Ok(()),
+      (ParameterDataType::Curve, ParameterValue::Curve(_)) => Ok(()),
       (expected, got) => Err(anyhow::anyhow!(
         "Wrong parameter type: expected {:?} got {:?}",
         expected,
@@ -526,14 +918,26 @@ impl ParameterDefinition {
   pub fn ui(&self, ui: &mut egui::Ui, value: &mut ParameterValue) -> bool {
     ui.horizontal(|ui| match (&self.param_type, value) {
       (ParameterDataType::Value(_), ParameterValue::Value(value)) => value.ui(ui),
-      (ParameterDataType::Select(values), ParameterValue::Selected(selected)) => {
+      (ParameterDataType::Text(_), ParameterValue::Text(text)) => {
+        ui.add(egui::TextEdit::singleline(text)).changed()
+      }
+      (ParameterDataType::MultilineText(_), ParameterValue::Text(text)) => ui
+        .add(
+          egui::TextEdit::multiline(text)
+            .code_editor()
+            .desired_rows(4),
+        )
+        .changed(),
+      (ParameterDataType::Select(values, _), ParameterValue::Selected(selected)) => {
         let mut changed = false;
+        let label = self.param_type.select_label(selected).to_string();
         egui::ComboBox::from_id_source(&self.field_name)
-          .selected_text(selected.as_str())
+          .selected_text(label)
           .show_ui(ui, |ui| {
             for value in values {
+              let label = self.param_type.select_label(value);
               if ui
-                .selectable_value(selected, value.to_string(), value)
+                .selectable_value(selected, value.to_string(), label)
                 .changed()
               {
                 changed = true;
@@ -542,6 +946,12 @@ impl ParameterDefinition {
           });
         changed
       }
+      (ParameterDataType::Curve, ParameterValue::Curve(points)) => {
+        let mut curve = Curve::new(points.clone());
+        let changed = curve.ui(ui, egui::vec2(100.0, 100.0));
+        *points = curve.points;
+        changed
+      }
       _ => {
         ui.label("Invalid node parameter.  The value and definition don't match.");
         false
@@ -569,24 +979,12 @@ impl<T: ValueType + Default, const N: u32, const C: u32> OutputTyped<T, N, C> {
     T::default().data_type().is_dynamic()
   }
 
-  pub fn update_concrete_type(&mut self, concrete_type: &NodeConcreteType) -> bool {
-    let dt = T::default().data_type();
-    let new_type = match dt {
-      DataType::Dynamic => concrete_type.data_type(),
-      DataType::DynamicVector => match concrete_type.min {
-        Some(DynamicSize::D2) => Some(DataType::Vec2),
-        Some(DynamicSize::D3) => Some(DataType::Vec3),
-        Some(DynamicSize::D4) => Some(DataType::Vec4),
-        _ => Some(DataType::F32),
-      },
-      DataType::DynamicMatrix => match concrete_type.min {
-        Some(DynamicSize::D2) => Some(DataType::Mat2),
-        Some(DynamicSize::D3) => Some(DataType::Mat3),
-        Some(DynamicSize::D4) => Some(DataType::Mat4),
-        _ => None,
-      },
-      _ => None,
-    };
+  /// The output index.
+  pub fn idx(&self) -> u32 {
+    N
+  }
+
+  pub fn update_concrete_type(&mut self, new_type: Option<DataType>) -> bool {
     if new_type != self.concrete_type {
       self.concrete_type = new_type;
       true
@@ -605,6 +1003,17 @@ impl<T: ValueType + Default, const N: u32, const C: u32> OutputTyped<T, N, C> {
   ) -> Result<()> {
     compile.add_output(OutputId::new(node, N), prefix, code, dt)
   }
+
+  /// Compile the output as a `const` with a value known when compiling.
+  pub fn compile_const(
+    &self,
+    compile: &mut NodeGraphCompile,
+    node: NodeId,
+    prefix: &str,
+    value: Value,
+  ) -> Result<()> {
+    compile.add_const_output(OutputId::new(node, N), prefix, value)
+  }
 }
 
 #[cfg(feature = "egui")]
@@ -612,7 +1021,7 @@ impl<T: ValueType + Default, const N: u32, const C: u32> OutputTyped<T, N, C> {
   #[cfg(feature = "egui")]
   pub fn ui(
     &mut self,
-    concrete_type: &mut NodeConcreteType,
+    concrete_type: Option<DataType>,
     def: &OutputDefinition,
     ui: &mut egui::Ui,
     id: NodeId,
@@ -620,13 +1029,19 @@ impl<T: ValueType + Default, const N: u32, const C: u32> OutputTyped<T, N, C> {
   ) {
     ui.horizontal(|ui| {
       ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+        let output_id = OutputId::new(id, N);
+        let graph = NodeGraphMeta::get(ui);
         if self.is_dynamic() && self.update_concrete_type(concrete_type) {
-          if let Some(graph) = NodeGraphMeta::get(ui) {
-            graph.update_output(OutputId::new(id, N));
+          if let Some(graph) = &graph {
+            graph.update_output(output_id);
           }
         }
         if !details {
-          ui.add(NodeSocket::output(id, N, def, self.concrete_type));
+          // Prefer the type inferred from the whole graph, it doesn't lag behind connection changes.
+          let dt = graph
+            .and_then(|g| g.inferred_type(&output_id))
+            .or(self.concrete_type);
+          ui.add(NodeSocket::output(id, N, def, dt));
         }
         ui.label(&def.name);
       });
@@ -649,4 +1064,125 @@ mod test {
     let test = TestOutput::default();
     eprintln!("{test:?}");
   }
+
+  #[cfg(feature = "egui")]
+  #[test]
+  fn swatch_ui() {
+    let ctx = egui::Context::default();
+    let _ = ctx.run(Default::default(), |ctx| {
+      egui::CentralPanel::default().show(ctx, |ui| {
+        // Colors are shown as a swatch of the requested size.
+        let swatch = ui.scope(|ui| Value::Vec4(Vec4::ONE).swatch_ui(ui, 16.0));
+        assert_eq!(swatch.response.rect.size(), egui::vec2(16.0, 16.0));
+        // Other values are shown as text.
+        let text = ui.scope(|ui| Value::Mat2(Mat2::IDENTITY).swatch_ui(ui, 16.0));
+        assert!(text.response.rect.width() > 16.0);
+      });
+    });
+  }
+
+  #[test]
+  fn value_convert() -> Result<()> {
+    // Scalars.
+    assert_eq!(Value::I32(3).convert(DataType::U32)?, Value::U32(3));
+    assert_eq!(Value::F32(2.5).convert(DataType::F32)?, Value::F32(2.5));
+    assert_eq!(
+      Value::F32(2.0).convert(DataType::Vec3)?,
+      Value::Vec3(Vec3::splat(2.0))
+    );
+    // Vectors.
+    assert_eq!(
+      Value::Vec2(Vec2::new(1., 2.)).convert(DataType::Vec4)?,
+      Value::Vec4(Vec4::new(1., 2., 0., 1.))
+    );
+    assert_eq!(
+      Value::Vec4(Vec4::new(1., 2., 3., 4.)).convert(DataType::Vec3)?,
+      Value::Vec3(Vec3::new(1., 2., 3.))
+    );
+    assert_eq!(
+      Value::Vec3(Vec3::new(4., 5., 6.)).convert(DataType::I32)?,
+      Value::I32(4)
+    );
+    assert_eq!(
+      Value::Vec3(Vec3::ONE).convert(DataType::Dynamic)?,
+      Value::Vec3(Vec3::ONE)
+    );
+    // Matrixes.
+    assert_eq!(
+      Value::Mat4(Mat4::IDENTITY).convert(DataType::Mat3)?,
+      Value::Mat3(Mat3::IDENTITY)
+    );
+
+    // Unsupported conversions.
+    assert!(Value::Mat2(Mat2::IDENTITY).convert(DataType::Vec2).is_err());
+    assert!(Value::Mat2(Mat2::IDENTITY).convert(DataType::Mat4).is_err());
+    assert!(Value::Vec3(Vec3::ONE).convert(DataType::Mat3).is_err());
+    assert!(Value::F32(1.0).convert(DataType::Texture2D).is_err());
+    assert!(Value::Mat3(Mat3::IDENTITY)
+      .convert(DataType::DynamicVector)
+      .is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn value_math() -> Result<()> {
+    // `map` keeps the type.
+    assert_eq!(Value::F32(-2.0).map(f32::abs)?, Value::F32(2.0));
+    assert_eq!(
+      Value::Vec2(Vec2::new(1.5, -0.5)).map(f32::floor)?,
+      Value::Vec2(Vec2::new(1.0, -1.0))
+    );
+    assert_eq!(
+      Value::Mat2(Mat2::IDENTITY).map(|v| v * 2.0)?,
+      Value::Mat2(Mat2::from_diagonal(Vec2::splat(2.0)))
+    );
+    assert!(Value::Texture2D(Default::default()).map(f32::abs).is_err());
+
+    // `zip_map` applies scalars to each component.
+    let v = Value::Vec3(Vec3::new(1., 2., 3.));
+    assert_eq!(
+      v.zip_map(&Value::F32(1.0), |a, b| a + b)?,
+      Value::Vec3(Vec3::new(2., 3., 4.))
+    );
+    assert_eq!(
+      Value::F32(10.0).zip_map(&v, |a, b| a - b)?,
+      Value::Vec3(Vec3::new(9., 8., 7.))
+    );
+    assert_eq!(
+      v.zip_map(&v, |a, b| a * b)?,
+      Value::Vec3(Vec3::new(1., 4., 9.))
+    );
+    assert!(v.zip_map(&Value::Vec2(Vec2::ONE), |a, b| a + b).is_err());
+
+    // `mul` uses matrix multiplication for matrixes.
+    let m = Mat2::from_cols(Vec2::new(1., 2.), Vec2::new(3., 4.));
+    let x = Vec2::new(1., 1.);
+    assert_eq!(
+      Value::Mat2(m).mul(&Value::Vec2(x))?,
+      Value::Vec2(Vec2::new(4., 6.))
+    );
+    assert_eq!(
+      Value::Vec2(x).mul(&Value::Mat2(m))?,
+      Value::Vec2(Vec2::new(3., 7.))
+    );
+    assert_eq!(
+      Value::Mat2(m).mul(&Value::Mat2(Mat2::IDENTITY))?,
+      Value::Mat2(m)
+    );
+    assert_eq!(
+      Value::F32(2.0).mul(&Value::Vec2(x))?,
+      Value::Vec2(Vec2::splat(2.0))
+    );
+    assert!(Value::Mat2(m).mul(&Value::Vec3(Vec3::ONE)).is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn multiline_text_param() {
+    let (name, def) = ParameterDefinition::multiline_text("code", "a\nb");
+    assert_eq!(name, "Code");
+    assert_eq!(def.default_value(), ParameterValue::Text("a\nb".into()));
+    assert!(def.check(ParameterValue::Text("x".into())).is_ok());
+    assert!(def.check(ParameterValue::Selected("x".into())).is_err());
+  }
 }
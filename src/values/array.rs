@@ -0,0 +1,85 @@
+use anyhow::{anyhow, Result};
+
+use crate::*;
+
+/// A constant array of floats.  Compiled to an `array<f32, N>` literal.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct F32Array(pub Vec<f32>);
+
+impl F32Array {
+  pub fn new(values: Vec<f32>) -> Self {
+    Self(values)
+  }
+
+  /// Get the value at `idx`.  The index is clamped to the array, empty arrays return `0.0`.
+  pub fn get(&self, idx: i64) -> f32 {
+    match self.0.len() {
+      0 => 0.0,
+      len => self.0[idx.clamp(0, len as i64 - 1) as usize],
+    }
+  }
+
+  pub fn compile(&self) -> Result<String> {
+    if self.0.is_empty() {
+      return Err(anyhow!("Empty arrays can't be compiled"));
+    }
+    let values: Vec<String> = self.0.iter().map(|v| format!("{v:?}")).collect();
+    Ok(format!(
+      "array<f32, {}>({})",
+      self.0.len(),
+      values.join(", ")
+    ))
+  }
+}
+
+impl ValueType for F32Array {
+  fn clone_value(&self) -> Box<dyn ValueType> {
+    Box::new(self.clone())
+  }
+
+  fn to_value(&self) -> Value {
+    Value::F32Array(self.clone())
+  }
+
+  fn set_value(&mut self, value: Value) -> Result<()> {
+    match value {
+      Value::F32Array(v) => {
+        *self = v;
+        Ok(())
+      }
+      _ => Err(anyhow!("Expected a F32Array got: {value:?}")),
+    }
+  }
+
+  fn data_type(&self) -> DataType {
+    DataType::F32Array
+  }
+
+  #[cfg(feature = "egui")]
+  fn ui(&mut self, ui: &mut egui::Ui) -> bool {
+    let mut changed = false;
+    ui.vertical(|ui| {
+      let mut remove = None;
+      for (idx, val) in self.0.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+          ui.label(format!("[{idx}]"));
+          if ui.add(egui::DragValue::new(val).speed(0.1)).changed() {
+            changed = true;
+          }
+          if ui.small_button("-").clicked() {
+            remove = Some(idx);
+          }
+        });
+      }
+      if let Some(idx) = remove {
+        self.0.remove(idx);
+        changed = true;
+      }
+      if ui.small_button("+").clicked() {
+        self.0.push(self.0.last().copied().unwrap_or_default());
+        changed = true;
+      }
+    });
+    changed
+  }
+}
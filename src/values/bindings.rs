@@ -271,12 +271,12 @@ impl ValueType for UV {
   #[cfg(feature = "egui")]
   fn ui(&mut self, ui: &mut egui::Ui) -> bool {
     egui::ComboBox::from_id_source("UV Channel")
-      .selected_text(format!("{:?}", self.1))
+      .selected_text(self.1.label())
       .show_ui(ui, |ui| {
-        ui.selectable_value(&mut self.1, UvChannel::UV0, "UV0")
-          | ui.selectable_value(&mut self.1, UvChannel::UV1, "UV1")
-          | ui.selectable_value(&mut self.1, UvChannel::UV2, "UV2")
-          | ui.selectable_value(&mut self.1, UvChannel::UV3, "UV3")
+        ui.selectable_value(&mut self.1, UvChannel::UV0, UvChannel::UV0.label())
+          | ui.selectable_value(&mut self.1, UvChannel::UV1, UvChannel::UV1.label())
+          | ui.selectable_value(&mut self.1, UvChannel::UV2, UvChannel::UV2.label())
+          | ui.selectable_value(&mut self.1, UvChannel::UV3, UvChannel::UV3.label())
       })
       .inner
       .map(|resp| resp.changed())
@@ -19,7 +19,7 @@ impl ValueType for bool {
     let val: u32 = match value {
       Value::I32(v) => v as _,
       Value::U32(v) => v,
-      Value::F32(v) => v as _,
+      Value::F32(v) | Value::F16(v) => v as _,
       Value::Vec2(v) => v.x as _,
       Value::Vec3(v) => v.x as _,
       Value::Vec4(v) => v.x as _,
@@ -58,7 +58,7 @@ impl ValueType for i32 {
         *self = v as _;
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = v as _;
         Ok(())
       }
@@ -107,7 +107,7 @@ impl ValueType for u32 {
         *self = v;
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = v as _;
         Ok(())
       }
@@ -156,7 +156,7 @@ impl ValueType for f32 {
         *self = v as _;
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = v;
         Ok(())
       }
@@ -9,6 +9,7 @@ pub enum DataTypeClass {
   Matrix,
   Dynamic,
   Texture,
+  Array,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -16,6 +17,8 @@ pub enum DataType {
   I32,
   U32,
   F32,
+  /// Half-precision float.  Requires the `shader-f16` feature on the GPU.
+  F16,
   Vec2,
   Vec3,
   Vec4,
@@ -29,9 +32,55 @@ pub enum DataType {
   Texture2DArray,
   Texture3D,
   Cubemap,
+  /// Constant array of `f32` values.
+  F32Array,
+}
+
+/// How a value is converted when connecting an output to an input of a different data type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Conversion {
+  /// Same data type (or a dynamic type), the value is passed as-is.
+  Identity,
+  /// The value is converted without dropping components.  For example `F32 -> Vec3`
+  /// splats the value and `Vec2 -> Vec4` pads the missing components.
+  Lossless,
+  /// Components or precision are dropped.  For example `Vec4 -> Vec2`, `F32 -> I32`
+  /// or `Mat4 -> Mat3`.
+  Lossy,
+  /// The value can't be converted.
+  Unsupported,
+}
+
+impl Conversion {
+  /// Returns true if the conversion is possible.
+  pub fn is_supported(&self) -> bool {
+    *self != Self::Unsupported
+  }
 }
 
 impl DataType {
+  /// All data types.
+  pub const ALL: [DataType; 18] = [
+    Self::I32,
+    Self::U32,
+    Self::F32,
+    Self::F16,
+    Self::Vec2,
+    Self::Vec3,
+    Self::Vec4,
+    Self::Mat2,
+    Self::Mat3,
+    Self::Mat4,
+    Self::Dynamic,
+    Self::DynamicVector,
+    Self::DynamicMatrix,
+    Self::Texture2D,
+    Self::Texture2DArray,
+    Self::Texture3D,
+    Self::Cubemap,
+    Self::F32Array,
+  ];
+
   /// Is this data type dynamic.
   pub const fn is_dynamic(&self) -> bool {
     match self {
@@ -48,6 +97,7 @@ impl DataType {
       Self::I32 => DataTypeClass::Scalar,
       Self::U32 => DataTypeClass::Scalar,
       Self::F32 => DataTypeClass::Scalar,
+      Self::F16 => DataTypeClass::Scalar,
       Self::Vec2 => DataTypeClass::Vector,
       Self::Vec3 => DataTypeClass::Vector,
       Self::Vec4 => DataTypeClass::Vector,
@@ -61,6 +111,7 @@ impl DataType {
       Self::Texture2DArray => DataTypeClass::Texture,
       Self::Texture3D => DataTypeClass::Texture,
       Self::Cubemap => DataTypeClass::Texture,
+      Self::F32Array => DataTypeClass::Array,
     }
   }
 
@@ -70,6 +121,7 @@ impl DataType {
       Self::I32 => Value::I32(Default::default()),
       Self::U32 => Value::U32(Default::default()),
       Self::F32 => Value::F32(Default::default()),
+      Self::F16 => Value::F16(Default::default()),
       Self::Vec2 => Value::Vec2(Default::default()),
       Self::Vec3 => Value::Vec3(Default::default()),
       Self::Vec4 => Value::Vec4(Default::default()),
@@ -83,6 +135,7 @@ impl DataType {
       Self::Texture2DArray => Value::Texture2DArray(Default::default()),
       Self::Texture3D => Value::Texture3D(Default::default()),
       Self::Cubemap => Value::Cubemap(Default::default()),
+      Self::F32Array => Value::F32Array(Default::default()),
     }
   }
 
@@ -93,6 +146,7 @@ impl DataType {
       Self::I32 => egui::Color32::LIGHT_BLUE,
       Self::U32 => egui::Color32::LIGHT_BLUE,
       Self::F32 => egui::Color32::LIGHT_BLUE,
+      Self::F16 => egui::Color32::LIGHT_BLUE,
       Self::Vec2 => egui::Color32::GREEN,
       Self::Vec3 => egui::Color32::YELLOW,
       Self::Vec4 => egui::Color32::LIGHT_RED,
@@ -106,138 +160,111 @@ impl DataType {
       Self::Texture2DArray => egui::Color32::RED,
       Self::Texture3D => egui::Color32::RED,
       Self::Cubemap => egui::Color32::RED,
+      Self::F32Array => egui::Color32::LIGHT_GREEN,
     }
   }
 
-  /// Check if the data type is compatible.
+  /// Number of components of a scalar, vector or matrix (columns * rows).
+  pub const fn components(&self) -> Option<usize> {
+    match self {
+      Self::I32 | Self::U32 | Self::F32 | Self::F16 => Some(1),
+      Self::Vec2 => Some(2),
+      Self::Vec3 => Some(3),
+      Self::Vec4 | Self::Mat2 => Some(4),
+      Self::Mat3 => Some(9),
+      Self::Mat4 => Some(16),
+      _ => None,
+    }
+  }
+
+  /// Classify the conversion of a value from this data type to `to`.
+  ///
+  /// This matches the conversions supported by `CompiledValue::convert`.  Dynamic
+  /// types are passed as-is, they get resolved to concrete types before compiling.
+  pub fn conversion(&self, to: &DataType) -> Conversion {
+    use DataTypeClass::*;
+    if self == to {
+      return Conversion::Identity;
+    }
+    match (self.class(), to.class()) {
+      (Texture | Array, _) | (_, Texture | Array) => Conversion::Unsupported,
+      (Matrix, Scalar | Vector) | (Scalar | Vector, Matrix) => Conversion::Unsupported,
+      _ if self.is_dynamic() || to.is_dynamic() => Conversion::Identity,
+      (Scalar, Scalar) => match (self, to) {
+        // Integers and half floats to float.
+        (Self::I32 | Self::U32 | Self::F16, Self::F32) => Conversion::Lossless,
+        // Float truncation, reduced precision or integer sign change.
+        _ => Conversion::Lossy,
+      },
+      // Splat the scalar.
+      (Scalar, Vector) => Conversion::Lossless,
+      // Only the first component is used.
+      (Vector, Scalar) => Conversion::Lossy,
+      (Vector, Vector) | (Matrix, Matrix) => {
+        let (from, to) = (self.components(), to.components());
+        if from < to {
+          if self.class() == Matrix {
+            // Promoting matrices isn't supported.
+            Conversion::Unsupported
+          } else {
+            // Pad the missing components.
+            Conversion::Lossless
+          }
+        } else {
+          Conversion::Lossy
+        }
+      }
+      _ => Conversion::Unsupported,
+    }
+  }
+  /// Describe the data lost when converting from this data type to `to`.
+  ///
+  /// Returns `None` for lossless conversions.
+  pub fn lossy_warning(&self, to: &DataType) -> Option<String> {
+    if self.conversion(to) != Conversion::Lossy {
+      return None;
+    }
+    match (self.components(), to.components()) {
+      (Some(from_len), Some(to_len)) if from_len > to_len => {
+        let dropped = from_len - to_len;
+        let s = if dropped == 1 { "" } else { "s" };
+        Some(format!(
+          "{dropped} component{s} dropped ({self:?} -> {to:?})"
+        ))
+      }
+      _ => Some(format!("Lossy conversion ({self:?} -> {to:?})")),
+    }
+  }
+
+  /// Check if an input of this data type accepts an output of the `other` data type.
+  ///
+  /// Defined by `DataType::conversion`, so only connections that can be compiled are allowed.
   pub fn is_compatible(&self, other: &DataType) -> bool {
-    // Same data type, no conversion.
-    if self == other {
-      return true;
+    other.conversion(self).is_supported()
+  }
+}
+
+/// How strict the compatibility check is when connecting an output to an input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ConnectionPolicy {
+  /// Allow any connection that can be converted.  See `DataType::is_compatible`.
+  #[default]
+  Permissive,
+  /// Only allow connections between data types of the same class.
+  ///
+  /// Dynamic inputs/outputs still accept any compatible type.
+  Strict,
+}
+
+impl ConnectionPolicy {
+  /// Check if the two data types can be connected under this policy.
+  pub fn is_compatible(&self, a: &DataType, b: &DataType) -> bool {
+    if !a.is_compatible(b) {
+      return false;
     }
     match self {
-      Self::I32 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::U32 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::F32 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::Vec2 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::Vec3 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::Vec4 => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::Mat2 => match other {
-        Self::Mat2 => true,
-        Self::Dynamic => true,
-        Self::DynamicMatrix => true,
-        _ => false,
-      },
-      Self::Mat3 => match other {
-        Self::Mat2 => true,
-        Self::Mat3 => true,
-        Self::Dynamic => true,
-        Self::DynamicMatrix => true,
-        _ => false,
-      },
-      Self::Mat4 => match other {
-        Self::Mat2 => true,
-        Self::Mat3 => true,
-        Self::Mat4 => true,
-        Self::Dynamic => true,
-        Self::DynamicMatrix => true,
-        _ => false,
-      },
-      Self::Dynamic => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Mat2 => true,
-        Self::Mat3 => true,
-        Self::Mat4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        Self::DynamicMatrix => true,
-        _ => false,
-      },
-      Self::DynamicVector => match other {
-        Self::I32 => true,
-        Self::U32 => true,
-        Self::F32 => true,
-        Self::Vec2 => true,
-        Self::Vec3 => true,
-        Self::Vec4 => true,
-        Self::Dynamic => true,
-        Self::DynamicVector => true,
-        _ => false,
-      },
-      Self::DynamicMatrix => match other {
-        Self::Mat2 => true,
-        Self::Mat3 => true,
-        Self::Mat4 => true,
-        Self::Dynamic => true,
-        Self::DynamicMatrix => true,
-        _ => false,
-      },
-      _ => false,
+      Self::Permissive => true,
+      Self::Strict => a.is_dynamic() || b.is_dynamic() || a.class() == b.class(),
     }
   }
 }
@@ -262,6 +289,9 @@ pub trait ValueType: core::fmt::Debug {
       Value::F32(val) => {
         format!("{val:?}")
       }
+      Value::F16(val) => {
+        format!("{val:?}h")
+      }
       Value::Vec2(v) => {
         format!("vec2<f32>({:?}, {:?})", v.x, v.y)
       }
@@ -305,6 +335,7 @@ pub trait ValueType: core::fmt::Debug {
         // TODO: Convert to wgsl syntax.
         format!("vec4<f32>(0.5, 0.5, 0., 1.)")
       }
+      Value::F32Array(v) => v.compile()?,
     };
     Ok(CompiledValue {
       value,
@@ -332,3 +363,61 @@ impl Clone for Box<dyn ValueType> {
     self.clone_value()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn conversion() {
+    use Conversion::*;
+    assert_eq!(DataType::Vec3.conversion(&DataType::Vec3), Identity);
+    assert_eq!(DataType::F32.conversion(&DataType::Vec3), Lossless);
+    assert_eq!(DataType::Vec2.conversion(&DataType::Vec4), Lossless);
+    assert_eq!(DataType::Vec4.conversion(&DataType::Vec2), Lossy);
+    assert_eq!(DataType::F32.conversion(&DataType::I32), Lossy);
+    assert_eq!(DataType::Mat4.conversion(&DataType::Mat3), Lossy);
+    assert_eq!(DataType::Mat2.conversion(&DataType::Mat3), Unsupported);
+    assert_eq!(DataType::Vec4.conversion(&DataType::Mat2), Unsupported);
+    assert_eq!(
+      DataType::Texture2D.conversion(&DataType::Texture3D),
+      Unsupported
+    );
+    assert_eq!(
+      DataType::Vec3.conversion(&DataType::DynamicVector),
+      Identity
+    );
+  }
+
+  #[test]
+  fn lossy_warning() {
+    assert_eq!(
+      DataType::Vec4.lossy_warning(&DataType::Vec2).as_deref(),
+      Some("2 components dropped (Vec4 -> Vec2)")
+    );
+    assert_eq!(
+      DataType::Vec2.lossy_warning(&DataType::F32).as_deref(),
+      Some("1 component dropped (Vec2 -> F32)")
+    );
+    assert_eq!(
+      DataType::F32.lossy_warning(&DataType::I32).as_deref(),
+      Some("Lossy conversion (F32 -> I32)")
+    );
+    assert_eq!(DataType::Vec2.lossy_warning(&DataType::Vec4), None);
+    assert_eq!(DataType::Vec3.lossy_warning(&DataType::Vec3), None);
+  }
+
+  #[test]
+  fn compatible_types_are_convertible() {
+    for input in DataType::ALL {
+      for output in DataType::ALL {
+        if input.is_compatible(&output) {
+          assert!(
+            output.conversion(&input).is_supported(),
+            "{output:?} -> {input:?} is compatible, but not convertible"
+          );
+        }
+      }
+    }
+  }
+}
@@ -99,7 +99,7 @@ impl ValueType for Vec2 {
         *self = (v as f32, 0.).into();
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = (v, 0.).into();
         Ok(())
       }
@@ -148,7 +148,7 @@ impl ValueType for Vec3 {
         *self = (v as f32, 0., 0.).into();
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = (v, 0., 0.).into();
         Ok(())
       }
@@ -197,7 +197,7 @@ impl ValueType for Vec4 {
         *self = (v as f32, 0., 0., 1.).into();
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = (v, 0., 0., 1.).into();
         Ok(())
       }
@@ -283,7 +283,7 @@ impl ValueType for DynamicVector {
         };
         Ok(())
       }
-      Value::F32(v) => {
+      Value::F32(v) | Value::F16(v) => {
         *self = Self {
           data: (v, 0., 0., 1.).into(),
           size: DynamicSize::D1,